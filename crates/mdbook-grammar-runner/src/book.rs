@@ -1,83 +1,437 @@
 use crate::{
-    code::{find_rules, parse_code},
-    iter::RecursiveIterable,
+    Config,
+    cache::{Cache, page_key},
+    code::{
+        Context, find_rules, overload_lists, panic_block, parse_code, rule_list,
+    },
+    index::{add_index, rule_index},
+    iter::{RecursiveIterable, RecursiveIterableMut},
+    lint::{Diagnostic, Level, check},
+    metas::anchor_metas,
     mode::parse_mode,
+    preview::preview_assets,
+    railroad::railroad_diagrams,
+    search::{page_metadata, write_search_index},
+    style::stylesheet,
+    timings::{ChapterTimings, Timings, timed},
+    tokens::{TOKENS, token_table},
 };
-use ecow::EcoString;
+use ecow::{EcoString, eco_format};
 use mdbook::book::Book;
-use mdbook_grammar_syntax::{SyntaxNode, parse};
+use mdbook_grammar_syntax::{
+    ParseOptions, SourceMap, SyntaxNode, parameterized_rules,
+    parse_with_options, span,
+};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
 use unscanny::Scanner;
 
-pub fn run(book: &mut Book, root: &str) {
-    let mut pages: Vec<Page> = Vec::new();
+/// Render the grammar blocks of the book, returning the problems found.
+///
+/// If enabled in the configuration, the time spent in each phase is returned
+/// last, as a note.
+pub fn run(book: &mut Book, config: &Config) -> Vec<Diagnostic> {
+    let mut timings = Timings::default();
+    let pages = read_pages(book, config, &mut timings);
+
+    let rules = timed(&mut timings.registry, || find_rules(&pages, config));
+    let mut diagnostics = timed(&mut timings.linting, || {
+        check(&pages, &rules.imports, config)
+    });
+    if let Some(path) = &config.search_index {
+        if let Err(err) = write_search_index(path, &pages, config) {
+            diagnostics.push(Diagnostic {
+                level: Level::Warn,
+                lint: None,
+                message: eco_format!("cannot write the search index: {err}"),
+                path: path.display().to_string().into(),
+                line: None,
+            });
+        }
+    }
+
+    let ctx = Context {
+        config,
+        rules: &rules,
+        page: None,
+        block: None,
+        params: &[],
+    };
+
+    let mut cache = config.cache.as_ref().map(Cache::new);
+    if let Some(cache) = &cache {
+        diagnostics.extend(cache.track_rules(&pages, &rules));
+    }
+    let mut parsed_pages =
+        pages
+            .iter()
+            .zip(&mut timings.chapters)
+            .map(|(page, timings)| {
+                timed(&mut timings.rendering, || {
+                    let Some(cache) = &mut cache else {
+                        return render_page(&ctx, page);
+                    };
+
+                    let key = page_key(&ctx, page);
+                    cache.get(key).unwrap_or_else(|| {
+                        let content = render_page(&ctx, page);
+                        cache.put(key, &content);
+                        content
+                    })
+                })
+            });
+
+    book.recur_for_each_mut(&mut |chapter| {
+        chapter.content = parsed_pages.next().unwrap();
+    });
+
+    // The index is rebuilt on every run, since it depends on all chapters.
+    if let Some(path) = &config.index {
+        add_index(book, config, path, rule_index(&ctx, &pages));
+    }
+
+    if let Some(cache) = cache {
+        cache.prune();
+    }
+
+    if config.timings {
+        diagnostics.push(Diagnostic {
+            level: Level::Allow,
+            lint: None,
+            message: eco_format!(
+                "time spent per phase\n{}",
+                timings.to_string().trim_end()
+            ),
+            path: EcoString::new(),
+            line: None,
+        });
+    }
+
+    diagnostics
+}
+
+/// Split the chapters of the book into pages of text and grammar blocks,
+/// recording the time spent in `timings`.
+pub fn read_pages(
+    book: &Book,
+    config: &Config,
+    timings: &mut Timings,
+) -> Vec<Page> {
+    let mut pages = Vec::new();
+
+    // A reference takes arguments only if its rule is defined with
+    // parameters, maybe in another chapter, so these rules are found first.
+    let mut parse_options = config.parse_options();
+    for chapter in book.recur_iter() {
+        for fence in fences(&chapter.content, config) {
+            if !fence.ignored() {
+                parse_options
+                    .parameterized
+                    .extend(parameterized_rules(fence.code));
+            }
+        }
+    }
 
     for chapter in book.recur_iter() {
+        let href: EcoString =
+            chapter.path.as_ref().unwrap().to_str().unwrap().into();
+        let mut parsing = Duration::ZERO;
+        let start = Instant::now();
+        let items = parse_content(
+            &chapter.content,
+            config,
+            &parse_options,
+            &mut parsing,
+        );
+        timings.chapters.push(ChapterTimings {
+            path: href.clone(),
+            scanning: start.elapsed().saturating_sub(parsing),
+            parsing,
+            rendering: Duration::ZERO,
+        });
         pages.push(Page {
-            href: chapter.path.as_ref().unwrap().to_str().unwrap().into(),
-            items: parse_content(chapter.content.clone()),
+            href,
+            name: chapter.name.as_str().into(),
+            items,
         });
     }
 
-    let rules = find_rules(&pages, root);
+    pages
+}
 
-    let mut parsed_pages = pages.iter().map(|page| {
-        page.items
-            .iter()
-            .map(|item| match item {
-                | Item::Text(text) => parse_mode(text),
-                | Item::Code(code) => parse_code(&rules, code),
-            })
-            .collect::<Vec<_>>()
-            .join("")
-    });
+/// Render the content of a page.
+pub fn render_page(ctx: &Context, page: &Page) -> String {
+    let ctx = &Context {
+        page: Some(page),
+        ..*ctx
+    };
+    let mut content = String::new();
+    if page.items.iter().any(|item| matches!(item, Item::Code(_))) {
+        if let Some(palette) = ctx.config.palette {
+            content += &format!("<style>\n{}</style>\n\n", stylesheet(palette));
+        }
+        if ctx.config.preview {
+            content += &preview_assets();
+        }
+    }
+    if ctx.config.rule_list {
+        content += &rule_list(page, ctx.config);
+    }
 
-    for chapter in book.recur_iter_mut() {
-        chapter.content = parsed_pages.next().unwrap();
+    let mut listed = HashSet::new();
+    for item in &page.items {
+        match item {
+            | Item::Text(text) => {
+                let mut text = Cow::Borrowed(text.as_str());
+                if text.contains(TOKENS) {
+                    let table = token_table(&ctx.rules.terminals);
+                    text = text.replace(TOKENS, &table).into();
+                }
+                if ctx.config.metas.as_ref() == Some(&page.href) {
+                    text = anchor_metas(ctx.config, &page.href, &text).into();
+                }
+                parse_mode(&text, &mut content);
+            },
+            | Item::Raw(text) => content += text,
+            | Item::Code(block) => {
+                content += &isolate(|| parse_code(ctx, block)).unwrap_or_else(
+                    |message| {
+                        let code = block
+                            .code
+                            .recur_iter()
+                            .map(|node| node.text().as_str())
+                            .collect::<String>();
+                        panic_block(&code, &message)
+                    },
+                );
+                if ctx.config.railroad {
+                    content += &railroad_diagrams(ctx, block);
+                }
+                content += &overload_lists(ctx, page, block, &mut listed);
+            },
+        }
+    }
+
+    if ctx.config.metadata {
+        if let Some(metadata) = page_metadata(page, ctx.config) {
+            content += "\n\n";
+            content += &metadata;
+        }
     }
+
+    content
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 pub struct Page {
     pub href: EcoString,
+    /// The name of the chapter.
+    pub name: EcoString,
     pub items: Vec<Item>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Hash)]
 pub enum Item {
     Text(String),
-    Code(SyntaxNode),
+    Code(Block),
+    /// Text kept as it is, like an ignored grammar block.
+    Raw(String),
 }
 
-fn parse_content(content: String) -> Vec<Item> {
-    let mut items = Vec::new();
-    let mut s = Scanner::new(content.as_str());
-    let mut start = s.cursor();
+/// A grammar code block.
+#[derive(Clone, Debug, Hash)]
+pub struct Block {
+    /// The parsed code.
+    pub code: SyntaxNode,
+    /// The options given in the fence info, after the default options of the
+    /// fence tag.
+    pub options: Vec<EcoString>,
+    /// The line of the chapter where the code starts, from 1.
+    pub line: usize,
+}
+
+impl Block {
+    /// The options recognized by the preprocessor.
+    ///
+    /// Options may take a value, like `action=rust`.
+    pub const OPTIONS: &[&str] =
+        &["ignore", "reflow", "verbatim", "action", "start"];
+
+    /// Whether the option is recognized by the preprocessor.
+    pub fn is_recognized(option: &str) -> bool {
+        let name = option.split_once('=').map_or(option, |(name, _)| name);
+        Self::OPTIONS.contains(&name)
+    }
+
+    /// The value of the last option with the given name, like `rust` in
+    /// `action=rust`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find_map(|option| option.strip_prefix(name)?.strip_prefix('='))
+    }
+
+    /// Whether the block should be reflowed by the formatter.
+    ///
+    /// The last of `reflow` and `verbatim` wins over the book configuration.
+    pub fn reflow(&self, config: &Config) -> bool {
+        self.options
+            .iter()
+            .rev()
+            .find_map(|option| match option.as_str() {
+                | "reflow" => Some(true),
+                | "verbatim" => Some(false),
+                | _ => None,
+            })
+            .unwrap_or(config.reflow)
+    }
+}
+
+/// Run the processing of a single block, catching a panic so that a bug only
+/// breaks the block it happens in.
+///
+/// The panic itself is reported by the panic hook; the message is returned to
+/// be shown in place of the block.
+fn isolate<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".into())
+    })
+}
+
+/// A fenced grammar block in the content of a chapter.
+struct Fence<'a> {
+    /// Where the fence starts, at its opening backticks.
+    start: usize,
+    /// Where the code starts, after the info string.
+    code_start: usize,
+    /// Where the fence ends, after its closing backticks.
+    end: usize,
+    code: &'a str,
+    options: Vec<EcoString>,
+}
+
+impl Fence<'_> {
+    /// Whether the block is shown without processing it.
+    fn ignored(&self) -> bool {
+        self.options.iter().any(|option| option == "ignore")
+    }
+}
+
+/// Find the grammar blocks in the content of a chapter, in a single pass over
+/// the content.
+fn fences<'a>(content: &'a str, config: &Config) -> Vec<Fence<'a>> {
+    let mut fences = Vec::new();
+    let mut s = Scanner::new(content);
+
+    loop {
+        // Fences start with backticks, so skip the text up to the next one.
+        s.eat_until('`');
+        if s.done() {
+            break;
+        }
 
-    while !s.done() {
         let mut cs = s;
         let backticks = cs.eat_while('`');
-        if backticks.len() >= 3 && cs.eat_if("syntax\n") {
-            items.push(Item::Text(s.from(start).to_string()));
-            let st = cs.cursor();
-            cs.eat_until(backticks);
-            items.push(Item::Code(parse(cs.from(st))));
-            cs.eat_if(backticks);
-            start = cs.cursor();
-            s = cs;
+        let info = cs.eat_until(|c| c == '\n' || c == '`');
+        let options = match parse_info(info, config) {
+            | Some(options) if backticks.len() >= 3 && cs.at('\n') => options,
+            | _ => {
+                s.eat();
+                continue;
+            },
+        };
+
+        let code_start = span::code_offset(content, s.cursor());
+        cs.jump(code_start);
+        cs.eat_until(backticks);
+        let code = cs.from(code_start);
+        cs.eat_if(backticks);
+        fences.push(Fence {
+            start: s.cursor(),
+            code_start,
+            end: cs.cursor(),
+            code,
+            options,
+        });
+        s = cs;
+    }
+
+    fences
+}
+
+/// Split the content of a chapter into text and grammar blocks.
+///
+/// The time spent parsing the blocks is added to `parsing`.
+fn parse_content(
+    content: &str,
+    config: &Config,
+    parse_options: &ParseOptions,
+    parsing: &mut Duration,
+) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let map = SourceMap::new(content, &[]);
+
+    for fence in fences(content, config) {
+        items.push(Item::Text(content[start..fence.start].to_string()));
+        if fence.ignored() {
+            // Show the notation itself without processing it.
+            items.push(Item::Raw(content[fence.start..fence.end].to_string()));
         } else {
-            s.eat();
+            let code = fence.code;
+            let parsed = timed(parsing, || {
+                isolate(|| parse_with_options(code, parse_options))
+            });
+            items.push(match parsed {
+                | Ok(code) => Item::Code(Block {
+                    code,
+                    options: fence.options,
+                    line: map.line(fence.code_start),
+                }),
+                | Err(message) => Item::Raw(panic_block(code, &message)),
+            });
         }
+        start = fence.end;
     }
 
-    items.push(Item::Text(s.from(start).to_string()));
+    items.push(Item::Text(content[start..].to_string()));
 
     items
 }
 
+/// Parse the info string of a fence, like `syntax` or `ebnf,option`.
+///
+/// Return the options of the block if the fence tag is a grammar language.
+fn parse_info(info: &str, config: &Config) -> Option<Vec<EcoString>> {
+    let mut words = info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty());
+
+    let defaults = config.aliases.get(words.next()?)?;
+    Some(
+        defaults
+            .iter()
+            .cloned()
+            .chain(words.map(Into::into))
+            .collect(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use assert_matches::assert_matches;
+    use mdbook::{BookItem, book::Chapter};
+    use mdbook_grammar_syntax::SyntaxKind;
 
     #[test]
     fn test_parse_content() {
@@ -101,12 +455,157 @@ mod tests {
       hahaha
     "#;
 
-        let items = parse_content(content.to_string());
+        let items = parse_content(
+            content,
+            &Config::default(),
+            &ParseOptions::default(),
+            &mut Duration::default(),
+        );
         assert_eq!(items.len(), 5);
         assert_matches!(items[0], Item::Text(_));
-        assert_matches!(items[1], Item::Code(_));
+        assert_matches!(&items[1], Item::Code(block) => {
+            assert_eq!(block.line, 5);
+        });
         assert_matches!(items[2], Item::Text(_));
-        assert_matches!(items[3], Item::Code(_));
+        assert_matches!(&items[3], Item::Code(block) => {
+            assert_eq!(block.line, 15);
+        });
         assert_matches!(items[4], Item::Text(_));
     }
+
+    #[test]
+    fn test_parse_content_alias() {
+        let mut config = Config::default();
+        config.aliases.insert("ebnf".into(), vec!["default".into()]);
+
+        let content = "```ebnf option\nrule: ;\n```\n\n```bnf\nrule: ;\n```";
+        let items = parse_content(
+            content,
+            &config,
+            &ParseOptions::default(),
+            &mut Duration::default(),
+        );
+        assert_eq!(items.len(), 3);
+        assert_matches!(&items[1], Item::Code(block) => {
+            assert_eq!(block.options, ["default", "option"]);
+        });
+    }
+
+    #[test]
+    fn test_block_reflow() {
+        let mut config = Config::default();
+        config.aliases.insert("ebnf".into(), vec!["reflow".into()]);
+
+        let content = "```ebnf\na: ;\n```\n```ebnf verbatim\na: ;\n```";
+        let items = parse_content(
+            content,
+            &config,
+            &ParseOptions::default(),
+            &mut Duration::default(),
+        );
+        assert_matches!(&items[1], Item::Code(block) => {
+            assert!(block.reflow(&config));
+        });
+        assert_matches!(&items[3], Item::Code(block) => {
+            assert!(!block.reflow(&config));
+        });
+    }
+
+    #[test]
+    fn test_read_pages_arguments() {
+        let mut book = Book::new();
+        for (name, content) in [
+            ("Uses", "```syntax\na: list(b) c(b)*;\nc: ;\n```\n"),
+            ("Lists", "```syntax\nlist(item): item;\n```\n"),
+        ] {
+            book.push_item(BookItem::Chapter(Chapter::new(
+                name,
+                content.into(),
+                format!("{name}.md"),
+                Vec::new(),
+            )));
+        }
+
+        let pages =
+            read_pages(&book, &Config::default(), &mut Timings::default());
+        assert_matches!(&pages[0].items[1], Item::Code(block) => {
+            let arguments = block
+                .code
+                .descendants()
+                .filter(|node| node.kind() == SyntaxKind::Argument)
+                .count();
+            assert_eq!(arguments, 1);
+        });
+    }
+
+    #[test]
+    fn test_read_pages_max_depth() {
+        let mut book = Book::new();
+        book.push_item(BookItem::Chapter(Chapter::new(
+            "Groups",
+            "```syntax\na: (b (c (d)));\n```\n".into(),
+            "groups.md",
+            Vec::new(),
+        )));
+
+        let erroneous = |config: &Config| {
+            let pages = read_pages(&book, config, &mut Timings::default());
+            match &pages[0].items[1] {
+                | Item::Code(block) => block.code.erroneous(),
+                | _ => unreachable!(),
+            }
+        };
+        assert!(!erroneous(&Config::default()));
+        assert!(erroneous(&Config {
+            max_depth: 2,
+            ..Config::default()
+        }));
+    }
+
+    #[test]
+    fn test_run_timings() {
+        let mut book = Book::new();
+        book.push_item(BookItem::Chapter(Chapter::new(
+            "Rules",
+            "```syntax\na: \"b\";\n```\n".into(),
+            "rules.md",
+            Vec::new(),
+        )));
+
+        assert!(run(&mut book.clone(), &Config::default()).is_empty());
+        let config = Config {
+            timings: true,
+            ..Config::default()
+        };
+        let diagnostics = run(&mut book, &config);
+        assert_eq!(diagnostics.len(), 1);
+        let note = diagnostics[0].to_string();
+        assert!(note.starts_with("note: time spent per phase\nchapter"));
+        assert!(note.contains("\nrules.md "));
+        assert!(!diagnostics[0].is_error());
+    }
+
+    #[test]
+    fn test_isolate() {
+        assert_eq!(isolate(|| 1), Ok(1));
+        assert_eq!(
+            isolate(|| -> () { panic!("broken {}", "block") }),
+            Err("broken block".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_content_ignore() {
+        let content = "text\n```syntax,ignore\nrule: ;\n```\n";
+        let items = parse_content(
+            content,
+            &Config::default(),
+            &ParseOptions::default(),
+            &mut Duration::default(),
+        );
+        assert_eq!(items.len(), 3);
+        assert_matches!(&items[1], Item::Raw(text) => {
+            assert_eq!(text, "```syntax,ignore\nrule: ;\n```");
+        });
+    }
 }
@@ -0,0 +1,364 @@
+use crate::{
+    Diagnostic, Level,
+    book::{Item, Page},
+    code::{Context, Rules, page_imports},
+    iter::RecursiveIterable,
+    tokens::TOKENS,
+};
+use ecow::eco_format;
+use mdbook_grammar_syntax::SyntaxKind;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+};
+
+/// A directory of rendered pages, reused while their inputs are unchanged.
+///
+/// The cache is best effort: failing to read or write it only costs a
+/// re-render.
+pub struct Cache {
+    dir: PathBuf,
+    used: HashSet<PathBuf>,
+}
+
+impl Cache {
+    /// Open the cache in the given directory.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let _ = fs::create_dir_all(&dir);
+        Self {
+            dir,
+            used: HashSet::new(),
+        }
+    }
+
+    /// Get the rendered content stored under the key.
+    pub fn get(&mut self, key: u64) -> Option<String> {
+        let path = self.path(key);
+        let content = fs::read_to_string(&path).ok()?;
+        self.used.insert(path);
+        Some(content)
+    }
+
+    /// Store the rendered content under the key.
+    pub fn put(&mut self, key: u64, content: &str) {
+        let path = self.path(key);
+        let _ = fs::write(&path, content);
+        self.used.insert(path);
+    }
+
+    /// Remove the entries not used since the cache was opened.
+    pub fn prune(self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "md")
+                && !self.used.contains(&path)
+            {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Warn about the chapters still referencing rules which were defined in
+    /// the previous build, and record the rules of this one.
+    ///
+    /// Under `mdbook serve`, renaming a rule otherwise silently leaves the
+    /// references of other chapters unresolved.
+    pub fn track_rules(
+        &self,
+        pages: &[Page],
+        rules: &Rules,
+    ) -> Vec<Diagnostic> {
+        let path = self.dir.join("rules.txt");
+        let previous = fs::read_to_string(&path).unwrap_or_default();
+
+        let mut diagnostics = Vec::new();
+        for page in pages {
+            let removed = page
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    | Item::Code(block) => Some(block.code.recur_iter()),
+                    | _ => None,
+                })
+                .flatten()
+                .filter(|node| node.kind() == SyntaxKind::Identifier)
+                .map(|node| node.text())
+                .filter(|&name| {
+                    !rules.is_defined(name)
+                        && previous.lines().any(|line| line == name)
+                })
+                .collect::<BTreeSet<_>>();
+
+            for name in removed {
+                diagnostics.push(Diagnostic {
+                    level: Level::Warn,
+                    lint: None,
+                    message: eco_format!(
+                        "rule `{name}` was removed or renamed since the last \
+                         build, but is still referenced"
+                    ),
+                    path: page.href.clone(),
+                    line: None,
+                });
+            }
+        }
+
+        let names = rules
+            .names
+            .iter()
+            .map(|&symbol| rules.symbols.name(symbol))
+            .collect::<BTreeSet<_>>();
+        let current = names
+            .iter()
+            .map(|name| format!("{name}\n"))
+            .collect::<String>();
+        if previous != current {
+            let _ = fs::write(path, current);
+        }
+
+        diagnostics
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.md"))
+    }
+}
+
+/// The key of a rendered page.
+///
+/// Besides the page itself and the configuration, the key covers the version
+/// of the rules as seen by the page: only the entries of the rules referenced
+/// by the page are hashed, so that a page is reused as long as its links are
+/// the same, even if other rules changed.
+pub fn page_key(ctx: &Context, page: &Page) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    ctx.config.hash(&mut hasher);
+    page.hash(&mut hasher);
+    // Imported grammars change without the page importing them.
+    for path in page_imports(page) {
+        ctx.rules.imports.get(&path).hash(&mut hasher);
+    }
+
+    for item in &page.items {
+        if let Item::Text(text) = item {
+            if text.contains(TOKENS) {
+                ctx.rules.terminals.hash(&mut hasher);
+            }
+        }
+        let Item::Code(block) = item else { continue };
+        for node in block.code.recur_iter() {
+            if node.kind() == SyntaxKind::Meta {
+                ctx.rules.metas.get(node.text()).hash(&mut hasher);
+            }
+            // Names in conditions link to the rules they name.
+            if matches!(
+                node.kind(),
+                SyntaxKind::Identifier | SyntaxKind::Variable
+            ) {
+                let symbol = ctx.rules.symbol(node.text());
+                (
+                    symbol.and_then(|s| ctx.rules.links.get(&s)),
+                    symbol.is_some_and(|s| ctx.rules.names.contains(&s)),
+                    symbol.and_then(|s| ctx.rules.overloads.get(&s)),
+                    symbol.and_then(|s| ctx.rules.chapters.get(&s)),
+                )
+                    .hash(&mut hasher);
+                // Definitions and references show what else is known of the
+                // rules, like badges for deprecated rules and tokens.
+                (
+                    symbol.and_then(|s| ctx.rules.deprecated.get(&s)),
+                    symbol.and_then(|s| ctx.rules.extensions.get(&s)),
+                    symbol.and_then(|s| ctx.rules.arities.get(&s)),
+                    symbol.is_some_and(|s| ctx.rules.tokens.contains(&s)),
+                    symbol.is_some_and(|s| ctx.rules.fragments.contains(&s)),
+                    symbol.is_some_and(|s| ctx.rules.starts.contains(&s)),
+                    symbol.is_some_and(|s| ctx.rules.skipped.contains(&s)),
+                )
+                    .hash(&mut hasher);
+                // Links preview the definitions of the rules.
+                if ctx.config.preview {
+                    (
+                        symbol.and_then(|s| ctx.rules.definitions.get(&s)),
+                        symbol.and_then(|s| ctx.rules.docs.get(&s)),
+                    )
+                        .hash(&mut hasher);
+                }
+                // Definitions list the rules using them.
+                if ctx.config.backlinks {
+                    let users = symbol.and_then(|s| ctx.rules.users.get(&s));
+                    for &user in users.into_iter().flatten() {
+                        (
+                            ctx.rules.symbols.name(user),
+                            ctx.rules.links.get(&user),
+                        )
+                            .hash(&mut hasher);
+                    }
+                }
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, book::Block, code::Rules, symbol::Symbol};
+    use mdbook_grammar_syntax::parse;
+
+    #[test]
+    fn test_track_rules() {
+        let dir = std::env::temp_dir()
+            .join(format!("mdbook-grammar-track-rules-{}", std::process::id()));
+        let cache = Cache::new(&dir);
+        let page = |code: &str| Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(code),
+                options: Vec::new(),
+                line: 1,
+            })],
+        };
+        let rules = |names: &[&str]| {
+            let mut rules = Rules::default();
+            for &name in names {
+                let symbol = rules.symbols.intern(&name.into());
+                rules.names.insert(symbol);
+            }
+            rules
+        };
+
+        let pages = [page("a: b;"), page("b: ;")];
+        assert_eq!(cache.track_rules(&pages, &rules(&["a", "b"])), []);
+
+        let pages = [page("a: b;"), page("c: ;")];
+        let diagnostics = cache.track_rules(&pages, &rules(&["a", "c"]));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "rule `b` was removed or renamed since the last build, but is \
+             still referenced"
+        );
+        assert_eq!(cache.track_rules(&pages, &rules(&["a", "c"])), []);
+
+        // The page referencing `b` changes with what is known of `b`.
+        let config = Config::default();
+        let key = |rules: &Rules| {
+            let ctx = Context {
+                config: &config,
+                rules,
+                page: None,
+                block: None,
+                params: &[],
+            };
+            page_key(&ctx, &pages[0])
+        };
+        let changes: [fn(&mut Rules, Symbol); 7] = [
+            |rules, b| {
+                rules.deprecated.insert(b, None);
+            },
+            |rules, b| {
+                rules
+                    .extensions
+                    .insert(b, vec![("other.md".into(), "b".into())]);
+            },
+            |rules, b| {
+                rules.arities.insert(b, 1);
+            },
+            |rules, b| {
+                rules.tokens.insert(b);
+            },
+            |rules, b| {
+                rules.fragments.insert(b);
+            },
+            |rules, b| rules.starts.push(b),
+            |rules, b| {
+                rules.skipped.insert(b);
+            },
+        ];
+        let original = rules(&["a", "b"]);
+        for change in changes {
+            let mut changed = original.clone();
+            change(&mut changed, original.symbol("b").unwrap());
+            assert_ne!(key(&changed), key(&original));
+        }
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_page_key() {
+        let config = Config::default();
+        let page = Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse("a: b;"),
+                options: Vec::new(),
+                line: 1,
+            })],
+        };
+
+        let mut rules = Rules::default();
+        let key = |rules: &Rules| {
+            let ctx = Context {
+                config: &config,
+                rules,
+                page: None,
+                block: None,
+                params: &[],
+            };
+            page_key(&ctx, &page)
+        };
+        let original = key(&rules);
+
+        let c = rules.symbols.intern(&"c".into());
+        rules.names.insert(c);
+        rules.links.insert(c, "/other.md".into());
+        assert_eq!(key(&rules), original);
+
+        let b = rules.symbols.intern(&"b".into());
+        assert_eq!(key(&rules), original);
+        rules.names.insert(b);
+        assert_ne!(key(&rules), original);
+    }
+
+    #[test]
+    fn test_page_key_imports() {
+        let config = Config::default();
+        let page = Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse("%import \"lexer.syntax\";\na: ident;"),
+                options: Vec::new(),
+                line: 1,
+            })],
+        };
+
+        let mut rules = Rules::default();
+        let mut key = |code: &str| {
+            rules.imports.insert("lexer.syntax".into(), Ok(parse(code)));
+            let ctx = Context {
+                config: &config,
+                rules: &rules,
+                page: None,
+                block: None,
+                params: &[],
+            };
+            page_key(&ctx, &page)
+        };
+        let original = key("ident: \"x\";");
+        assert_eq!(key("ident: \"x\";"), original);
+        assert_ne!(key("ident: \"y\";"), original);
+    }
+}
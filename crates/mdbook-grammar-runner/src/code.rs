@@ -1,162 +1,2483 @@
-use crate::book::{Item, Page};
-use ecow::EcoString;
-use html_escape::encode_safe;
-use mdbook_grammar_syntax::{SyntaxError, SyntaxKind, SyntaxNode};
-use std::collections::HashMap;
+use crate::{
+    Config, Level, Lint,
+    book::{Block, Item, Page},
+    config::{Anchors, Notation, Scope, Unresolved},
+    iter::RecursiveIterable,
+    lint::{
+        complexity_excess, has_annotation, is_extension, is_fragment,
+        is_helper, nested_rules, rule_annotations, rule_complexity, rule_doc,
+        rule_name, rule_params, rule_references, rule_since,
+    },
+    metas::find_metas,
+    search::{definition_line, flat_text},
+    symbol::{Symbol, SymbolTable},
+    tokens::{describe_layout, find_terminals},
+};
+use ecow::{EcoString, eco_format};
+use html_escape::{encode_double_quoted_attribute, encode_safe};
+use mdbook::utils::normalize_id;
+use mdbook_grammar_syntax::{
+    NegatedClass, ParseOptions, Repeat, SourceMap, SyntaxError, SyntaxKind,
+    SyntaxNode, format, parse_with_options, range_bounds, signature, unescape,
+    unescape_class,
+};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    path::Path,
+};
 
-type Rules = HashMap<EcoString, EcoString>;
+/// The state of the rendering of grammar blocks.
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    pub config: &'a Config,
+    pub rules: &'a Rules,
+    /// The page being rendered, if any.
+    pub page: Option<&'a Page>,
+    /// The block being rendered, if any.
+    pub block: Option<&'a Block>,
+    /// The parameters of the rule being rendered.
+    pub params: &'a [EcoString],
+}
+
+impl Context<'_> {
+    /// The language of the code in actions and parameters, if any.
+    pub fn action_language(&self) -> Option<&str> {
+        self.block
+            .and_then(|block| block.value("action"))
+            .or(self.config.action_language.as_deref())
+    }
+}
+
+/// The rules defined in the book.
+///
+/// Rules are keyed by the symbols of their names, interned in `symbols`.
+#[derive(Clone, Debug, Default)]
+pub struct Rules {
+    /// The names of the rules, and of the rules named by pragmas.
+    pub symbols: SymbolTable,
+    /// The links to the definitions of rules.
+    pub links: HashMap<Symbol, EcoString>,
+    /// All defined rules, including the ones not linked.
+    pub names: HashSet<Symbol>,
+    /// The terminals used in the book, as written.
+    pub terminals: BTreeSet<EcoString>,
+    /// The chapters defining overloaded rules, as pairs of path and name.
+    pub overloads: HashMap<Symbol, Vec<(EcoString, EcoString)>>,
+    /// The links to the definitions of metas, by meta.
+    pub metas: HashMap<EcoString, EcoString>,
+    /// The paths of the chapters defining each public rule, with the anchor
+    /// of the definition.
+    pub chapters: HashMap<Symbol, Vec<(EcoString, EcoString)>>,
+    /// The number of parameters of each rule taking some.
+    pub arities: HashMap<Symbol, usize>,
+    /// The documentation of each documented rule, from its first documented
+    /// definition.
+    pub docs: HashMap<Symbol, String>,
+    /// The definition of each rule on a single line, from its first
+    /// definition.
+    pub definitions: HashMap<Symbol, String>,
+    /// The rules annotated with `@token`.
+    pub tokens: HashSet<Symbol>,
+    /// The rules only composing tokens, like `fragment a: b;`.
+    pub fragments: HashSet<Symbol>,
+    /// The notes of the rules annotated with `@deprecated`, if given, like
+    /// `use b` for `@deprecated(use b)`.
+    pub deprecated: HashMap<Symbol, Option<EcoString>>,
+    /// The chapters adding alternatives to each rule with `+=`, with the
+    /// anchor of the extension.
+    pub extensions: HashMap<Symbol, Vec<(EcoString, EcoString)>>,
+    /// The public rules referencing each rule, other than itself, in the order
+    /// of the book.
+    pub users: HashMap<Symbol, Vec<Symbol>>,
+    /// The start rules, given by `%start` pragmas.
+    pub starts: Vec<Symbol>,
+    /// The rules skipped between tokens, given by `%skip` pragmas.
+    pub skipped: HashSet<Symbol>,
+    /// The grammars imported with `%import`, read once for the whole book.
+    pub imports: Imports,
+}
+
+/// Grammars imported with `%import`, by path relative to the source
+/// directory, parsed or with the error reading them.
+pub type Imports = BTreeMap<EcoString, Result<SyntaxNode, EcoString>>;
+
+impl Rules {
+    /// The symbol of a rule name, if it names a rule.
+    pub fn symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name)
+    }
+
+    /// Whether a rule of this name is defined.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.symbol(name).is_some_and(|s| self.names.contains(&s))
+    }
+
+    /// The link of a reference to a rule from a chapter.
+    ///
+    /// When rules are scoped to chapters, a definition in the chapter itself
+    /// is preferred to the one of the book.
+    pub fn link(
+        &self,
+        config: &Config,
+        chapter: Option<&EcoString>,
+        name: &str,
+    ) -> Option<EcoString> {
+        if let (Scope::Chapter, Some(chapter)) = (config.scope, chapter) {
+            if let Some(anchor) = self.anchor(chapter, name) {
+                return Some(config.link(chapter, anchor).into());
+            }
+        }
+
+        self.links.get(&self.symbol(name)?).cloned()
+    }
 
-pub fn find_rules(pages: &Vec<Page>, root: &str) -> Rules {
-    let mut rules: Rules = HashMap::new();
+    /// The anchor of the first definition of a public rule in a chapter.
+    pub fn anchor(&self, chapter: &str, name: &str) -> Option<&EcoString> {
+        self.chapters
+            .get(&self.symbol(name)?)?
+            .iter()
+            .find(|(c, _)| c == chapter)
+            .map(|(_, anchor)| anchor)
+    }
+}
+
+pub fn find_rules(pages: &[Page], config: &Config) -> Rules {
+    let mut rules = Rules::default();
 
     for page in pages {
-        for item in &page.items {
-            if let Item::Code(code) = item {
-                // Find rule definitions in code blocks.
-                debug_assert_eq!(code.kind(), SyntaxKind::Root);
-
-                for node in code.children() {
-                    if node.kind() == SyntaxKind::Rule && !node.erroneous() {
-                        // Found a rule definition.
-                        let Some(name) = node
-                            .children()
-                            .find(|n| n.kind() == SyntaxKind::Identifier)
-                            .map(SyntaxNode::text)
-                            .filter(|name| !name.starts_with('_'))
-                        else {
-                            continue;
-                        };
-
-                        let href =
-                            format!("{root}{}#{}", page.href, rule_hash(name));
-                        rules.insert(name.into(), href.into());
-                    }
+        for name in page_pragma_rules(page, "start") {
+            let symbol = rules.symbols.intern(name);
+            rules.starts.push(symbol);
+        }
+        for name in page_pragma_rules(page, "skip") {
+            let symbol = rules.symbols.intern(name);
+            rules.skipped.insert(symbol);
+        }
+        for rule in page_rule_nodes(page) {
+            let symbol = add_rule(&mut rules, rule);
+            if is_extension(rule) {
+                rules
+                    .extensions
+                    .entry(symbol)
+                    .or_default()
+                    .push((page.href.clone(), extension_anchor(rule).into()));
+            } else if !is_helper(rule) {
+                link_rule(config, &mut rules, page, rule);
+            }
+            if !is_helper(rule) {
+                add_users(&mut rules, rule, symbol);
+            }
+            for group in nested_rules(rule) {
+                add_rule(&mut rules, group);
+                if !is_helper(group) {
+                    link_rule(config, &mut rules, page, group);
                 }
             }
         }
     }
 
+    // Imported rules are known, but have no definition in the book to link
+    // to.
+    rules.imports = import_grammars(pages, config);
+    for grammar in rules.imports.clone().into_values().flatten() {
+        let imported = grammar.children().filter(|node| {
+            node.kind() == SyntaxKind::Rule && !node.erroneous()
+        });
+        for rule in imported {
+            add_rule(&mut rules, rule);
+        }
+    }
+
+    rules.terminals = find_terminals(pages, config.layout);
+    rules.metas = find_metas(pages, config);
+
+    if config.overloads() {
+        link_overloads(pages, config, &mut rules);
+    }
+
+    if config.link_helpers {
+        link_helpers(pages, &mut rules);
+    }
+
+    rules
+}
+
+/// Record the name of a rule, with its number of parameters, documentation
+/// and whether it is a token, returning its symbol.
+fn add_rule(rules: &mut Rules, rule: &SyntaxNode) -> Symbol {
+    let symbol = rules.symbols.intern(rule_name(rule));
+    rules.names.insert(symbol);
+    let params = rule_params(rule);
+    if !params.is_empty() {
+        rules.arities.insert(symbol, params.len());
+    }
+    if let Some(doc) = rule_doc(rule) {
+        rules.docs.entry(symbol).or_insert(doc);
+    }
+    if rule.kind() == SyntaxKind::Rule && !is_extension(rule) {
+        rules
+            .definitions
+            .entry(symbol)
+            .or_insert_with(|| definition_line(rule));
+    }
+    if has_annotation(rule, "token") {
+        rules.tokens.insert(symbol);
+    }
+    if is_fragment(rule) {
+        rules.fragments.insert(symbol);
+    }
+    for (annotation, note) in rule_annotations(rule) {
+        if annotation == "deprecated" {
+            let note = note.map(|note| note.trim().into());
+            rules.deprecated.insert(symbol, note);
+        }
+    }
+    symbol
+}
+
+/// Record a public rule as a user of the rules it references.
+fn add_users(rules: &mut Rules, rule: &SyntaxNode, user: Symbol) {
+    for name in rule_references(rule) {
+        let symbol = rules.symbols.intern(name);
+        let users = rules.users.entry(symbol).or_default();
+        if symbol != user && !users.contains(&user) {
+            users.push(user);
+        }
+    }
+}
+
+/// Link the name of a rule to its definition in a page.
+fn link_rule(
+    config: &Config,
+    rules: &mut Rules,
+    page: &Page,
+    rule: &SyntaxNode,
+) {
+    let symbol = rules.symbols.intern(rule_name(rule));
+    let anchor = rule_anchor(config, rule);
+    let href = config.link(&page.href, &anchor);
+    rules.links.insert(symbol, href.into());
     rules
+        .chapters
+        .entry(symbol)
+        .or_default()
+        .push((page.href.clone(), anchor.into()));
+}
+
+/// Link the rules defined more than once to a list of their definitions, shown
+/// after the block containing the first one.
+fn link_overloads(pages: &[Page], config: &Config, rules: &mut Rules) {
+    let mut definitions: HashMap<&EcoString, Vec<&Page>> = HashMap::new();
+    for page in pages {
+        let rules = page_rule_nodes(page)
+            .filter(|rule| !is_helper(rule) && !is_extension(rule));
+        for rule in rules {
+            definitions.entry(rule_name(rule)).or_default().push(page);
+        }
+    }
+
+    for (name, pages) in definitions {
+        if pages.len() < 2 {
+            continue;
+        }
+
+        let symbol = rules.symbols.intern(name);
+        let href = config.link(&pages[0].href, &overloads_hash(name));
+        rules.links.insert(symbol, href.into());
+
+        let mut chapters = Vec::<(EcoString, EcoString)>::new();
+        for page in pages {
+            if chapters.iter().all(|(href, _)| *href != page.href) {
+                chapters.push((page.href.clone(), page.name.clone()));
+            }
+        }
+        rules.overloads.insert(symbol, chapters);
+    }
+}
+
+/// Render the lists of definitions of the overloaded rules first defined in a
+/// block, skipping the rules already listed in the page.
+pub fn overload_lists(
+    ctx: &Context,
+    page: &Page,
+    block: &Block,
+    listed: &mut HashSet<EcoString>,
+) -> String {
+    let mut out = String::new();
+
+    for rule in block
+        .code
+        .children()
+        .filter(|node| node.kind() == SyntaxKind::Rule && !node.erroneous())
+    {
+        let name = rule_name(rule);
+        let symbol = ctx.rules.symbol(name);
+        let Some(chapters) = symbol.and_then(|s| ctx.rules.overloads.get(&s))
+        else {
+            continue;
+        };
+        if chapters[0].0 != page.href || !listed.insert(name.clone()) {
+            continue;
+        }
+
+        out += &format!(
+            "\n\n<div class=\"syntax-overloads\" \
+             id=\"{id}\">\n<p><code>{name}</code> has several \
+             definitions:</p>\n<ul>\n",
+            id = ctx.config.anchor(&page.href, &overloads_hash(name)),
+            name = encode_safe(name),
+        );
+        for (href, chapter) in chapters {
+            let anchor = ctx.rules.anchor(href, name);
+            let anchor = anchor.map_or_else(|| rule_hash(name), |a| a.into());
+            out += &format!(
+                "<li><a class=\"syntax-link\" \
+                 href=\"{href}\">{chapter}</a></li>\n",
+                href = ctx.config.link(href, &anchor),
+                chapter = encode_safe(chapter),
+            );
+        }
+        out += "</ul>\n</div>\n\n";
+    }
+
+    out
+}
+
+/// Link the helper rules, whose names start with `_`, to the nearest public
+/// rule using them, possibly through other helpers.
+fn link_helpers(pages: &[Page], rules: &mut Rules) {
+    let mut users: HashMap<&EcoString, Vec<&EcoString>> = HashMap::new();
+    for rule in pages.iter().flat_map(page_rule_nodes) {
+        let name = rule_name(rule);
+        for reference in rule_references(rule) {
+            users.entry(reference).or_default().push(name);
+        }
+    }
+
+    let mut links = Vec::new();
+    let helpers = pages
+        .iter()
+        .flat_map(page_rule_nodes)
+        .filter(|rule| is_helper(rule))
+        .map(rule_name)
+        .collect::<HashSet<_>>();
+    for helper in helpers {
+        let mut queue = VecDeque::from([helper]);
+        let mut seen = HashSet::from([helper]);
+        while let Some(name) = queue.pop_front() {
+            let symbol = rules.symbol(name);
+            if let Some(href) = symbol.and_then(|s| rules.links.get(&s)) {
+                links.push((rules.symbols.intern(helper), href.clone()));
+                break;
+            }
+            for &user in users.get(name).into_iter().flatten() {
+                if seen.insert(user) {
+                    queue.push_back(user);
+                }
+            }
+        }
+    }
+
+    rules.links.extend(links);
+}
+
+/// Find the rule definitions of a page, in order.
+pub fn page_rule_nodes(page: &Page) -> impl Iterator<Item = &SyntaxNode> {
+    page.items
+        .iter()
+        .filter_map(|item| match item {
+            | Item::Code(block) => Some(&block.code),
+            | Item::Text(_) | Item::Raw(_) => None,
+        })
+        .flat_map(|code| {
+            // Find rule definitions in code blocks.
+            debug_assert_eq!(code.kind(), SyntaxKind::Root);
+            code.children()
+        })
+        .filter(|node| node.kind() == SyntaxKind::Rule && !node.erroneous())
+}
+
+/// The pragmas of a page with the name, like `start` for `%start program;`.
+pub fn page_pragmas<'a>(
+    page: &'a Page,
+    pragma: &'a str,
+) -> impl Iterator<Item = &'a SyntaxNode> {
+    page.items
+        .iter()
+        .filter_map(|item| match item {
+            | Item::Code(block) => Some(&block.code),
+            | Item::Text(_) | Item::Raw(_) => None,
+        })
+        .flat_map(|code| code.children())
+        .filter(|node| node.kind() == SyntaxKind::Pragma && !node.erroneous())
+        .filter(move |node| {
+            node.children().any(|n| {
+                n.kind() == SyntaxKind::PragmaName && n.text() == pragma
+            })
+        })
+}
+
+/// The names of the rules given by the pragmas of a page with the name, like
+/// `program` for `start` in `%start program;`.
+pub fn page_pragma_rules<'a>(
+    page: &'a Page,
+    pragma: &'a str,
+) -> impl Iterator<Item = &'a EcoString> {
+    page_pragmas(page, pragma)
+        .flat_map(|node| node.children())
+        .filter(|n| n.kind() == SyntaxKind::Identifier)
+        .map(SyntaxNode::text)
+}
+
+/// The grammars imported with `%import` by the pages.
+pub fn import_grammars(pages: &[Page], config: &Config) -> Imports {
+    let mut grammars = BTreeMap::new();
+    for path in pages.iter().flat_map(page_imports) {
+        if grammars.contains_key(&path) {
+            continue;
+        }
+        let grammar = match &config.src {
+            | Some(src) => read_import(src, &path, config),
+            | None => Err(eco_format!(
+                "cannot import `{path}` without the source directory of the \
+                 book"
+            )),
+        };
+        grammars.insert(path, grammar);
+    }
+    grammars
+}
+
+/// The paths imported with `%import` by a page, as written.
+pub fn page_imports(page: &Page) -> impl Iterator<Item = EcoString> {
+    page_pragmas(page, "import")
+        .flat_map(|node| node.children())
+        .filter(|n| n.kind() == SyntaxKind::String)
+        .filter_map(|n| unescape(n.text()))
+        .map(Into::into)
+}
+
+/// Read and parse an imported grammar, which must be inside the source
+/// directory.
+fn read_import(
+    src: &Path,
+    path: &str,
+    config: &Config,
+) -> Result<SyntaxNode, EcoString> {
+    let error =
+        |err: std::io::Error| eco_format!("cannot import `{path}`: {err}");
+    let src = src.canonicalize().map_err(error)?;
+    let file = src.join(path).canonicalize().map_err(error)?;
+    if !file.starts_with(&src) {
+        return Err(eco_format!(
+            "cannot import `{path}` from outside the source directory"
+        ));
+    }
+    std::fs::read_to_string(file)
+        .map(|code| parse_with_options(&code, &config.parse_options()))
+        .map_err(error)
+}
+
+/// The rules of a grammar block, with the title of the section containing
+/// each of them, if any.
+pub fn sectioned_rules(
+    code: &SyntaxNode,
+) -> impl Iterator<Item = (Option<&str>, &SyntaxNode)> {
+    debug_assert_eq!(code.kind(), SyntaxKind::Root);
+
+    code.children()
+        .scan(None, |section, node| {
+            if let Some(title) = section_title(node) {
+                *section = Some(title);
+            }
+            Some((*section, node))
+        })
+        .filter(|(_, node)| {
+            node.kind() == SyntaxKind::Rule && !node.erroneous()
+        })
+}
+
+/// The title of a section header, like `Expressions` in `//! Expressions`.
+pub fn section_title(node: &SyntaxNode) -> Option<&str> {
+    if node.kind() != SyntaxKind::Comment {
+        return None;
+    }
+    let title = node.text().strip_prefix("//!")?.trim();
+    (!title.is_empty()).then_some(title)
+}
+
+/// Render a list linking to the rules defined in a page, grouped by section.
+///
+/// The start rules of the blocks, set with the `start` option, come first.
+/// Return an empty string if the page defines no rules.
+pub fn rule_list(page: &Page, config: &Config) -> String {
+    let blocks = page
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            | Item::Code(block) => Some(block),
+            | Item::Text(_) | Item::Raw(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let starts = blocks.iter().filter_map(|block| {
+        let start = block.value("start")?;
+        let (_, rule) = sectioned_rules(&block.code)
+            .find(|&(_, rule)| rule_name(rule) == start)?;
+        Some((None, rule))
+    });
+    // Named groups follow the rule defining them.
+    let rules = blocks
+        .iter()
+        .flat_map(|block| sectioned_rules(&block.code))
+        .flat_map(|(section, rule)| {
+            let groups = nested_rules(rule).into_iter();
+            std::iter::once(rule)
+                .chain(groups)
+                .map(move |r| (section, r))
+        });
+
+    let mut seen = HashSet::new();
+    let mut groups: Vec<(Option<&str>, Vec<&SyntaxNode>)> = Vec::new();
+    for (section, rule) in starts.chain(rules) {
+        let name = rule_name(rule);
+        if is_helper(rule) || !seen.insert(name) {
+            continue;
+        }
+        match groups.last_mut() {
+            | Some((last, rules)) if *last == section => rules.push(rule),
+            | _ => groups.push((section, vec![rule])),
+        }
+    }
+
+    if groups.is_empty() {
+        return String::new();
+    }
+
+    let items = |rules: &[&SyntaxNode]| {
+        rules
+            .iter()
+            .map(|rule| {
+                format!(
+                    "<li><a class=\"syntax-link\" \
+                     href=\"#{hash}\">{content}</a>{since}</li>",
+                    hash =
+                        config.anchor(&page.href, &rule_anchor(config, rule)),
+                    content = wrap_node_raw(rule_name(rule), "identifier"),
+                    since = rule_since(rule)
+                        .map(|version| since_badge(config, version))
+                        .unwrap_or_default(),
+                )
+            })
+            .collect::<String>()
+    };
+
+    let content = groups
+        .iter()
+        .map(|(section, rules)| match section {
+            | Some(title) => format!(
+                "<li><a class=\"syntax-link\" \
+                 href=\"#{hash}\">{title}</a><ul>{items}</ul></li>",
+                hash = config.anchor(&page.href, &section_hash(title)),
+                title = encode_safe(title),
+                items = items(rules),
+            ),
+            | None => items(rules),
+        })
+        .collect::<String>();
+
+    // Keep the list on a single line and end it with a blank line, so that
+    // markdown treats it as one html block.
+    format!("<ul class=\"syntax-rule-list\">{content}</ul>\n\n")
 }
 
-pub fn parse_code(rules: &Rules, code: &SyntaxNode) -> String {
+pub fn parse_code(ctx: &Context, block: &Block) -> String {
+    let ctx = &Context {
+        block: Some(block),
+        params: &[],
+        ..*ctx
+    };
+    let reflowed;
+    let code = if block.reflow(ctx.config) {
+        let options = ParseOptions {
+            parameterized: ctx
+                .rules
+                .arities
+                .keys()
+                .map(|&symbol| ctx.rules.symbols.name(symbol).clone())
+                .collect(),
+            ..ctx.config.parse_options()
+        };
+        reflowed = parse_with_options(&format(&block.code), &options);
+        &reflowed
+    } else {
+        &block.code
+    };
     debug_assert_eq!(code.kind(), SyntaxKind::Root);
 
+    // Reflowing keeps the rules in order, so the lines of the original code
+    // still apply.
+    let mut lines = rule_lines(block).into_iter();
     let content = code
         .children()
         .map(|node| {
             if node.kind() == SyntaxKind::Rule && !node.erroneous() {
-                parse_rule(rules, node)
+                parse_rule(ctx, node, lines.next())
+            } else if let Some(title) = section_title(node) {
+                wrap_section(ctx, title)
             } else {
-                wrap(rules, node)
+                wrap(ctx, node)
             }
         })
-        .collect::<Vec<_>>()
-        .join("");
+        .collect::<String>();
+
+    // Unrecognized options are kept as classes, like mdbook does for the
+    // attributes of other code blocks.
+    let classes = block
+        .options
+        .iter()
+        .filter(|option| !Block::is_recognized(option))
+        .map(|option| format!(" {}", encode_safe(option)))
+        .collect::<String>();
+
+    format!("<pre><code class=\"syntax{classes}\">{content}</code></pre>")
+}
+
+/// The code of a block, from the text of its tokens.
+pub fn code_text(code: &SyntaxNode) -> String {
+    code.recur_iter().map(|node| node.text().as_str()).collect()
+}
+
+/// Find the lines of the chapter where the rules of a block start, at their
+/// names after their doc comments.
+pub fn rule_lines(block: &Block) -> Vec<usize> {
+    let map = SourceMap::new(&code_text(&block.code), &[]);
 
-    format!("<pre><code class=\"syntax\">{content}</code></pre>")
+    block
+        .code
+        .children()
+        .filter(|node| node.kind() == SyntaxKind::Rule && !node.erroneous())
+        .filter_map(|rule| rule.find_by_kind(SyntaxKind::Identifier))
+        .map(|name| block.line + map.line(name.span().start) - 1)
+        .collect()
 }
 
-fn parse_rule(rules: &Rules, rule: &SyntaxNode) -> String {
+fn parse_rule(ctx: &Context, rule: &SyntaxNode, line: Option<usize>) -> String {
     debug_assert_eq!(rule.kind(), SyntaxKind::Rule);
     debug_assert!(!rule.erroneous());
 
-    let name = rule
-        .children()
-        .find(|n| n.kind() == SyntaxKind::Identifier)
-        .unwrap()
-        .text();
+    let name = rule.find_by_kind(SyntaxKind::Identifier).unwrap().text();
 
-    if name.starts_with('_') {
+    if is_helper(rule) {
         // Ignored rule.
-        wrap(rules, rule)
+        wrap(ctx, rule)
     } else {
+        let start = ctx.block.and_then(|block| block.value("start"));
+        let symbol = ctx.rules.symbol(name);
+        let skipped = symbol.is_some_and(|s| ctx.rules.skipped.contains(&s));
+        let deprecated = symbol.and_then(|s| ctx.rules.deprecated.get(&s));
+        let (anchor, extensions) = if is_extension(rule) {
+            (extension_anchor(rule), String::new())
+        } else {
+            (rule_anchor(ctx.config, rule), extension_links(ctx, name))
+        };
+
+        let mut badges = complexity_badge(ctx, rule);
+        if skipped {
+            badges += "<span class=\"syntax-badge syntax-skip\" \
+                       title=\"skipped between tokens\">⤼</span>";
+        }
+        if let Some(note) = deprecated {
+            badges += &format!(
+                "<span class=\"syntax-badge syntax-deprecated-notice\" \
+                 title=\"{}\">deprecated</span>",
+                encode_double_quoted_attribute(&deprecation_notice(
+                    name,
+                    note.as_deref()
+                )),
+            );
+        }
+        if let Some(version) = rule_since(rule) {
+            badges += &since_badge(ctx.config, version);
+        }
+        badges += &extensions;
+
         format!(
-            "<span class=\"syntax-rule\" rule=\"{name}\"><a \
-             name=\"{name}\"></a>{content}</span>",
-            name = rule_hash(name),
-            content = wrap(rules, rule)
+            "<span class=\"syntax-rule{class}\" rule=\"{name}\"><a \
+             name=\"{name}\"></a>{content}{badges}{source}{users}</span>",
+            class = [
+                (start == Some(name)
+                    || symbol.is_some_and(|s| ctx.rules.starts.contains(&s)))
+                .then_some(" syntax-start"),
+                skipped.then_some(" syntax-skipped"),
+                deprecated.is_some().then_some(" syntax-deprecated"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<String>(),
+            name = page_anchor(ctx, &anchor),
+            content = wrap(ctx, rule),
+            source =
+                line.map(|line| source_link(ctx, line)).unwrap_or_default(),
+            users = if ctx.config.backlinks && !is_extension(rule) {
+                used_by(ctx, name)
+            } else {
+                String::new()
+            },
         )
     }
 }
 
-pub fn wrap(rules: &Rules, node: &SyntaxNode) -> String {
+/// A badge with the version a rule was added in, linking to its entry in the
+/// changelog if one is configured.
+fn since_badge(config: &Config, version: &str) -> String {
+    let title = encode_double_quoted_attribute(version);
+    let text = encode_safe(version);
+    match &config.changelog {
+        | Some(changelog) => format!(
+            "<a class=\"syntax-badge syntax-since\" href=\"{href}\" \
+             title=\"since {title}\">{text}</a>",
+            href = config.link(changelog, &normalize_id(version)),
+        ),
+        | None => format!(
+            "<span class=\"syntax-badge syntax-since\" title=\"since \
+             {title}\">{text}</span>"
+        ),
+    }
+}
+
+/// Links to the extensions of a rule, like `a += b;`, in the order of the
+/// book.
+fn extension_links(ctx: &Context, name: &str) -> String {
+    let symbol = ctx.rules.symbol(name);
+    let Some(extensions) = symbol.and_then(|s| ctx.rules.extensions.get(&s))
+    else {
+        return String::new();
+    };
+
+    extensions
+        .iter()
+        .map(|(chapter, anchor)| {
+            format!(
+                "<a class=\"syntax-badge syntax-extension\" href=\"{href}\" \
+                 title=\"extended in {chapter}\">+</a>",
+                href = ctx.config.link(chapter, anchor),
+                chapter = encode_double_quoted_attribute(chapter),
+            )
+        })
+        .collect()
+}
+
+/// A line under a rule linking to the rules referencing it, if any.
+fn used_by(ctx: &Context, name: &str) -> String {
+    let symbol = ctx.rules.symbol(name);
+    let Some(users) = symbol.and_then(|s| ctx.rules.users.get(&s)) else {
+        return String::new();
+    };
+
+    let chapter = ctx.page.map(|page| &page.href);
+    let users = users
+        .iter()
+        .map(|&user| {
+            let user = ctx.rules.symbols.name(user);
+            match ctx.rules.link(ctx.config, chapter, user) {
+                | Some(href) => format!(
+                    "<a class=\"syntax-link\" href=\"{href}\">{content}</a>",
+                    content = wrap_name(ctx, user),
+                ),
+                | None => wrap_name(ctx, user),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    format!(
+        "<span class=\"syntax-used-by\">used by: {}</span>",
+        users.join(", ")
+    )
+}
+
+/// A badge on a rule exceeding the complexity limits, if the `complex-rule`
+/// lint is enabled.
+fn complexity_badge(ctx: &Context, rule: &SyntaxNode) -> String {
+    if ctx.config.level(Lint::ComplexRule) == Level::Allow {
+        return String::new();
+    }
+
+    let excess =
+        complexity_excess(rule_complexity(rule), ctx.config.complexity);
+    if excess.is_empty() {
+        return String::new();
+    }
+
+    format!(
+        "<span class=\"syntax-badge syntax-complex\" title=\"complex rule: \
+         {}\">◆</span>",
+        excess.join(", "),
+    )
+}
+
+/// Link to the line of the rule in the source of the chapter, if a
+/// repository is configured.
+fn source_link(ctx: &Context, line: usize) -> String {
+    let (Some(repository), Some(page), Some(_)) =
+        (&ctx.config.repository, ctx.page, ctx.block)
+    else {
+        return String::new();
+    };
+
+    let path = [
+        repository.trim_end_matches('/'),
+        "blob",
+        &ctx.config.branch,
+        ctx.config.source_root.trim_matches('/'),
+        &page.href,
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join("/");
+
+    format!(
+        "<a class=\"syntax-source\" href=\"{href}#L{line}\" title=\"view the \
+         source\">↗</a>",
+        href = encode_double_quoted_attribute(&path),
+    )
+}
+
+pub fn wrap(ctx: &Context, node: &SyntaxNode) -> String {
     let cls = match node.kind() {
         | SyntaxKind::Error => return wrap_error(node),
         | SyntaxKind::Comment => "comment",
+        | SyntaxKind::DocComment => "comment syntax-doc",
         | SyntaxKind::Whitespace => return node.text().into(),
-        | SyntaxKind::Identifier => return wrap_identifier(rules, node),
-        | SyntaxKind::String => "string",
+        | SyntaxKind::Identifier => {
+            let badge = deprecation_badge(ctx, node.text());
+            return wrap_identifier(ctx, node) + &badge;
+        },
+        | SyntaxKind::String | SyntaxKind::RawString => "string",
+        | SyntaxKind::Modifier => return wrap_modifier(node),
         | SyntaxKind::Integer => "integer",
-        | SyntaxKind::Meta => "meta",
-        | SyntaxKind::Operation => "action",
-        | SyntaxKind::If => "keyword",
-        | k if k.is_operator() => "operator",
-        | _ => {
+        | SyntaxKind::Meta => return wrap_meta(ctx, node),
+        | SyntaxKind::CharClass => return wrap_char_class(node),
+        | SyntaxKind::Operation => return wrap_operation(ctx, node.text()),
+        | SyntaxKind::Call if ctx.action_language().is_some() => {
+            let mut text = String::new();
+            flat_text(node, &mut text);
+            return wrap_operation(ctx, &text);
+        },
+        | SyntaxKind::Function => "function",
+        | SyntaxKind::Empty => {
+            return "<span class=\"syntax-empty\" title=\"empty\">ε</span>"
+                .into();
+        },
+        | SyntaxKind::If | SyntaxKind::Fragment => "keyword",
+        | SyntaxKind::Variable => return wrap_variable(ctx, node),
+        | SyntaxKind::Operator => "operator",
+        | SyntaxKind::Colon if ctx.config.notation == Notation::Bnf => {
+            return wrap_node_raw("::=", "operator");
+        },
+        | SyntaxKind::Range if !node.erroneous() => {
+            return wrap_range(ctx, node);
+        },
+        | SyntaxKind::Repeating if !node.erroneous() => {
+            return wrap_repeating(ctx, node);
+        },
+        | SyntaxKind::Separated | SyntaxKind::Difference if !node.erroneous() =>
+        {
+            return wrap_binary(ctx, node);
+        },
+        | SyntaxKind::Optional if !node.erroneous() => {
             return node
                 .children()
-                .map(|n| wrap(rules, n))
-                .collect::<Vec<_>>()
-                .join("");
+                .map(|n| match n.kind() {
+                    | SyntaxKind::LeftBracket | SyntaxKind::RightBracket => {
+                        format!(
+                            "<span class=\"syntax-repetition\" \
+                             title=\"optional\">{}</span>",
+                            n.text()
+                        )
+                    },
+                    | _ => wrap(ctx, n),
+                })
+                .collect();
+        },
+        | SyntaxKind::Converse if !node.erroneous() => {
+            return wrap_converse(ctx, node);
+        },
+        | SyntaxKind::NamedGroup if !node.erroneous() => {
+            return wrap_named_group(ctx, node);
+        },
+        | SyntaxKind::Looking if !node.erroneous() => {
+            return wrap_looking(ctx, node);
+        },
+        | SyntaxKind::Constraint if !node.erroneous() => {
+            return wrap_constraint(ctx, node);
+        },
+        | SyntaxKind::Annotation => {
+            let text = node.children().map(|n| n.text().as_str());
+            return wrap_node_raw(&text.collect::<String>(), "annotation");
+        },
+        | SyntaxKind::Pragma if !node.erroneous() => {
+            return wrap_pragma(ctx, node);
+        },
+        | SyntaxKind::Reference if !node.erroneous() => {
+            return wrap_reference(ctx, node);
+        },
+        | SyntaxKind::Rule => {
+            let params =
+                rule_params(node).into_iter().cloned().collect::<Vec<_>>();
+            let ctx = Context {
+                params: &params,
+                ..*ctx
+            };
+            return node
+                .children()
+                .map(|n| match n.kind() {
+                    | SyntaxKind::Definition if !is_helper(node) => n
+                        .children()
+                        .map(|n| match n.kind() {
+                            | SyntaxKind::Label => wrap_label(&ctx, node, n),
+                            | _ => wrap(&ctx, n),
+                        })
+                        .collect(),
+                    | SyntaxKind::Identifier => wrap_header(&ctx, n),
+                    | _ => wrap(&ctx, n),
+                })
+                .collect();
+        },
+        | SyntaxKind::Label => "label",
+        | SyntaxKind::Dot => return wrap_dot(ctx, node),
+        | SyntaxKind::Caret => {
+            return "<span class=\"syntax-operator\" title=\"start of a \
+                    line\">^</span>"
+                .into();
+        },
+        | SyntaxKind::Dollar => {
+            return "<span class=\"syntax-operator\" title=\"end of a \
+                    line\">$</span>"
+                .into();
+        },
+        | k if k.is_operator() => "operator",
+        | _ => {
+            return node.children().map(|n| wrap(ctx, n)).collect::<String>();
         },
     };
 
     wrap_node_raw(node.text(), cls)
 }
 
-fn wrap_identifier(rules: &Rules, rule: &SyntaxNode) -> String {
-    debug_assert_eq!(rule.kind(), SyntaxKind::Identifier);
-
-    let name = rule.text();
-    if let Some(href) = rules.get(name) {
-        format!(
-            "<a class=\"syntax-link\" href=\"{href}\">{content}</a>",
-            content = wrap_node_raw(name, "identifier"),
-        )
-    } else {
-        wrap_node_raw(name, "identifier")
+/// Wrap the code of an action or a parameter.
+///
+/// If the language of the code is known, the code gets the class picked up by
+/// the highlighter of mdbook, even if it was parsed as a call.
+fn wrap_operation(ctx: &Context, text: &str) -> String {
+    match ctx.action_language() {
+        | Some(language) => format!(
+            "<code class=\"syntax-action language-{language}\">{text}</code>",
+            language = encode_safe(language),
+            text = encode_safe(text),
+        ),
+        | None => wrap_node_raw(text, "action"),
     }
 }
 
-fn wrap_error(error: &SyntaxNode) -> String {
-    debug_assert_eq!(error.kind(), SyntaxKind::Error);
-    wrap_error_raw(error.text(), error.as_error().unwrap())
+/// Wrap a pragma, like `%start program;`, as a banner.
+fn wrap_pragma(ctx: &Context, pragma: &SyntaxNode) -> String {
+    debug_assert_eq!(pragma.kind(), SyntaxKind::Pragma);
+
+    let content = pragma
+        .children()
+        .map(|n| match n.kind() {
+            | SyntaxKind::Percent | SyntaxKind::PragmaName => {
+                wrap_node_raw(n.text(), "keyword")
+            },
+            | _ => wrap(ctx, n),
+        })
+        .collect::<String>();
+    let name = pragma
+        .find_by_kind(SyntaxKind::PragmaName)
+        .map(|n| n.text().as_str());
+    format!(
+        "<span class=\"syntax-pragma\" title=\"{title}\">{content}</span>",
+        title = match name {
+            | Some("skip") => "the rules skipped between tokens",
+            | Some("import") => "the rules imported from a file",
+            | _ => "the start rule of the grammar",
+        },
+    )
 }
 
-fn wrap_node_raw(code: &str, cls: &str) -> String {
+/// Wrap a section header as a heading with its own anchor.
+fn wrap_section(ctx: &Context, title: &str) -> String {
     format!(
-        "<span class=\"syntax-{cls}\">{text}</span>",
-        cls = cls,
-        text = encode_safe(code)
+        "<a class=\"syntax-section\" id=\"{hash}\" \
+         href=\"#{hash}\">{title}</a>",
+        hash = page_anchor(ctx, &section_hash(title)),
+        title = encode_safe(title),
     )
 }
 
-fn wrap_error_raw(code: &str, error: &SyntaxError) -> String {
-    let text = {
-        let text = code;
-        if text.trim().is_empty() {
-            "[error]"
-        } else {
-            text
-        }
-    };
+/// Wrap the label of an alternative, like `#add`, as a link to its own
+/// anchor.
+fn wrap_label(ctx: &Context, rule: &SyntaxNode, label: &SyntaxNode) -> String {
+    format!(
+        "<a class=\"syntax-label\" id=\"{hash}\" href=\"#{hash}\">{label}</a>",
+        hash = page_anchor(ctx, &label_anchor(ctx.config, rule, label.text())),
+        label = encode_safe(label.text()),
+    )
+}
 
-    let message = error.message.escape_default();
-    let hints = error
-        .hints
-        .iter()
-        .map(|hint| format!("\"{}\"", hint.escape_default()))
-        .collect::<Vec<_>>()
-        .join(",");
+/// Wrap an expression with a constraint, showing the constraint as a
+/// superscript note.
+fn wrap_constraint(ctx: &Context, constraint: &SyntaxNode) -> String {
+    debug_assert_eq!(constraint.kind(), SyntaxKind::Constraint);
+
+    constraint
+        .children()
+        .map(|node| {
+            if node.kind() != SyntaxKind::Param {
+                return wrap(ctx, node);
+            }
+            let note = node
+                .find_by_kind(SyntaxKind::Operation)
+                .map_or("", |n| n.text().trim());
+            format!(
+                "<sup class=\"syntax-constraint\">{}</sup>",
+                encode_safe(note)
+            )
+        })
+        .collect()
+}
+
+/// Wrap a reference to a rule with arguments, telling how many parameters the
+/// rule takes in a tooltip.
+fn wrap_reference(ctx: &Context, reference: &SyntaxNode) -> String {
+    debug_assert_eq!(reference.kind(), SyntaxKind::Reference);
+
+    let content = reference
+        .children()
+        .map(|n| wrap(ctx, n))
+        .collect::<String>();
+    let arguments = reference
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::Argument)
+        .count();
+    let name = reference
+        .find_by_kind(SyntaxKind::Identifier)
+        .unwrap()
+        .text();
+    let symbol = ctx.rules.symbol(name).filter(|_| arguments > 0);
+    let Some(&arity) = symbol.and_then(|s| ctx.rules.arities.get(&s)) else {
+        return content;
+    };
 
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    let mut title = format!("`{name}` takes {arity} argument{}", plural(arity));
+    if arguments != arity {
+        title += &format!(", given {arguments}");
+    }
     format!(
-        "<span class=\"syntax-error\" message=\"{message}\" \
-         hints=\"[{hints}]\">{text}</span>",
-        hints = encode_safe(&hints),
+        "<span class=\"syntax-reference\" title=\"{title}\">{content}</span>",
+        title = encode_double_quoted_attribute(&title),
     )
 }
 
-#[inline]
-pub fn rule_hash(name: impl ToString) -> String {
-    format!("syntax-rule-{name}", name = name.to_string())
+/// Wrap the wildcard `.`, telling what it matches in a tooltip if the book
+/// states it.
+fn wrap_dot(ctx: &Context, dot: &SyntaxNode) -> String {
+    debug_assert_eq!(dot.kind(), SyntaxKind::Dot);
+
+    match ctx.config.wildcard {
+        | Some(wildcard) => format!(
+            "<span class=\"syntax-operator syntax-wildcard\" \
+             title=\"{}\">.</span>",
+            wildcard.describe()
+        ),
+        | None => wrap_node_raw(dot.text(), "operator"),
+    }
+}
+
+/// Wrap a meta, badging layout tokens like `<indent>` with their description
+/// if they are recognized.
+fn wrap_meta(ctx: &Context, meta: &SyntaxNode) -> String {
+    debug_assert_eq!(meta.kind(), SyntaxKind::Meta);
+
+    match describe_layout(meta.text()).filter(|_| ctx.config.layout) {
+        | Some(title) => format!(
+            "<span class=\"syntax-meta syntax-layout\" \
+             title=\"{title}\">{text}</span>",
+            text = encode_safe(meta.text()),
+        ),
+        | None => match ctx.rules.metas.get(meta.text()) {
+            | Some(href) => format!(
+                "<a class=\"syntax-link\" href=\"{href}\">{meta}</a>",
+                meta = wrap_node_raw(meta.text(), "meta"),
+            ),
+            | None => wrap_node_raw(meta.text(), "meta"),
+        },
+    }
+}
+
+/// Wrap a character range, showing `..` and `..=` as an en dash and the code
+/// points of the first and last characters in a tooltip.
+fn wrap_range(ctx: &Context, range: &SyntaxNode) -> String {
+    debug_assert_eq!(range.kind(), SyntaxKind::Range);
+
+    let title = range_bounds(range)
+        .map(|(start, end)| {
+            format!("U+{:04X} – U+{:04X}", start as u32, end as u32)
+        })
+        .unwrap_or_default();
+
+    let content = range
+        .children()
+        .map(|n| {
+            if matches!(n.kind(), SyntaxKind::Dots | SyntaxKind::DotsEq) {
+                wrap_node_raw("–", "operator")
+            } else {
+                wrap(ctx, n)
+            }
+        })
+        .collect::<String>();
+
+    format!("<span class=\"syntax-range\" title=\"{title}\">{content}</span>")
+}
+
+/// Wrap a character class, listing the code points it matches in a tooltip.
+fn wrap_char_class(class: &SyntaxNode) -> String {
+    debug_assert_eq!(class.kind(), SyntaxKind::CharClass);
+
+    let title = unescape_class(class.text())
+        .unwrap_or_default()
+        .iter()
+        .map(|&range| describe_class_range(range))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "<span class=\"syntax-class\" title=\"{title}\">{text}</span>",
+        text = encode_safe(class.text()),
+    )
+}
+
+/// Wrap the modifier of a string literal, like the `i` of `i"select"`, with a
+/// tooltip telling what it does.
+fn wrap_modifier(modifier: &SyntaxNode) -> String {
+    debug_assert_eq!(modifier.kind(), SyntaxKind::Modifier);
+
+    let title = match modifier.text().as_str() {
+        | "i" => "case-insensitive",
+        | _ => "",
+    };
+    format!(
+        "<span class=\"syntax-modifier\" title=\"{title}\">{text}</span>",
+        text = encode_safe(modifier.text()),
+    )
+}
+
+/// Describe a range of a character class by its code points.
+fn describe_class_range((start, end): (char, char)) -> String {
+    let code_point = |c: char| format!("U+{:04X}", c as u32);
+    if start == end {
+        code_point(start)
+    } else {
+        format!("{} – {}", code_point(start), code_point(end))
+    }
+}
+
+/// Wrap a converse expression, explaining the characters it matches in a
+/// tooltip if it negates a character class.
+fn wrap_converse(ctx: &Context, converse: &SyntaxNode) -> String {
+    debug_assert_eq!(converse.kind(), SyntaxKind::Converse);
+
+    let content = converse
+        .children()
+        .map(|n| wrap(ctx, n))
+        .collect::<String>();
+    let code_point = |c: char| format!("U+{:04X}", c as u32);
+    let title = match NegatedClass::of(converse) {
+        | Some(NegatedClass::Range(start, end)) => format!(
+            "any character outside {} – {}",
+            code_point(start),
+            code_point(end)
+        ),
+        | Some(NegatedClass::Char(c)) => {
+            format!("any character except {}", code_point(c))
+        },
+        | Some(NegatedClass::Class(ranges)) => format!(
+            "any character outside {}",
+            ranges
+                .into_iter()
+                .map(describe_class_range)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        | Some(NegatedClass::Meta(meta)) => {
+            format!("any character not matched by {meta}")
+        },
+        | None => return content,
+    };
+
+    format!(
+        "<span class=\"syntax-converse\" title=\"{title}\">{content}</span>",
+        title = encode_double_quoted_attribute(&title),
+    )
+}
+
+/// Wrap a repeating expression, explaining the repetition in a tooltip.
+fn wrap_repeating(ctx: &Context, repeating: &SyntaxNode) -> String {
+    debug_assert_eq!(repeating.kind(), SyntaxKind::Repeating);
+
+    let mut children = repeating.children();
+    let item = children.next().map(|n| wrap(ctx, n)).unwrap_or_default();
+    let content = children.map(|n| wrap(ctx, n)).collect::<String>();
+
+    match Repeat::of(repeating) {
+        | Some(repeat) => format!(
+            "{item}<span class=\"syntax-repetition\" \
+             title=\"{title}\">{content}</span>",
+            title = describe_repetition(repeat),
+        ),
+        | None => item + &content,
+    }
+}
+
+/// Wrap a separated list, like `arg % ","`, or a difference, like
+/// `char - "\""`, with a tooltip on the operator telling what its right side
+/// does.
+fn wrap_binary(ctx: &Context, binary: &SyntaxNode) -> String {
+    let (operator, cls, title) = match binary.kind() {
+        | SyntaxKind::Separated => (
+            SyntaxKind::Percent,
+            "repetition",
+            "one or more occurrences separated by",
+        ),
+        | _ => (SyntaxKind::Minus, "operator", "excluding the matches of"),
+    };
+
+    let mut right = String::new();
+    if let Some(node) = binary
+        .children()
+        .skip_while(|n| n.kind() != operator)
+        .find(|n| !n.kind().is_trivia() && n.kind() != operator)
+    {
+        flat_text(node, &mut right);
+    }
+
+    binary
+        .children()
+        .map(|n| match n.kind() {
+            | kind if kind == operator => format!(
+                "<span class=\"syntax-{cls}\" title=\"{title} {}\">{}</span>",
+                encode_double_quoted_attribute(right.trim()),
+                n.text(),
+            ),
+            | _ => wrap(ctx, n),
+        })
+        .collect()
+}
+
+/// Describe a repetition, like `{2,}` or `*?`.
+fn describe_repetition(repeat: Repeat) -> String {
+    let plural = |n: u64| if n == 1 { "occurrence" } else { "occurrences" };
+    let mut text = match (repeat.min, repeat.max) {
+        | (0, Some(1)) => "optional".to_string(),
+        | (min, Some(max)) if min == max => {
+            format!("exactly {min} {}", plural(min))
+        },
+        | (min, Some(max)) => format!("between {min} and {max} occurrences"),
+        | (0, None) => "any number of occurrences".to_string(),
+        | (min, None) => format!("at least {min} {}", plural(min)),
+    };
+
+    if !repeat.greedy {
+        text += ", matching as few as possible";
+    }
+
+    text
+}
+
+/// Wrap a named group, like `(sub: "a" | "b")`, with the anchor of the rule
+/// it defines.
+fn wrap_named_group(ctx: &Context, group: &SyntaxNode) -> String {
+    debug_assert_eq!(group.kind(), SyntaxKind::NamedGroup);
+
+    let content = group.children().map(|n| wrap(ctx, n)).collect::<String>();
+    if is_helper(group) {
+        return content;
+    }
+
+    format!(
+        "<span class=\"syntax-named-group\"><a \
+         name=\"{anchor}\"></a>{content}</span>",
+        anchor = page_anchor(ctx, &rule_anchor(ctx.config, group)),
+    )
+}
+
+/// Wrap a lookahead or lookbehind expression with a badge showing its
+/// direction and a tooltip explaining it.
+fn wrap_looking(ctx: &Context, looking: &SyntaxNode) -> String {
+    debug_assert_eq!(looking.kind(), SyntaxKind::Looking);
+
+    let content = looking.children().map(|n| wrap(ctx, n)).collect::<String>();
+
+    let Some(operator) = looking.children().find(|n| n.kind().is_looking())
+    else {
+        return content;
+    };
+
+    let (cls, badge, title) = match operator.kind() {
+        | SyntaxKind::LookAheadPos => (
+            "lookahead",
+            "⇢",
+            "positive lookahead: the following text must match, without being \
+             consumed",
+        ),
+        | SyntaxKind::LookAheadNeg => (
+            "lookahead syntax-negative",
+            "⇢",
+            "negative lookahead: the following text must not match",
+        ),
+        | SyntaxKind::LookBehindPos => (
+            "lookbehind",
+            "⇠",
+            "positive lookbehind: the preceding text must match",
+        ),
+        | _ => (
+            "lookbehind syntax-negative",
+            "⇠",
+            "negative lookbehind: the preceding text must not match",
+        ),
+    };
+
+    format!(
+        "<span class=\"syntax-looking syntax-{cls}\" title=\"{title}\"><span \
+         class=\"syntax-badge\">{badge}</span>{content}</span>"
+    )
+}
+
+fn wrap_identifier(ctx: &Context, rule: &SyntaxNode) -> String {
+    debug_assert_eq!(rule.kind(), SyntaxKind::Identifier);
+
+    let name = rule.text();
+    let chapter = ctx.page.map(|page| &page.href);
+    if ctx.params.contains(name) {
+        wrap_node_raw(name, "param")
+    } else if let Some(href) = ctx.rules.link(ctx.config, chapter, name) {
+        let symbol = ctx.rules.symbol(name);
+        let doc = symbol.and_then(|s| ctx.rules.docs.get(&s));
+        // Previews show the documentation along with the definition.
+        let definition = symbol.and_then(|s| ctx.rules.definitions.get(&s));
+        let attributes = match definition.filter(|_| ctx.config.preview) {
+            | Some(definition) => preview_attributes(name, definition, doc),
+            | None => doc
+                .map(|doc| {
+                    format!(
+                        " title=\"{}\"",
+                        encode_double_quoted_attribute(doc)
+                    )
+                })
+                .unwrap_or_default(),
+        };
+        format!(
+            "<a class=\"syntax-link\" \
+             href=\"{href}\"{attributes}>{content}</a>",
+            content = wrap_name(ctx, name),
+        )
+    } else if ctx.rules.is_defined(name) {
+        wrap_name(ctx, name)
+    } else {
+        wrap_unresolved(ctx, name)
+    }
+}
+
+/// The attributes of a link previewing the definition of a rule.
+fn preview_attributes(
+    name: &str,
+    definition: &str,
+    doc: Option<&String>,
+) -> String {
+    let mut attributes = format!(
+        " data-preview=\"{}\"",
+        encode_double_quoted_attribute(&format!("{name}: {definition};"))
+    );
+    if let Some(doc) = doc {
+        attributes +=
+            &format!(" data-doc=\"{}\"", encode_double_quoted_attribute(doc));
+    }
+    attributes
+}
+
+/// Wrap a name in a condition, linking it to the rule of the same name if
+/// there is one.
+fn wrap_variable(ctx: &Context, variable: &SyntaxNode) -> String {
+    debug_assert_eq!(variable.kind(), SyntaxKind::Variable);
+
+    let name = variable.text();
+    let chapter = ctx.page.map(|page| &page.href);
+    if let Some(href) = ctx.rules.link(ctx.config, chapter, name) {
+        format!(
+            "<a class=\"syntax-link\" href=\"{href}\">{content}</a>",
+            content = wrap_node_raw(name, "variable"),
+        )
+    } else if matches!(name.as_str(), "true" | "false") {
+        wrap_node_raw(name, "keyword")
+    } else {
+        wrap_node_raw(name, "variable")
+    }
+}
+
+/// Wrap the name of a rule in its definition, struck through if the rule is
+/// deprecated.
+fn wrap_header(ctx: &Context, name: &SyntaxNode) -> String {
+    let content = wrap_identifier(ctx, name);
+    let symbol = ctx.rules.symbol(name.text());
+    match symbol.and_then(|s| ctx.rules.deprecated.get(&s)) {
+        | Some(note) => format!(
+            "<s class=\"syntax-deprecated-name\" \
+             title=\"{title}\">{content}</s>",
+            title = encode_double_quoted_attribute(&deprecation_notice(
+                name.text(),
+                note.as_deref()
+            )),
+        ),
+        | None => content,
+    }
+}
+
+/// A warning badge after a reference to a deprecated rule.
+fn deprecation_badge(ctx: &Context, name: &str) -> String {
+    if ctx.params.iter().any(|param| param == name) {
+        return String::new();
+    }
+    let symbol = ctx.rules.symbol(name);
+    let Some(note) = symbol.and_then(|s| ctx.rules.deprecated.get(&s)) else {
+        return String::new();
+    };
+    format!(
+        "<span class=\"syntax-badge syntax-deprecated-use\" \
+         title=\"{title}\">⚠</span>",
+        title = encode_double_quoted_attribute(&deprecation_notice(
+            name,
+            note.as_deref()
+        )),
+    )
+}
+
+/// The notice of a deprecated rule, like ``rule `a` is deprecated: use b``.
+fn deprecation_notice(name: &str, note: Option<&str>) -> String {
+    match note {
+        | Some(note) => format!("rule `{name}` is deprecated: {note}"),
+        | None => format!("rule `{name}` is deprecated"),
+    }
+}
+
+/// Wrap the name of a rule, quoted in angle brackets in the BNF notation.
+///
+/// Rules annotated with `@token` are highlighted as tokens, and fragments
+/// like them but in italics.
+fn wrap_name(ctx: &Context, name: &str) -> String {
+    let symbol = ctx.rules.symbol(name);
+    let cls = if symbol.is_some_and(|s| ctx.rules.tokens.contains(&s)) {
+        "token"
+    } else if symbol.is_some_and(|s| ctx.rules.fragments.contains(&s)) {
+        "token syntax-fragment"
+    } else {
+        "identifier"
+    };
+    wrap_node_raw(&display_name(ctx, name), cls)
+}
+
+/// The name of a rule as displayed in the notation of the book.
+fn display_name(ctx: &Context, name: &str) -> String {
+    match ctx.config.notation {
+        | Notation::Native => name.to_string(),
+        | Notation::Bnf => format!("<{name}>"),
+    }
+}
+
+/// Wrap an identifier not referring to any rule of the book.
+fn wrap_unresolved(ctx: &Context, name: &str) -> String {
+    match ctx.config.unresolved {
+        | Unresolved::Plain => wrap_name(ctx, name),
+        | Unresolved::Warning => format!(
+            "<span class=\"syntax-identifier syntax-unresolved\" \
+             title=\"{title}\">{text}</span>",
+            title = encode_double_quoted_attribute(&unresolved_message(name)),
+            text = encode_safe(&display_name(ctx, name)),
+        ),
+        | Unresolved::Error => {
+            let mut error = SyntaxError::new(unresolved_message(name));
+            error.hint("consider defining the rule in a grammar block");
+            wrap_error_raw(&display_name(ctx, name), &error)
+        },
+    }
+}
+
+/// The message shown for an identifier not referring to any rule.
+pub fn unresolved_message(name: &str) -> String {
+    format!("rule `{name}` is not defined")
+}
+
+fn wrap_error(error: &SyntaxNode) -> String {
+    debug_assert_eq!(error.kind(), SyntaxKind::Error);
+    wrap_error_raw(error.text(), error.as_error().unwrap())
+}
+
+/// Render a block whose processing panicked as a single error showing the
+/// panic message.
+pub fn panic_block(code: &str, message: &str) -> String {
+    let mut error = SyntaxError::new(format!("internal error: {message}"));
+    error.hint("this is a bug in mdbook-grammar, please report it");
+    format!(
+        "<pre><code class=\"syntax\">{content}</code></pre>",
+        content = wrap_error_raw(code, &error)
+    )
+}
+
+pub fn wrap_node_raw(code: &str, cls: &str) -> String {
+    format!(
+        "<span class=\"syntax-{cls}\">{text}</span>",
+        cls = cls,
+        text = encode_safe(code)
+    )
+}
+
+/// Wrap the text of an error, escaping it, with its message and the JSON
+/// array of its hints as attributes.
+fn wrap_error_raw(code: &str, error: &SyntaxError) -> String {
+    let text = if code.trim().is_empty() {
+        "[error]".into()
+    } else {
+        encode_safe(code)
+    };
+    let hints = error.hints.iter().map(|hint| hint.as_str());
+    let hints = serde_json::to_string(&hints.collect::<Vec<_>>()).unwrap();
+
+    format!(
+        "<span class=\"syntax-error\" message=\"{message}\" \
+         hints=\"{hints}\">{text}</span>",
+        message = encode_double_quoted_attribute(&error.message),
+        hints = encode_double_quoted_attribute(&hints),
+    )
+}
+
+/// The anchor of the list of definitions of an overloaded rule.
+fn overloads_hash(name: &str) -> String {
+    format!("syntax-overloads-{}", anchor_name(name))
+}
+
+/// The bytes of names kept as they are in anchors, the others being
+/// percent-encoded.
+const ANCHOR_SET: &AsciiSet =
+    &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b':');
+
+/// The anchor of a rule from its name, like `syntax-rule-expr`.
+///
+/// Names in other scripts than Latin, like `выражение`, are percent-encoded,
+/// so that anchors are valid in every renderer.
+#[inline]
+pub fn rule_hash(name: impl ToString) -> String {
+    format!("syntax-rule-{}", anchor_name(&name.to_string()))
+}
+
+/// A name as part of an anchor, percent-encoded if needed.
+fn anchor_name(name: &str) -> String {
+    utf8_percent_encode(name, ANCHOR_SET).to_string()
+}
+
+/// The anchor of the definition of a rule, following the anchor scheme of
+/// the book.
+pub fn rule_anchor(config: &Config, rule: &SyntaxNode) -> String {
+    let hash = rule_hash(rule_name(rule));
+    match config.anchors {
+        | Anchors::Name => hash,
+        | Anchors::Content => format!("{hash}-{:04x}", signature(rule) >> 48),
+    }
+}
+
+/// The anchor of an extension of a rule, like `a += b;`, from its content.
+pub fn extension_anchor(rule: &SyntaxNode) -> String {
+    format!(
+        "{}-extension-{:04x}",
+        rule_hash(rule_name(rule)),
+        signature(rule) >> 48
+    )
+}
+
+/// The anchor of a labeled alternative of a rule, like `syntax-rule-expr-add`
+/// for `#add`.
+pub fn label_anchor(config: &Config, rule: &SyntaxNode, label: &str) -> String {
+    format!(
+        "{}-{}",
+        rule_anchor(config, rule),
+        anchor_name(label.trim_start_matches('#'))
+    )
+}
+
+/// An anchor of the page being rendered, as written in the page.
+fn page_anchor(ctx: &Context, anchor: &str) -> String {
+    match ctx.page {
+        | Some(page) => ctx.config.anchor(&page.href, anchor),
+        | None => anchor.into(),
+    }
+}
+
+/// The anchor of a section, like `syntax-section-binary-operators`.
+pub fn section_hash(title: &str) -> String {
+    format!("syntax-section-{}", slug(title))
+}
+
+/// The lowercase alphanumeric words of some text, joined with `-`.
+pub fn slug(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        book::render_page,
+        config::{Complexity, Links, Wildcard},
+        embed::render_grammar,
+    };
+    use mdbook_grammar_syntax::parse;
+
+    fn sym(rules: &Rules, name: &str) -> Symbol {
+        rules.symbol(name).unwrap()
+    }
+
+    fn render(code: &str) -> String {
+        render_with(code, &[], &Config::default())
+    }
+
+    fn render_with(code: &str, options: &[&str], config: &Config) -> String {
+        let rules = Rules::default();
+        parse_code(&ctx(config, &rules, None), &Block {
+            options: options.iter().map(|&option| option.into()).collect(),
+            ..block(code)
+        })
+    }
+
+    /// A grammar block of the code, at the first line of its chapter.
+    fn block(code: &str) -> Block {
+        Block {
+            code: parse(code),
+            options: Vec::new(),
+            line: 1,
+        }
+    }
+
+    /// A chapter holding a single grammar block.
+    fn page_at(href: &str, code: &str) -> Page {
+        Page {
+            href: href.into(),
+            name: "Page".into(),
+            items: vec![Item::Code(block(code))],
+        }
+    }
+
+    /// A chapter at `page.md` holding a single grammar block.
+    fn page(code: &str) -> Page {
+        page_at("page.md", code)
+    }
+
+    /// The context of rendering the page, or code outside of any page.
+    fn ctx<'a>(
+        config: &'a Config,
+        rules: &'a Rules,
+        page: Option<&'a Page>,
+    ) -> Context<'a> {
+        Context {
+            config,
+            rules,
+            page,
+            block: None,
+            params: &[],
+        }
+    }
+
+    #[test]
+    fn test_import_outside() {
+        let dir = std::env::temp_dir().join(format!(
+            "mdbook-grammar-import-outside-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("secret.syntax"), "secret: ;").unwrap();
+        std::fs::write(dir.join("src/lexer.syntax"), "ident: ;").unwrap();
+        let outside = dir.join("secret.syntax");
+
+        let page = page(&format!(
+            "%import \"lexer.syntax\";\n%import \
+             \"../secret.syntax\";\n%import \"{}\";",
+            outside.display()
+        ));
+        let config = Config {
+            src: Some(dir.join("src")),
+            ..Config::default()
+        };
+        let imports = import_grammars(std::slice::from_ref(&page), &config);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(imports.len(), 3);
+        assert!(imports["lexer.syntax"].is_ok());
+        assert_eq!(
+            imports["../secret.syntax"],
+            Err("cannot import `../secret.syntax` from outside the source \
+                 directory"
+                .into())
+        );
+        let absolute = EcoString::from(outside.display().to_string());
+        assert!(imports[&absolute].as_ref().is_err_and(|err| {
+            err.ends_with("from outside the source directory")
+        }));
+    }
+
+    #[test]
+    fn test_action_language() {
+        let html = render("a: b -> push(b);");
+        assert!(html.contains("<span class=\"syntax-function\">push</span>"));
+        let html = render("a: b -> { push(b) };");
+        assert!(html.contains("<span class=\"syntax-action\">"));
+
+        let html = render_with(
+            "a: b -> push(b);",
+            &["action=rust"],
+            &Config::default(),
+        );
+        assert!(html.contains("<code class=\"syntax-action language-rust\">"));
+    }
+
+    #[test]
+    fn test_pragma() {
+        let page = page("%start a;\n%skip ws;\na: ;\nws: \" \";");
+        let config = Config::default();
+        let rules = find_rules(std::slice::from_ref(&page), &config);
+        assert_eq!(rules.starts, [sym(&rules, "a")]);
+
+        let ctx = ctx(&config, &rules, Some(&page));
+        let html = render_page(&ctx, &page);
+        assert!(html.contains("<span class=\"syntax-pragma\""));
+        assert!(html.contains("<span class=\"syntax-keyword\">start</span>"));
+        assert!(html.contains("href=\"/page.md#syntax-rule-a\""));
+        assert!(html.contains("class=\"syntax-rule syntax-skipped\""));
+        assert!(html.contains("title=\"skipped between tokens\""));
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let html = render_grammar(
+            "expr: lexer::Ident;\nlexer::Ident: \"x\";",
+            &Config::default(),
+        );
+        assert!(html.contains("<a name=\"syntax-rule-lexer::Ident\"></a>"));
+        assert!(html.contains("href=\"#syntax-rule-lexer::Ident\""));
+
+        // A dot still matches any character between two items.
+        let html = render_grammar("a: b.c;\nb: ;\nc: ;", &Config::default());
+        assert!(html.contains("href=\"#syntax-rule-b\""));
+        assert!(html.contains("href=\"#syntax-rule-c\""));
+    }
+
+    #[test]
+    fn test_extensions() {
+        let pages =
+            vec![page_at("a.md", "x: \"a\";"), page_at("b.md", "x += \"b\";")];
+        let config = Config::default();
+        let rules = find_rules(&pages, &config);
+        assert_eq!(rules.links[&sym(&rules, "x")], "/a.md#syntax-rule-x");
+        assert!(rules.overloads.is_empty());
+
+        let ctx = ctx(&config, &rules, None);
+        let anchor = &rules.extensions[&sym(&rules, "x")][0].1;
+        assert!(anchor.starts_with("syntax-rule-x-extension-"));
+        assert!(
+            render_page(&ctx, &pages[0])
+                .contains(&format!("href=\"/b.md#{anchor}\""))
+        );
+        let html = render_page(&ctx, &pages[1]);
+        assert!(html.contains(&format!("<a name=\"{anchor}\">")));
+        assert!(html.contains("href=\"/a.md#syntax-rule-x\""));
+        assert!(html.contains("<span class=\"syntax-operator\">+=</span>"));
+    }
+
+    #[test]
+    fn test_used_by() {
+        let config = Config {
+            backlinks: true,
+            ..Config::default()
+        };
+        let html = render_grammar("a: b c;\nb: b c;\nc: ;\n_d: c;", &config);
+        assert_eq!(html.matches("syntax-used-by").count(), 2);
+        assert!(html.contains(
+            "<span class=\"syntax-used-by\">used by: <a class=\"syntax-link\" \
+             href=\"#syntax-rule-a\"><span \
+             class=\"syntax-identifier\">a</span></a></span>"
+        ));
+        assert!(html.contains(
+            "used by: <a class=\"syntax-link\" href=\"#syntax-rule-a\"><span \
+             class=\"syntax-identifier\">a</span></a>, <a \
+             class=\"syntax-link\" href=\"#syntax-rule-b\">"
+        ));
+        assert!(
+            !render_grammar("a: b;\nb: ;", &Config::default())
+                .contains("syntax-used-by")
+        );
+    }
+
+    #[test]
+    fn test_preview() {
+        let code = "a: b;\n/// The b.\nb: \"x\" | c;\nc: ;";
+        let config = Config {
+            preview: true,
+            ..Config::default()
+        };
+        let html = render_grammar(code, &config);
+        assert!(html.contains(
+            "href=\"#syntax-rule-b\" data-preview=\"b: &quot;x&quot; | c;\" \
+             data-doc=\"The b.\">"
+        ));
+        assert!(html.contains("data-preview=\"c: ;\""));
+        assert!(!html.contains(" title=\"The b.\""));
+
+        let html = render_grammar(code, &Config::default());
+        assert!(!html.contains("data-preview"));
+        assert!(html.contains(" title=\"The b.\""));
+    }
+
+    #[test]
+    fn test_deprecated() {
+        let html = render_grammar(
+            "a: b c;\n@deprecated(use c)\nb: ;\n@deprecated\nc: ;",
+            &Config::default(),
+        );
+        assert!(html.contains("class=\"syntax-rule syntax-deprecated\""));
+        assert!(html.contains(
+            "<s class=\"syntax-deprecated-name\" title=\"rule `b` is \
+             deprecated: use c\">"
+        ));
+        assert_eq!(html.matches("syntax-deprecated-use").count(), 2);
+        assert_eq!(html.matches("syntax-deprecated-notice").count(), 2);
+    }
+
+    #[test]
+    fn test_since() {
+        let code = "@since(\"1.2\")\na: b;\nb: ;";
+        assert!(render(code).contains(
+            "<span class=\"syntax-badge syntax-since\" title=\"since \
+             1.2\">1.2</span>"
+        ));
+
+        let config = Config {
+            changelog: Some("changelog.md".into()),
+            ..Config::default()
+        };
+        let page = page(code);
+        let badge = "<a class=\"syntax-badge syntax-since\" \
+                     href=\"/changelog.md#12\" title=\"since 1.2\">1.2</a>";
+        assert!(
+            render_grammar(code, &config).contains("href=\"changelog.md#12\"")
+        );
+        assert!(
+            rule_list(&page, &config).contains(&format!("</a>{badge}</li>"))
+        );
+    }
+
+    #[test]
+    fn test_fragment() {
+        let html = render_grammar(
+            "NUMBER: DIGIT+;\nfragment DIGIT: \"0\";",
+            &Config::default(),
+        );
+        assert!(
+            html.contains("<span class=\"syntax-keyword\">fragment</span>")
+        );
+        assert_eq!(html.matches("syntax-token syntax-fragment").count(), 2);
+    }
+
+    #[test]
+    fn test_named_group() {
+        let code = "a: (sub: \"x\" | b) c;\nb: ;\nc: sub;";
+        let html = render_grammar(code, &Config::default());
+        assert!(html.contains(
+            "<span class=\"syntax-named-group\"><a \
+             name=\"syntax-rule-sub\"></a>"
+        ));
+        assert_eq!(html.matches("href=\"#syntax-rule-sub\"").count(), 2);
+
+        let page = page(code);
+        let list = rule_list(&page, &Config::default());
+        let names = ["a", "sub", "b", "c"].map(|name| {
+            list.find(&format!("href=\"#syntax-rule-{name}\"")).unwrap()
+        });
+        assert!(names.is_sorted());
+    }
+
+    #[test]
+    fn test_unicode_names() {
+        let html =
+            render_grammar("выражение: терм;\nтерм: ;", &Config::default());
+        assert!(html.contains(
+            "href=\"#syntax-rule-%D1%82%D0%B5%D1%80%D0%BC\"><span \
+             class=\"syntax-identifier\">терм</span></a>"
+        ));
+        assert_eq!(rule_hash("lexer::ident_2"), "syntax-rule-lexer::ident_2");
+
+        // Overloaded rules link to the list of their definitions.
+        let mut config = Config::default();
+        config.lints.insert(Lint::DuplicateRule, Level::Allow);
+        let html = render_grammar("терм: ;\nтерм: \"x\";", &config);
+        let id = "syntax-overloads-%D1%82%D0%B5%D1%80%D0%BC";
+        assert!(html.contains(&format!("href=\"#{id}\"")));
+        assert_eq!(overloads_hash("терм"), id);
+    }
+
+    #[test]
+    fn test_empty() {
+        let html = render("a: empty | \"x\" a;");
+        assert!(
+            html.contains(
+                "<span class=\"syntax-empty\" title=\"empty\">ε</span>"
+            )
+        );
+        assert_eq!(html, render("a: ε | \"x\" a;"));
+    }
+
+    #[test]
+    fn test_condition() {
+        let page = page("a: b if strict && !c; c: ;");
+        let config = Config::default();
+        let rules = find_rules(std::slice::from_ref(&page), &config);
+        let ctx = ctx(&config, &rules, Some(&page));
+        let html = render_page(&ctx, &page);
+        assert!(html.contains("<span class=\"syntax-variable\">strict</span>"));
+        assert!(html.contains(
+            "<a class=\"syntax-link\" href=\"/page.md#syntax-rule-c\"><span \
+             class=\"syntax-variable\">c</span></a>"
+        ));
+        assert!(
+            html.contains("<span class=\"syntax-operator\">&amp;&amp;</span>")
+        );
+    }
+
+    #[test]
+    fn test_notation() {
+        let config = Config {
+            notation: Notation::Bnf,
+            ..Config::default()
+        };
+        let html = render_with("a: b;", &[], &config);
+        assert!(html.contains("<span class=\"syntax-operator\">::=</span>"));
+        assert!(
+            html.contains("<span class=\"syntax-identifier\">&lt;b&gt;</span>")
+        );
+    }
+
+    #[test]
+    fn test_link_helpers() {
+        let pages = vec![page("a: _b; _b: _c; _c: ; _d: ;")];
+
+        let mut config = Config::default();
+        let rules = find_rules(&pages, &config);
+        assert!(!rules.links.contains_key(&sym(&rules, "_c")));
+
+        config.link_helpers = true;
+        let rules = find_rules(&pages, &config);
+        assert_eq!(rules.links[&sym(&rules, "_c")], "/page.md#syntax-rule-a");
+        assert_eq!(rules.links[&sym(&rules, "_b")], "/page.md#syntax-rule-a");
+        assert!(!rules.links.contains_key(&sym(&rules, "_d")));
+    }
+
+    #[test]
+    fn test_overloads() {
+        let pages = vec![
+            Page {
+                name: "A".into(),
+                ..page_at("a.md", "x: ; x: y;")
+            },
+            Page {
+                name: "B".into(),
+                ..page_at("b.md", "x: ; y: ;")
+            },
+        ];
+
+        let mut config = Config::default();
+        let rules = find_rules(&pages, &config);
+        assert_eq!(rules.links[&sym(&rules, "x")], "/b.md#syntax-rule-x");
+        assert!(rules.overloads.is_empty());
+
+        config.lints.insert(Lint::DuplicateRule, Level::Allow);
+        let rules = find_rules(&pages, &config);
+        assert_eq!(rules.links[&sym(&rules, "x")], "/a.md#syntax-overloads-x");
+        assert_eq!(rules.overloads[&sym(&rules, "x")], [
+            ("a.md".into(), "A".into()),
+            ("b.md".into(), "B".into())
+        ]);
+
+        let ctx = ctx(&config, &rules, None);
+        let Item::Code(block) = &pages[0].items[0] else {
+            unreachable!()
+        };
+        let mut listed = HashSet::new();
+        let html = overload_lists(&ctx, &pages[0], block, &mut listed);
+        assert!(html.contains("id=\"syntax-overloads-x\""));
+        assert!(html.contains("href=\"/b.md#syntax-rule-x\">B</a>"));
+        assert_eq!(overload_lists(&ctx, &pages[0], block, &mut listed), "");
+    }
+
+    #[test]
+    fn test_scope() {
+        let pages =
+            vec![page_at("a.md", "x: y; y: ;"), page_at("b.md", "y: ;")];
+
+        let mut config = Config::default();
+        let rules = find_rules(&pages, &config);
+        let link = |config: &Config, chapter: &str, name: &str| {
+            rules.link(config, Some(&chapter.into()), name)
+        };
+        assert_eq!(link(&config, "a.md", "y").unwrap(), "/b.md#syntax-rule-y");
+
+        config.scope = Scope::Chapter;
+        assert_eq!(link(&config, "a.md", "y").unwrap(), "/a.md#syntax-rule-y");
+        assert_eq!(link(&config, "b.md", "x").unwrap(), "/a.md#syntax-rule-x");
+        assert_eq!(link(&config, "c.md", "y").unwrap(), "/b.md#syntax-rule-y");
+    }
+
+    #[test]
+    fn test_fragment_anchors() {
+        let pages =
+            vec![page_at("a.md", "x: y; y: ;"), page_at("dir/b.md", "y: ;")];
+        let config = Config {
+            links: Links::Fragments,
+            scope: Scope::Chapter,
+            ..Config::default()
+        };
+        let rules = find_rules(&pages, &config);
+        let ctx = ctx(&config, &rules, None);
+
+        let html = render_page(&ctx, &pages[0]);
+        assert!(html.contains("name=\"a-syntax-rule-y\""));
+        assert!(html.contains("href=\"#a-syntax-rule-y\""));
+        let html = render_page(&ctx, &pages[1]);
+        assert!(html.contains("name=\"dir-b-syntax-rule-y\""));
+        assert!(!html.contains("\"syntax-rule-y\""));
+    }
+
+    #[test]
+    fn test_content_anchors() {
+        let root = parse("x: a; x: b;");
+        let mut rules =
+            root.children().filter(|n| n.kind() == SyntaxKind::Rule);
+        let (first, second) = (rules.next().unwrap(), rules.next().unwrap());
+
+        let mut config = Config::default();
+        assert_eq!(rule_anchor(&config, first), rule_anchor(&config, second));
+
+        config.anchors = Anchors::Content;
+        let anchor = rule_anchor(&config, first);
+        assert!(anchor.starts_with("syntax-rule-x-"));
+        assert_eq!(anchor.len(), "syntax-rule-x-".len() + 4);
+        assert_ne!(anchor, rule_anchor(&config, second));
+
+        let html = render_with("x: a;", &[], &config);
+        assert!(html.contains(&format!("name=\"{anchor}\"")));
+    }
+
+    #[test]
+    fn test_source_link() {
+        let config = Config {
+            repository: Some("https://github.com/org/repo/".into()),
+            ..Config::default()
+        };
+        let rules = Rules::default();
+        let page = page_at("dir/page.md", "");
+        let ctx = ctx(&config, &rules, Some(&page));
+
+        let html = parse_code(&ctx, &Block {
+            line: 3,
+            ..block("a: ;\n\n// b\nb: ;")
+        });
+        assert!(html.contains(
+            "href=\"https://github.com/org/repo/blob/main/src/dir/page.md#L3\""
+        ));
+        assert!(html.contains("page.md#L6\""));
+    }
+
+    #[test]
+    fn test_range() {
+        let html = render(r#"a: "\u{41}".."Z";"#);
+        assert!(html.contains("title=\"U+0041 – U+005A\""));
+        assert!(html.contains("<span class=\"syntax-operator\">–</span>"));
+
+        let html = render(r#"a: "A"..="Z";"#);
+        assert!(html.contains("title=\"U+0041 – U+005A\""));
+        assert!(html.contains("<span class=\"syntax-operator\">–</span>"));
+
+        let html = render(r#"a: "A"..<"Z";"#);
+        assert!(html.contains("title=\"U+0041 – U+0059\""));
+        assert!(html.contains("<span class=\"syntax-operator\">..&lt;</span>"));
+    }
+
+    #[test]
+    fn test_char_class() {
+        assert!(render("a: [a-z_];").contains(
+            "<span class=\"syntax-class\" title=\"U+0061 – U+007A, \
+             U+005F\">[a-z_]</span>"
+        ));
+        assert!(
+            render("a: ~[0-9];")
+                .contains("title=\"any character outside U+0030 – U+0039\"")
+        );
+    }
+
+    #[test]
+    fn test_sections() {
+        let code = "//! Binary operators\nadd: a \"+\" a;\n// comment\n//! \
+                    Atoms\na: \"a\";";
+        let html = render(code);
+        assert!(html.contains(
+            "<a class=\"syntax-section\" \
+             id=\"syntax-section-binary-operators\" \
+             href=\"#syntax-section-binary-operators\">Binary operators</a>"
+        ));
+        assert!(html.contains("syntax-comment"));
+
+        let page = page(&format!("top: ;\n{code}"));
+        let list = rule_list(&page, &Config::default());
+        let link = |href: &str, text: &str| {
+            format!("<a class=\"syntax-link\" href=\"#{href}\">{text}</a>")
+        };
+        let rule = |name: &str| {
+            let name_html =
+                format!("<span class=\"syntax-identifier\">{name}</span>");
+            format!("<li>{}</li>", link(&rule_hash(name), &name_html))
+        };
+        assert_eq!(
+            list,
+            format!(
+                "<ul class=\"syntax-rule-list\">{}<li>{}<ul>{}</ul></\
+                 li><li>{}<ul>{}</ul></li></ul>\n\n",
+                rule("top"),
+                link("syntax-section-binary-operators", "Binary operators"),
+                rule("add"),
+                link("syntax-section-atoms", "Atoms"),
+                rule("a"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_constraint() {
+        let html = render("a: b [not a keyword] c[x];");
+        assert!(html.contains(
+            "<span class=\"syntax-identifier\">b</span> <sup \
+             class=\"syntax-constraint\">not a keyword</sup>"
+        ));
+        assert!(!html.contains("<sup class=\"syntax-constraint\">x"));
+    }
+
+    #[test]
+    fn test_start() {
+        let html = render_with("a: b;\nb: ;", &["start=b"], &Config::default());
+        assert!(html.contains("class=\"syntax-rule\" rule=\"syntax-rule-a\""));
+        assert!(html.contains(
+            "class=\"syntax-rule syntax-start\" rule=\"syntax-rule-b\""
+        ));
+
+        let page = Page {
+            items: vec![Item::Code(Block {
+                options: vec!["start=b".into()],
+                ..block("a: b;\nb: ;")
+            })],
+            ..page("")
+        };
+        let list = rule_list(&page, &Config::default());
+        let position = |name| list.find(&rule_hash(name)).unwrap();
+        assert!(position("b") < position("a"));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert!(
+            render("a: .;")
+                .contains("<span class=\"syntax-operator\">.</span>")
+        );
+
+        let config = Config {
+            wildcard: Some(Wildcard::Character),
+            ..Config::default()
+        };
+        let html = render_with("a: .;", &[], &config);
+        assert!(html.contains("title=\"any character\">.</span>"));
+    }
+
+    #[test]
+    fn test_anchors() {
+        let html = render("a: ^ b $;");
+        assert!(html.contains(
+            "<span class=\"syntax-operator\" title=\"start of a \
+             line\">^</span>"
+        ));
+        assert!(html.contains("title=\"end of a line\">$</span>"));
+        assert!(!html.contains("syntax-error"));
+    }
+
+    #[test]
+    fn test_complexity_badge() {
+        let code = "a: b | c | d;";
+        let mut config = Config {
+            complexity: Complexity {
+                alternatives: 2,
+                ..Complexity::default()
+            },
+            ..Config::default()
+        };
+        assert!(!render_with(code, &[], &config).contains("syntax-complex"));
+
+        config.lints.insert(Lint::ComplexRule, Level::Warn);
+        assert!(render_with(code, &[], &config).contains(
+            "<span class=\"syntax-badge syntax-complex\" title=\"complex \
+             rule: 3 alternatives\">◆</span>"
+        ));
+    }
+
+    #[test]
+    fn test_converse() {
+        assert!(render("a: ~\"a\"..\"z\";").contains(
+            "<span class=\"syntax-converse\" title=\"any character outside \
+             U+0061 – U+007A\">"
+        ));
+        assert!(render("a: ~<newline>;").contains(
+            "title=\"any character not matched by &lt;newline&gt;\""
+        ));
+        assert!(!render("a: ~b;").contains("syntax-converse"));
+    }
+
+    #[test]
+    fn test_reference_arguments() {
+        let page = page("list(item): item;\na: list(b) list(b, b);\nb: ;");
+        let config = Config::default();
+        let rules = find_rules(std::slice::from_ref(&page), &config);
+        assert_eq!(rules.arities, HashMap::from([(sym(&rules, "list"), 1)]));
+
+        let ctx = ctx(&config, &rules, Some(&page));
+        let html = render_page(&ctx, &page);
+        assert!(html.contains("<span class=\"syntax-param\">item</span>"));
+        assert!(html.contains("title=\"`list` takes 1 argument\""));
+        assert!(html.contains("title=\"`list` takes 1 argument, given 2\""));
+        assert!(!html.contains("syntax-unresolved"));
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let page = page("a: b;\n/// The \"b\" rule.\n/// Empty.\nb: ;");
+        let config = Config::default();
+        let rules = find_rules(std::slice::from_ref(&page), &config);
+        let ctx = ctx(&config, &rules, Some(&page));
+        let html = render_page(&ctx, &page);
+        assert!(html.contains(
+            "<a class=\"syntax-link\" href=\"/page.md#syntax-rule-b\" \
+             title=\"The &quot;b&quot; rule.\nEmpty.\">"
+        ));
+        assert!(html.contains("<span class=\"syntax-comment syntax-doc\">"));
+
+        let Item::Code(block) = &page.items[0] else {
+            unreachable!()
+        };
+        assert_eq!(rule_lines(block), [1, 4]);
+    }
+
+    #[test]
+    fn test_unresolved_error() {
+        let page = page("a: b;");
+        let config = Config {
+            notation: Notation::Bnf,
+            unresolved: Unresolved::Error,
+            ..Config::default()
+        };
+        let rules = find_rules(std::slice::from_ref(&page), &config);
+        let ctx = ctx(&config, &rules, Some(&page));
+        let html = render_page(&ctx, &page);
+        assert!(html.contains(
+            "<span class=\"syntax-error\" message=\"rule `b` is not defined\" \
+             hints=\"[&quot;consider defining the rule in a grammar \
+             block&quot;]\">&lt;b&gt;</span>"
+        ));
+        assert!(!html.contains("<b>"));
+
+        let html = panic_block("a: \"<\";", "index \"out\" of bounds");
+        assert!(html.contains(
+            "message=\"internal error: index &quot;out&quot; of bounds\""
+        ));
+        assert!(html.contains(">a: &quot;&lt;&quot;;</span>"));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let page = page("a: b c;\n@hidden b: ;\n@token(x) c: ;");
+        let config = Config::default();
+        let rules = find_rules(std::slice::from_ref(&page), &config);
+        assert!(!rules.links.contains_key(&sym(&rules, "b")));
+        assert!(rules.tokens.contains(&sym(&rules, "c")));
+
+        let ctx = ctx(&config, &rules, Some(&page));
+        let html = render_page(&ctx, &page);
+        assert!(html.contains("> <span class=\"syntax-identifier\">b</span>"));
+        assert!(html.contains("<span class=\"syntax-token\">c</span></a>"));
+        assert!(
+            html.contains("<span class=\"syntax-annotation\">@token(x)</span>")
+        );
+        assert!(!html.contains("syntax-rule-b"));
+    }
+
+    #[test]
+    fn test_meta_link() {
+        let rules = Rules {
+            metas: HashMap::from([(
+                "<digit>".into(),
+                "/metas.md#syntax-meta-digit".into(),
+            )]),
+            ..Rules::default()
+        };
+        let config = Config::default();
+        let html = parse_code(
+            &ctx(&config, &rules, None),
+            &block("a: <digit> <other>;"),
+        );
+        assert!(html.contains(
+            "<a class=\"syntax-link\" \
+             href=\"/metas.md#syntax-meta-digit\"><span \
+             class=\"syntax-meta\">&lt;digit&gt;</span></a>"
+        ));
+        assert!(
+            html.contains("<span class=\"syntax-meta\">&lt;other&gt;</span>")
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let code = "a: <indent> b <dedent> <other>;";
+        let html = render(code);
+        assert!(!html.contains("syntax-layout"));
+
+        let config = Config {
+            layout: true,
+            ..Config::default()
+        };
+        let html = render_with(code, &[], &config);
+        assert!(html.contains(
+            "<span class=\"syntax-meta syntax-layout\" title=\"the \
+             indentation increases\">&lt;indent&gt;</span>"
+        ));
+        assert!(html.contains("<span class=\"syntax-meta\">&lt;other&gt;"));
+    }
+
+    #[test]
+    fn test_looking() {
+        let html = render("a: (?<! b);");
+        assert!(html.contains("syntax-lookbehind syntax-negative"));
+        assert!(html.contains("<span class=\"syntax-badge\">⇠</span>"));
+
+        let html = render("a: !b;");
+        assert!(html.contains("syntax-lookahead syntax-negative"));
+        assert!(html.contains("<span class=\"syntax-operator\">!</span>"));
+    }
+
+    #[test]
+    fn test_separated() {
+        let html = render("r: arg % \",\";");
+        assert!(
+            html.contains(
+                "title=\"one or more occurrences separated by \
+                 &quot;,&quot;\">%</span>"
+            ),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn test_difference() {
+        let html = render("r: . - \"x\";");
+        assert!(
+            html.contains(
+                "<span class=\"syntax-operator\" title=\"excluding the \
+                 matches of &quot;x&quot;\">-</span>"
+            ),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn test_optional() {
+        let html = render("r: a [ \",\" a ];");
+        assert!(
+            html.contains(
+                "<span class=\"syntax-repetition\" title=\"optional\">[</span>"
+            ),
+            "{html}"
+        );
+        assert!(html.contains("title=\"optional\">]</span>"), "{html}");
+    }
+
+    #[test]
+    fn test_label() {
+        let html = render("expr: #add expr \"+\" expr | #mul expr;\n_a: #x a;");
+        assert!(
+            html.contains(
+                "<a class=\"syntax-label\" id=\"syntax-rule-expr-add\" \
+                 href=\"#syntax-rule-expr-add\">#add</a>"
+            ),
+            "{html}"
+        );
+        assert!(html.contains("id=\"syntax-rule-expr-mul\""), "{html}");
+        assert!(
+            html.contains("<span class=\"syntax-label\">#x</span>"),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn test_insensitive() {
+        let html = render("select: i\"select\";");
+        assert!(
+            html.contains(
+                "<span class=\"syntax-modifier\" \
+                 title=\"case-insensitive\">i</span><span \
+                 class=\"syntax-string\">&quot;select&quot;</span>"
+            ),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn test_raw_string() {
+        let html = render(r##"path: r#"C:\"#;"##);
+        assert!(
+            html.contains(
+                "<span class=\"syntax-string\">r#&quot;C:\\&quot;#</span>"
+            ),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn test_repetition() {
+        for (code, title) in [
+            ("a?", "optional"),
+            ("a*", "any number of occurrences"),
+            ("a+?", "at least 1 occurrence, matching as few as possible"),
+            ("a{2}", "exactly 2 occurrences"),
+            ("a{2,}", "at least 2 occurrences"),
+            ("a{2, 5}", "between 2 and 5 occurrences"),
+        ] {
+            let html = render(&format!("r: {code};"));
+            assert!(html.contains(&format!("title=\"{title}\"")), "{html}");
+        }
+    }
 }
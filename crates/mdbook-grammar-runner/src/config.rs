@@ -0,0 +1,1239 @@
+use crate::{Diagnostic, Level, Lint, Palette};
+use ecow::{EcoString, eco_format};
+use mdbook::preprocess::PreprocessorContext;
+use mdbook_grammar_syntax::{MAX_DEPTH, ParseOptions};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// The name of the preprocessor table in `book.toml`.
+pub const NAME: &str = "grammar";
+
+/// The keys of the preprocessor table handled by mdbook itself.
+const MDBOOK_KEYS: &[&str] =
+    &["command", "renderers", "before", "after", "optional"];
+
+/// The keys of the preprocessor table, with the types of their values.
+const KEYS: &[(&str, &str)] = &[
+    ("rule-list", "boolean"),
+    ("railroad", "boolean"),
+    ("backlinks", "boolean"),
+    ("preview", "boolean"),
+    ("reflow", "boolean"),
+    ("unresolved", "string"),
+    ("strict", "boolean"),
+    ("max-depth", "integer"),
+    ("notation", "string"),
+    ("links", "string"),
+    ("scope", "string"),
+    ("anchors", "string"),
+    ("palette", "string"),
+    ("link-helpers", "boolean"),
+    ("layout", "boolean"),
+    ("wildcard", "string"),
+    ("metas", "string"),
+    ("changelog", "string"),
+    ("index", "string"),
+    ("index-title", "string"),
+    ("index-position", "string"),
+    ("action-language", "string"),
+    ("cache", "string"),
+    ("search-index", "string"),
+    ("metadata", "boolean"),
+    ("timings", "boolean"),
+    ("repository", "string"),
+    ("branch", "string"),
+    ("source-root", "string"),
+    ("aliases", "table"),
+    ("lints", "table"),
+    ("naming", "table"),
+    ("complexity", "table"),
+    ("profile", "table"),
+    ("renderer", "table"),
+];
+
+/// The keys of the naming table, with the types of their values.
+const NAMING_KEYS: &[(&str, &str)] = &[
+    ("case", "string"),
+    ("uppercase-terminals", "boolean"),
+    ("forbidden-prefixes", "array"),
+];
+
+/// The keys of the complexity table, whose values are all integers.
+const COMPLEXITY_KEYS: &[&str] = &["alternatives", "depth", "tokens"];
+
+/// Options of the preprocessor, read from `[preprocessor.grammar]`.
+#[derive(Clone, Debug, Hash)]
+pub struct Config {
+    /// The root url of the site, prepended to links between chapters.
+    pub site_url: EcoString,
+    /// How links to rules are written.
+    pub links: Links,
+    /// Where references to rules are resolved first.
+    pub scope: Scope,
+    /// How the anchors of rule definitions are named.
+    pub anchors: Anchors,
+    /// Whether to list the rules defined in a chapter at its top.
+    pub rule_list: bool,
+    /// Whether to draw a railroad diagram of each public rule after its
+    /// block.
+    pub railroad: bool,
+    /// Whether to list the rules referencing each rule under its definition.
+    pub backlinks: bool,
+    /// Whether hovering a link to a rule shows its definition in a popover.
+    pub preview: bool,
+    /// Whether to reflow grammar blocks with the formatter by default.
+    pub reflow: bool,
+    /// How identifiers not referring to any rule are rendered.
+    pub unresolved: Unresolved,
+    /// Whether errors in grammar blocks fail the build.
+    pub strict: bool,
+    /// How deep items of grammar blocks may be nested, deeper ones being
+    /// reported as errors.
+    pub max_depth: usize,
+    /// The notation in which rules are displayed.
+    pub notation: Notation,
+    /// The palette of the stylesheet added to chapters, if any.
+    pub palette: Option<Palette>,
+    /// Whether references to helper rules, whose names start with `_`, link
+    /// to the nearest public rule using them.
+    pub link_helpers: bool,
+    /// Whether the metas `<indent>`, `<dedent>`, `<newline>` and `<eof>` are
+    /// recognized as layout tokens.
+    pub layout: bool,
+    /// What the wildcard `.` matches, if stated.
+    pub wildcard: Option<Wildcard>,
+    /// The path of the chapter defining the metas, relative to the source
+    /// directory, if any.
+    pub metas: Option<EcoString>,
+    /// The path of the chapter listing the changes of each version, relative
+    /// to the source directory, linked from the badges of `@since`, if any.
+    pub changelog: Option<EcoString>,
+    /// The path of the generated chapter indexing the rules, relative to the
+    /// source directory, if any.
+    pub index: Option<EcoString>,
+    /// The title of the index chapter.
+    pub index_title: EcoString,
+    /// Where the index chapter is added to the book.
+    pub index_position: Position,
+    /// The language of the code in actions and parameters, if any.
+    pub action_language: Option<EcoString>,
+    /// The fence tags of grammar blocks, with their default block options.
+    pub aliases: BTreeMap<EcoString, Vec<EcoString>>,
+    /// The levels of lints, overriding their default levels.
+    pub lints: BTreeMap<Lint, Level>,
+    /// The naming conventions of rules, checked by the `rule-naming` lint.
+    pub naming: Naming,
+    /// The limits of rules, checked by the `complex-rule` lint.
+    pub complexity: Complexity,
+    /// The directory caching rendered chapters, if any.
+    pub cache: Option<PathBuf>,
+    /// The file receiving the search index of the rules, if any.
+    pub search_index: Option<PathBuf>,
+    /// Whether to embed metadata of the rules in chapters, for search
+    /// engines.
+    pub metadata: bool,
+    /// Whether to report the time spent in each phase, as a note.
+    pub timings: bool,
+    /// The url of the repository of the book, to link rules to their source.
+    pub repository: Option<EcoString>,
+    /// The branch of the repository to link to.
+    pub branch: EcoString,
+    /// The path of the source directory of the book in the repository.
+    pub source_root: EcoString,
+    /// The source directory of the book on disk, to read the grammars
+    /// imported with `%import`, if known.
+    pub src: Option<PathBuf>,
+}
+
+impl Config {
+    /// Read the options from the context of the preprocessor.
+    ///
+    /// The cache is resolved relative to the root of the book, and the search
+    /// index relative to its source directory, so that it is copied to the
+    /// output along with the other static files. Imports are read from the
+    /// source directory.
+    pub fn from_context(ctx: &PreprocessorContext) -> Self {
+        let mut config = Self::from_book(&ctx.config, &ctx.renderer);
+        config.cache = config.cache.map(|dir| ctx.root.join(dir));
+        config.search_index = config
+            .search_index
+            .map(|file| ctx.root.join(&ctx.config.book.src).join(file));
+        config.src = Some(ctx.root.join(&ctx.config.book.src));
+        config
+    }
+
+    /// Read the options from the configuration of the book, as used by the
+    /// given renderer.
+    ///
+    /// Options can be overridden for the `serve` or `build` profile in
+    /// `[preprocessor.grammar.profile.<profile>]`, and for a renderer in
+    /// `[preprocessor.grammar.renderer.<renderer>]`.
+    pub fn from_book(book: &mdbook::Config, renderer: &str) -> Self {
+        let mut config = Self::default();
+
+        if let Some(url) = book
+            .get("output")
+            .and_then(|output| output.get("html"))
+            .and_then(|html| html.get("site-url"))
+            .and_then(|url| url.as_str())
+        {
+            config.site_url = url.into();
+        }
+        config.source_root = book.book.src.to_string_lossy().into();
+        config.links = Links::for_renderer(renderer);
+
+        // Overrides of the profile, then of the renderer, apply over the
+        // options of the whole book.
+        let base = format!("preprocessor.{NAME}");
+        let profile = if book.get("output.html.live-reload-endpoint").is_some()
+        {
+            "serve"
+        } else {
+            "build"
+        };
+        let tables = [
+            base.clone(),
+            format!("{base}.profile.{profile}"),
+            format!("{base}.renderer.{renderer}"),
+        ];
+
+        for table in tables.iter().filter_map(|key| book.get(key)?.as_table()) {
+            if let Some(rule_list) =
+                table.get("rule-list").and_then(|v| v.as_bool())
+            {
+                config.rule_list = rule_list;
+            }
+
+            if let Some(railroad) =
+                table.get("railroad").and_then(|v| v.as_bool())
+            {
+                config.railroad = railroad;
+            }
+
+            if let Some(backlinks) =
+                table.get("backlinks").and_then(|v| v.as_bool())
+            {
+                config.backlinks = backlinks;
+            }
+
+            if let Some(preview) =
+                table.get("preview").and_then(|v| v.as_bool())
+            {
+                config.preview = preview;
+            }
+
+            if let Some(reflow) = table.get("reflow").and_then(|v| v.as_bool())
+            {
+                config.reflow = reflow;
+            }
+
+            if let Some(unresolved) = table
+                .get("unresolved")
+                .and_then(|v| v.as_str())
+                .and_then(Unresolved::from_name)
+            {
+                config.unresolved = unresolved;
+            }
+
+            if let Some(strict) = table.get("strict").and_then(|v| v.as_bool())
+            {
+                config.strict = strict;
+            }
+
+            if let Some(max_depth) = table
+                .get("max-depth")
+                .and_then(|v| v.as_integer())
+                .and_then(|v| usize::try_from(v).ok())
+                .filter(|&v| v > 0)
+            {
+                config.max_depth = max_depth;
+            }
+
+            if let Some(notation) = table
+                .get("notation")
+                .and_then(|v| v.as_str())
+                .and_then(Notation::from_name)
+            {
+                config.notation = notation;
+            }
+
+            if let Some(scope) = table
+                .get("scope")
+                .and_then(|v| v.as_str())
+                .and_then(Scope::from_name)
+            {
+                config.scope = scope;
+            }
+
+            if let Some(anchors) = table
+                .get("anchors")
+                .and_then(|v| v.as_str())
+                .and_then(Anchors::from_name)
+            {
+                config.anchors = anchors;
+            }
+
+            if let Some(links) = table
+                .get("links")
+                .and_then(|v| v.as_str())
+                .and_then(Links::from_name)
+            {
+                config.links = links;
+            }
+
+            if let Some(palette) = table
+                .get("palette")
+                .and_then(|v| v.as_str())
+                .and_then(Palette::from_name)
+            {
+                config.palette = Some(palette);
+            }
+
+            if let Some(link_helpers) =
+                table.get("link-helpers").and_then(|v| v.as_bool())
+            {
+                config.link_helpers = link_helpers;
+            }
+
+            if let Some(layout) = table.get("layout").and_then(|v| v.as_bool())
+            {
+                config.layout = layout;
+            }
+
+            if let Some(wildcard) = table
+                .get("wildcard")
+                .and_then(|v| v.as_str())
+                .and_then(Wildcard::from_name)
+            {
+                config.wildcard = Some(wildcard);
+            }
+
+            if let Some(metas) = table.get("metas").and_then(|v| v.as_str()) {
+                config.metas = Some(metas.into());
+            }
+
+            if let Some(changelog) =
+                table.get("changelog").and_then(|v| v.as_str())
+            {
+                config.changelog = Some(changelog.into());
+            }
+
+            if let Some(index) = table.get("index").and_then(|v| v.as_str()) {
+                config.index = Some(index.into());
+            }
+
+            if let Some(title) =
+                table.get("index-title").and_then(|v| v.as_str())
+            {
+                config.index_title = title.into();
+            }
+
+            if let Some(position) = table
+                .get("index-position")
+                .and_then(|v| v.as_str())
+                .and_then(Position::from_name)
+            {
+                config.index_position = position;
+            }
+
+            if let Some(language) =
+                table.get("action-language").and_then(|v| v.as_str())
+            {
+                config.action_language = Some(language.into());
+            }
+
+            if let Some(cache) = table.get("cache").and_then(|v| v.as_str()) {
+                config.cache = Some(cache.into());
+            }
+
+            if let Some(timings) =
+                table.get("timings").and_then(|v| v.as_bool())
+            {
+                config.timings = timings;
+            }
+
+            if let Some(repository) =
+                table.get("repository").and_then(|v| v.as_str())
+            {
+                config.repository = Some(repository.into());
+            }
+
+            if let Some(branch) = table.get("branch").and_then(|v| v.as_str()) {
+                config.branch = branch.into();
+            }
+
+            if let Some(root) =
+                table.get("source-root").and_then(|v| v.as_str())
+            {
+                config.source_root = root.into();
+            }
+
+            if let Some(file) =
+                table.get("search-index").and_then(|v| v.as_str())
+            {
+                config.search_index = Some(file.into());
+            }
+
+            if let Some(metadata) =
+                table.get("metadata").and_then(|v| v.as_bool())
+            {
+                config.metadata = metadata;
+            }
+
+            if let Some(aliases) =
+                table.get("aliases").and_then(|v| v.as_table())
+            {
+                for (alias, options) in aliases {
+                    let options = options
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|option| option.as_str())
+                        .map(Into::into)
+                        .collect();
+                    config.aliases.insert(alias.into(), options);
+                }
+            }
+
+            if let Some(lints) = table.get("lints").and_then(|v| v.as_table()) {
+                for (lint, level) in lints {
+                    let lint = Lint::from_name(lint);
+                    let level = level.as_str().and_then(Level::from_name);
+                    if let (Some(lint), Some(level)) = (lint, level) {
+                        config.lints.insert(lint, level);
+                    }
+                }
+            }
+
+            if let Some(naming) = table.get("naming").and_then(|v| v.as_table())
+            {
+                if let Some(case) = naming
+                    .get("case")
+                    .and_then(|v| v.as_str())
+                    .and_then(Case::from_name)
+                {
+                    config.naming.case = Some(case);
+                }
+
+                if let Some(uppercase) =
+                    naming.get("uppercase-terminals").and_then(|v| v.as_bool())
+                {
+                    config.naming.uppercase_terminals = uppercase;
+                }
+
+                if let Some(prefixes) =
+                    naming.get("forbidden-prefixes").and_then(|v| v.as_array())
+                {
+                    config.naming.forbidden_prefixes = prefixes
+                        .iter()
+                        .filter_map(|prefix| prefix.as_str())
+                        .map(Into::into)
+                        .collect();
+                }
+            }
+
+            if let Some(complexity) =
+                table.get("complexity").and_then(|v| v.as_table())
+            {
+                let limits = &mut config.complexity;
+                for (key, limit) in [
+                    ("alternatives", &mut limits.alternatives),
+                    ("depth", &mut limits.depth),
+                    ("tokens", &mut limits.tokens),
+                ] {
+                    if let Some(value) = complexity
+                        .get(key)
+                        .and_then(|v| v.as_integer())
+                        .and_then(|v| usize::try_from(v).ok())
+                    {
+                        *limit = value;
+                    }
+                }
+            }
+        }
+
+        config
+    }
+}
+
+impl Config {
+    /// The options of the parser for the grammar blocks of the book.
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            max_depth: self.max_depth,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Check the preprocessor table of the configuration of the book.
+    ///
+    /// Unknown keys are reported as warnings, with the closest known key as a
+    /// suggestion. Values of the wrong type or out of range, and conflicting
+    /// options, are reported as errors, since they would otherwise be ignored.
+    pub fn validate(book: &mdbook::Config) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let Some(table) = book.get_preprocessor(NAME) else {
+            return diagnostics;
+        };
+
+        let mut report = |level, message| {
+            diagnostics.push(Diagnostic {
+                level,
+                lint: None,
+                message,
+                path: "book.toml".into(),
+                line: None,
+            });
+        };
+
+        // The table of the book, then its overrides.
+        let mut scopes = vec![(String::new(), table)];
+        for kind in ["profile", "renderer"] {
+            let overrides = table.get(kind).and_then(|v| v.as_table());
+            for (name, value) in overrides.into_iter().flatten() {
+                match value.as_table() {
+                    | Some(table) => {
+                        scopes.push((format!("{kind}.{name}."), table));
+                    },
+                    | None => report(
+                        Level::Deny,
+                        eco_format!("option `{kind}.{name}` must be a table"),
+                    ),
+                }
+            }
+        }
+
+        for &(ref scope, table) in &scopes {
+            for (key, value) in table {
+                if scope.is_empty() && MDBOOK_KEYS.contains(&key.as_str()) {
+                    continue;
+                }
+                let name = format!("{scope}{key}");
+
+                let Some(&(_, ty)) = KEYS.iter().find(|(name, _)| name == key)
+                else {
+                    let mut message = eco_format!("unknown option `{name}`");
+                    if let Some(key) = suggest(key, KEYS.iter().map(|k| k.0)) {
+                        message =
+                            eco_format!("{message}, did you mean `{key}`?");
+                    }
+                    report(Level::Warn, message);
+                    continue;
+                };
+
+                if value.type_str() != ty {
+                    report(
+                        Level::Deny,
+                        eco_format!(
+                            "option `{name}` must be a {ty}, found a {}",
+                            value.type_str()
+                        ),
+                    );
+                    continue;
+                }
+
+                let variants: &[&str] = match key.as_str() {
+                    | "unresolved" => &["plain", "warning", "error"],
+                    | "notation" => &["native", "bnf"],
+                    | "links" => &["pages", "fragments"],
+                    | "scope" => &["book", "chapter"],
+                    | "anchors" => &["name", "content"],
+                    | "palette" => &["default", "color-blind", "high-contrast"],
+                    | "wildcard" => &["character", "token"],
+                    | "index-position" => &["start", "end"],
+                    | "profile" if scope.is_empty() => {
+                        for profile in value.as_table().into_iter().flatten() {
+                            if !["serve", "build"].contains(&profile.0.as_str())
+                            {
+                                report(
+                                    Level::Warn,
+                                    eco_format!(
+                                        "unknown profile `{}`, expected \
+                                         `serve` or `build`",
+                                        profile.0
+                                    ),
+                                );
+                            }
+                        }
+                        &[]
+                    },
+                    | _ => &[],
+                };
+                if value.as_integer().is_some_and(|v| v <= 0) {
+                    report(
+                        Level::Deny,
+                        eco_format!(
+                            "option `{name}` must be a positive integer"
+                        ),
+                    );
+                }
+                if let Some(value) = value.as_str() {
+                    if !variants.is_empty() && !variants.contains(&value) {
+                        let mut message = eco_format!(
+                            "option `{name}` must be one of {}, found \
+                             `{value}`",
+                            quoted(variants.iter().copied())
+                        );
+                        if let Some(value) =
+                            suggest(value, variants.iter().copied())
+                        {
+                            message = eco_format!(
+                                "{message}, did you mean `{value}`?"
+                            );
+                        }
+                        report(Level::Deny, message);
+                    }
+                }
+            }
+
+            if let Some(aliases) =
+                table.get("aliases").and_then(|v| v.as_table())
+            {
+                for (alias, options) in aliases {
+                    let Some(options) = options.as_array().filter(|options| {
+                        options.iter().all(|option| option.is_str())
+                    }) else {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "alias `{alias}` must be an array of strings"
+                            ),
+                        );
+                        continue;
+                    };
+
+                    let has =
+                        |name| options.iter().any(|o| o.as_str() == Some(name));
+                    if has("reflow") && has("verbatim") {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "alias `{alias}` sets both `reflow` and \
+                                 `verbatim`"
+                            ),
+                        );
+                    }
+                }
+            }
+
+            if let Some(lints) = table.get("lints").and_then(|v| v.as_table()) {
+                let names = Lint::ALL.iter().map(|lint| lint.name());
+                let levels = ["allow", "warn", "deny"];
+                for (lint, level) in lints {
+                    if Lint::from_name(lint).is_none() {
+                        let mut message = eco_format!("unknown lint `{lint}`");
+                        if let Some(name) = suggest(lint, names.clone()) {
+                            message = eco_format!(
+                                "{message}, did you mean `{name}`?"
+                            );
+                        }
+                        report(Level::Deny, message);
+                    }
+                    if level.as_str().and_then(Level::from_name).is_none() {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "level of lint `{lint}` must be one of {}",
+                                quoted(levels.into_iter())
+                            ),
+                        );
+                    }
+                }
+            }
+
+            if let Some(naming) = table.get("naming").and_then(|v| v.as_table())
+            {
+                for (key, value) in naming {
+                    let name = format!("{scope}naming.{key}");
+                    let Some(&(_, ty)) =
+                        NAMING_KEYS.iter().find(|(name, _)| name == key)
+                    else {
+                        let mut message =
+                            eco_format!("unknown option `{name}`");
+                        if let Some(key) =
+                            suggest(key, NAMING_KEYS.iter().map(|k| k.0))
+                        {
+                            message =
+                                eco_format!("{message}, did you mean `{key}`?");
+                        }
+                        report(Level::Warn, message);
+                        continue;
+                    };
+
+                    if value.type_str() != ty {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "option `{name}` must be a {ty}, found a {}",
+                                value.type_str()
+                            ),
+                        );
+                    } else if let Some(case) =
+                        value.as_str().filter(|&c| Case::from_name(c).is_none())
+                    {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "option `{name}` must be one of {}, found \
+                                 `{case}`",
+                                quoted(Case::ALL.iter().map(|c| c.name()))
+                            ),
+                        );
+                    } else if value.as_array().is_some_and(|prefixes| {
+                        !prefixes.iter().all(|p| p.is_str())
+                    }) {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "option `{name}` must be an array of strings"
+                            ),
+                        );
+                    }
+                }
+            }
+
+            if let Some(complexity) =
+                table.get("complexity").and_then(|v| v.as_table())
+            {
+                for (key, value) in complexity {
+                    let name = format!("{scope}complexity.{key}");
+                    if !COMPLEXITY_KEYS.contains(&key.as_str()) {
+                        let mut message =
+                            eco_format!("unknown option `{name}`");
+                        if let Some(key) =
+                            suggest(key, COMPLEXITY_KEYS.iter().copied())
+                        {
+                            message =
+                                eco_format!("{message}, did you mean `{key}`?");
+                        }
+                        report(Level::Warn, message);
+                    } else if value.as_integer().is_none_or(|v| v <= 0) {
+                        report(
+                            Level::Deny,
+                            eco_format!(
+                                "option `{name}` must be a positive integer"
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Find the candidate closest to a misspelled name, if any is close enough.
+fn suggest<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= 2.max(name.len() / 3))
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Quote and join names, like `` `a`, `b` or `c` ``.
+fn quoted<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let names = names.map(|name| format!("`{name}`")).collect::<Vec<_>>();
+    match names.split_last() {
+        | Some((last, rest)) if !rest.is_empty() => {
+            format!("{} or {last}", rest.join(", "))
+        },
+        | _ => names.concat(),
+    }
+}
+
+impl Config {
+    /// The link to an anchor of a chapter.
+    pub fn link(&self, chapter: &str, anchor: &str) -> String {
+        match self.links {
+            | Links::Pages => format!("{}{chapter}#{anchor}", self.site_url),
+            | Links::Fragments => format!("#{}", self.anchor(chapter, anchor)),
+        }
+    }
+
+    /// An anchor of a chapter, as written in the chapter.
+    ///
+    /// When the chapters are concatenated into a single document, anchors are
+    /// prefixed with their chapter, like `grammar-syntax-rule-a`, since
+    /// several chapters may define a rule of the same name.
+    pub fn anchor(&self, chapter: &str, anchor: &str) -> String {
+        let chapter = chapter.strip_suffix(".md").unwrap_or(chapter);
+        if self.links == Links::Pages || chapter.is_empty() {
+            return anchor.into();
+        }
+        let prefix = chapter
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect::<String>();
+        format!("{prefix}-{anchor}")
+    }
+
+    /// The link to a chapter, or `None` if the chapters are concatenated
+    /// into a single document.
+    pub fn chapter_link(&self, chapter: &str) -> Option<String> {
+        match self.links {
+            | Links::Pages => Some(format!("{}{chapter}", self.site_url)),
+            | Links::Fragments => None,
+        }
+    }
+
+    /// The level of a lint, as configured or by default.
+    pub fn level(&self, lint: Lint) -> Level {
+        let level = self.lints.get(&lint).copied();
+        level.unwrap_or_else(|| lint.default_level())
+    }
+
+    /// Whether rules may be overloaded, that is defined more than once on
+    /// purpose, as set by allowing the `duplicate-rule` lint.
+    pub fn overloads(&self) -> bool {
+        self.level(Lint::DuplicateRule) == Level::Allow
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            site_url: "/".into(),
+            links: Links::Pages,
+            scope: Scope::Book,
+            anchors: Anchors::Name,
+            rule_list: false,
+            railroad: false,
+            backlinks: false,
+            preview: false,
+            reflow: false,
+            unresolved: Unresolved::Plain,
+            strict: false,
+            max_depth: MAX_DEPTH,
+            notation: Notation::Native,
+            palette: None,
+            link_helpers: false,
+            layout: false,
+            wildcard: None,
+            metas: None,
+            changelog: None,
+            index: None,
+            index_title: "Rule Index".into(),
+            index_position: Position::End,
+            action_language: None,
+            aliases: BTreeMap::from([("syntax".into(), Vec::new())]),
+            lints: BTreeMap::new(),
+            naming: Naming::default(),
+            complexity: Complexity::default(),
+            cache: None,
+            search_index: None,
+            metadata: false,
+            timings: false,
+            repository: None,
+            branch: "main".into(),
+            source_root: "src".into(),
+            src: None,
+        }
+    }
+}
+
+/// How identifiers not referring to any rule are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unresolved {
+    /// As plain identifiers.
+    Plain,
+    /// Styled as warnings, with a tooltip.
+    Warning,
+    /// As errors, which fail the build in strict mode.
+    Error,
+}
+
+impl Unresolved {
+    /// Find a rendering by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "plain" => Some(Unresolved::Plain),
+            | "warning" => Some(Unresolved::Warning),
+            | "error" => Some(Unresolved::Error),
+            | _ => None,
+        }
+    }
+}
+
+/// How links to rules are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Links {
+    /// To the chapter and the anchor, like `/grammar.md#syntax-rule-a`.
+    Pages,
+    /// To the anchor only, like `#syntax-rule-a`, for renderers concatenating
+    /// the chapters into a single document.
+    Fragments,
+}
+
+impl Links {
+    /// The renderers known to concatenate the chapters.
+    const SINGLE_DOCUMENT: &[&str] = &["pdf", "latex", "typst", "pandoc"];
+
+    /// Find a way of linking by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "pages" => Some(Links::Pages),
+            | "fragments" => Some(Links::Fragments),
+            | _ => None,
+        }
+    }
+
+    /// The way of linking suited to the renderer.
+    pub fn for_renderer(renderer: &str) -> Self {
+        if Self::SINGLE_DOCUMENT.contains(&renderer) {
+            Links::Fragments
+        } else {
+            Links::Pages
+        }
+    }
+}
+
+/// The notation in which rules are displayed.
+///
+/// Only the display changes: grammar blocks are always written in the native
+/// notation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Notation {
+    /// As written, like `rule: item;`.
+    Native,
+    /// With the cosmetics of classic BNF, like `<rule> ::= <item>;`.
+    Bnf,
+}
+
+impl Notation {
+    /// Find a notation by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "native" => Some(Notation::Native),
+            | "bnf" => Some(Notation::Bnf),
+            | _ => None,
+        }
+    }
+}
+
+/// Where references to rules are resolved first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// In the whole book.
+    Book,
+    /// In the chapter of the reference, then in the whole book, for books
+    /// defining separate grammars in their chapters.
+    Chapter,
+}
+
+impl Scope {
+    /// Find a scope by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "book" => Some(Scope::Book),
+            | "chapter" => Some(Scope::Chapter),
+            | _ => None,
+        }
+    }
+}
+
+/// How the anchors of rule definitions are named.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Anchors {
+    /// After the name of the rule, like `syntax-rule-expr`.
+    Name,
+    /// After the name and a short hash of the content of the rule, like
+    /// `syntax-rule-expr-3fa2`, so that rules with the same name get distinct
+    /// anchors.
+    Content,
+}
+
+impl Anchors {
+    /// Find an anchor scheme by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "name" => Some(Anchors::Name),
+            | "content" => Some(Anchors::Content),
+            | _ => None,
+        }
+    }
+}
+
+/// What the wildcard `.` matches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Wildcard {
+    /// Any single character.
+    Character,
+    /// Any single token.
+    Token,
+}
+
+impl Wildcard {
+    /// Find a meaning of the wildcard by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "character" => Some(Wildcard::Character),
+            | "token" => Some(Wildcard::Token),
+            | _ => None,
+        }
+    }
+
+    /// Describe what the wildcard matches.
+    pub fn describe(self) -> &'static str {
+        match self {
+            | Wildcard::Character => "any character",
+            | Wildcard::Token => "any token",
+        }
+    }
+}
+
+/// Where a generated chapter is added to the book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Position {
+    /// Before the first chapter.
+    Start,
+    /// After the last chapter.
+    End,
+}
+
+impl Position {
+    /// Find a position by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "start" => Some(Position::Start),
+            | "end" => Some(Position::End),
+            | _ => None,
+        }
+    }
+}
+
+/// The naming conventions of rules.
+#[derive(Clone, Debug, Default, Hash)]
+pub struct Naming {
+    /// The case of rule names, if enforced.
+    pub case: Option<Case>,
+    /// Whether rules defined only with terminals, like strings and ranges,
+    /// must be `UPPER_CASE` instead.
+    pub uppercase_terminals: bool,
+    /// The prefixes rule names must not start with.
+    pub forbidden_prefixes: Vec<EcoString>,
+}
+
+/// The limits of the complexity of a rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Complexity {
+    /// The number of alternatives.
+    pub alternatives: usize,
+    /// The nesting depth of groups.
+    pub depth: usize,
+    /// The number of tokens in the definition.
+    pub tokens: usize,
+}
+
+impl Default for Complexity {
+    fn default() -> Self {
+        Self {
+            alternatives: 12,
+            depth: 4,
+            tokens: 60,
+        }
+    }
+}
+
+/// The case of a rule name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// Like `rule_name`.
+    Snake,
+    /// Like `RuleName`.
+    Camel,
+    /// Like `RULE_NAME`.
+    Upper,
+}
+
+impl Case {
+    /// All the cases.
+    pub const ALL: &[Case] = &[Case::Snake, Case::Camel, Case::Upper];
+
+    /// Find a case by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|case| case.name() == name)
+    }
+
+    /// The name of the case, written in that case.
+    pub fn name(self) -> &'static str {
+        match self {
+            | Case::Snake => "snake_case",
+            | Case::Camel => "CamelCase",
+            | Case::Upper => "UPPER_CASE",
+        }
+    }
+
+    /// Whether the name is written in this case.
+    pub fn matches(self, name: &str) -> bool {
+        let word = |c: char| c.is_ascii_digit() || c == '_';
+        match self {
+            | Case::Snake => name.chars().all(|c| c.is_lowercase() || word(c)),
+            | Case::Camel => {
+                name.is_empty()
+                    || name.starts_with(char::is_uppercase)
+                        && name.chars().all(char::is_alphanumeric)
+            },
+            | Case::Upper => name.chars().all(|c| c.is_uppercase() || word(c)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_from_book() {
+        let book = mdbook::Config::from_str(
+            r#"
+            [output.html]
+            site-url = "/book/"
+
+            [preprocessor.grammar]
+            rule-list = true
+            unresolved = "warning"
+            notation = "bnf"
+            wildcard = "token"
+            metas = "reference/metas.md"
+            index = "appendix/rules.md"
+            index-position = "start"
+            max-depth = 64
+
+            [preprocessor.grammar.aliases]
+            ebnf = ["option"]
+
+            [preprocessor.grammar.lints]
+            unused-rule = "deny"
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::from_book(&book, "html");
+        assert_eq!(config.site_url, "/book/");
+        assert!(config.rule_list);
+        assert_eq!(config.unresolved, Unresolved::Warning);
+        assert_eq!(config.notation, Notation::Bnf);
+        assert_eq!(config.wildcard, Some(Wildcard::Token));
+        assert_eq!(config.metas.as_deref(), Some("reference/metas.md"));
+        assert_eq!(config.index.as_deref(), Some("appendix/rules.md"));
+        assert_eq!(config.index_title, "Rule Index");
+        assert_eq!(config.index_position, Position::Start);
+        assert_eq!(config.parse_options().max_depth, 64);
+        assert_eq!(config.aliases["syntax"], [] as [&str; 0]);
+        assert_eq!(config.aliases["ebnf"], ["option"]);
+        assert_eq!(config.lints[&Lint::UnusedRule], Level::Deny);
+        assert_eq!(Config::validate(&book), []);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut book = mdbook::Config::from_str(
+            r#"
+            [preprocessor.grammar]
+            strict = true
+            reflow = true
+
+            [preprocessor.grammar.profile.serve]
+            strict = false
+
+            [preprocessor.grammar.renderer.epub]
+            reflow = false
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::from_book(&book, "html");
+        assert!(config.strict && config.reflow);
+        let config = Config::from_book(&book, "epub");
+        assert!(config.strict && !config.reflow);
+        assert_eq!(config.links, Links::Pages);
+        let config = Config::from_book(&book, "pdf");
+        assert_eq!(config.links, Links::Fragments);
+        assert_eq!(config.link("a.md", "b"), "#a-b");
+        assert_eq!(config.anchor("dir/a.md", "b"), "dir-a-b");
+        assert_eq!(config.anchor("", "b"), "b");
+
+        book.set("output.html.live-reload-endpoint", "__livereload")
+            .unwrap();
+        let config = Config::from_book(&book, "html");
+        assert!(!config.strict && config.reflow);
+        assert_eq!(Config::validate(&book), []);
+    }
+
+    #[test]
+    fn test_validate() {
+        let book = mdbook::Config::from_str(
+            r#"
+            [preprocessor.grammar]
+            command = "mdbook-grammar"
+            stict = true
+            reflow = "yes"
+            unresolved = "warnig"
+            max-depth = 0
+
+            [preprocessor.grammar.aliases]
+            ebnf = ["reflow", "verbatim"]
+
+            [preprocessor.grammar.lints]
+            unused-rules = "deny"
+
+            [preprocessor.grammar.naming]
+            case = "kebab-case"
+            forbiden-prefixes = ["tmp_"]
+
+            [preprocessor.grammar.complexity]
+            depth = 0
+            token = 40
+
+            [preprocessor.grammar.renderer.epub]
+            notation = "classic"
+            "#,
+        )
+        .unwrap();
+
+        let messages = Config::validate(&book)
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>();
+        assert_eq!(messages, [
+            "option `max-depth` must be a positive integer",
+            "option `reflow` must be a boolean, found a string",
+            "unknown option `stict`, did you mean `strict`?",
+            "option `unresolved` must be one of `plain`, `warning` or \
+             `error`, found `warnig`, did you mean `warning`?",
+            "alias `ebnf` sets both `reflow` and `verbatim`",
+            "unknown lint `unused-rules`, did you mean `unused-rule`?",
+            "option `naming.case` must be one of `snake_case`, `CamelCase` or \
+             `UPPER_CASE`, found `kebab-case`",
+            "unknown option `naming.forbiden-prefixes`, did you mean \
+             `forbidden-prefixes`?",
+            "option `complexity.depth` must be a positive integer",
+            "unknown option `complexity.token`, did you mean `tokens`?",
+            "option `renderer.epub.notation` must be one of `native` or \
+             `bnf`, found `classic`",
+        ]);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    book::{Item, Page},
+    code::code_text,
+};
+use mdbook_grammar_syntax::{SourceMap, SyntaxKind, parse};
+
+/// The share of rules which must parse cleanly for a block to be taken for
+/// a grammar.
+const THRESHOLD: f64 = 0.9;
+
+/// Find the fenced code blocks without a tag which look like grammars, as the
+/// lines of their opening fences in the chapter.
+pub fn untagged_grammars(page: &Page) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut line = 1;
+
+    for item in &page.items {
+        match item {
+            | Item::Text(text) => {
+                let map = SourceMap::new(text, &[]);
+                for (offset, code) in untagged_blocks(text) {
+                    if looks_like_grammar(&code) {
+                        found.push(line + map.line(offset) - 1);
+                    }
+                }
+                line += map.line(text.len()) - 1;
+            },
+            | Item::Raw(text) => {
+                line += SourceMap::new(text, &[]).line(text.len()) - 1;
+            },
+            | Item::Code(block) => {
+                // The text following the block starts on its closing fence.
+                let code = code_text(&block.code);
+                let map = SourceMap::new(&code, &[]);
+                line = block.line + map.line(code.len()) - 1;
+            },
+        }
+    }
+
+    found
+}
+
+/// Find the fenced code blocks without a tag in markdown text, as the offsets
+/// of their opening fences with their content.
+fn untagged_blocks(text: &str) -> Vec<(usize, String)> {
+    let mut blocks = Vec::new();
+    let mut end = 0;
+    let mut lines = text.split_inclusive('\n').map(|line| {
+        end += line.len();
+        (end - line.len(), line)
+    });
+
+    while let Some((offset, line)) = lines.next() {
+        let line = line.trim_start();
+        let fence = line.len() - line.trim_start_matches('`').len();
+        if fence < 3 {
+            continue;
+        }
+
+        let info = line[fence..].trim();
+        let mut code = String::new();
+        for (_, line) in lines.by_ref() {
+            let line = line.trim();
+            if line.len() >= fence && line.chars().all(|c| c == '`') {
+                break;
+            }
+            code += line;
+            code.push('\n');
+        }
+
+        if info.is_empty() {
+            blocks.push((offset, code));
+        }
+    }
+
+    blocks
+}
+
+/// Whether code looks like grammar rules: it defines some rules, and nearly
+/// all of them parse cleanly.
+fn looks_like_grammar(code: &str) -> bool {
+    let root = parse(code);
+    let rules = root
+        .children()
+        .filter(|n| !n.kind().is_trivia())
+        .collect::<Vec<_>>();
+    let clean = rules
+        .iter()
+        .filter(|n| n.kind() == SyntaxKind::Rule && !n.erroneous())
+        .count();
+
+    clean > 0 && clean as f64 >= THRESHOLD * rules.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::Block;
+
+    #[test]
+    fn test_untagged_grammars() {
+        let page = Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![
+                Item::Text(
+                    "Text\n\n```\nexpr: term;\nterm: \"1\";\n```\n\n".into(),
+                ),
+                Item::Code(Block {
+                    code: parse("a: ;\n"),
+                    options: Vec::new(),
+                    line: 9,
+                }),
+                Item::Text(
+                    "\n\n```\nfn main() {}\n```\n\n```rust\na: \
+                     b;\n```\n\n```\na: b;\n```\n"
+                        .into(),
+                ),
+            ],
+        };
+
+        assert_eq!(untagged_grammars(&page), [3, 20]);
+    }
+}
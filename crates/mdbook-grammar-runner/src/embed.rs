@@ -0,0 +1,59 @@
+use crate::{
+    Config,
+    book::{Block, Item, Page},
+    code::{Context, find_rules, parse_code},
+};
+use mdbook_grammar_syntax::parse_with_options;
+
+/// Render a grammar to a fragment of HTML, for documentation outside of mdbook
+/// like rustdoc comments.
+///
+/// The fragment does not depend on the rest of a book: identifiers link to
+/// the rules defined in the grammar itself. It is styled by
+/// [`stylesheet`](crate::stylesheet).
+pub fn render_grammar(code: &str, config: &Config) -> String {
+    let config = Config {
+        site_url: "".into(),
+        rule_list: false,
+        repository: None,
+        ..config.clone()
+    };
+
+    let block = Block {
+        code: parse_with_options(code, &config.parse_options()),
+        options: Vec::new(),
+        line: 1,
+    };
+    let page = Page {
+        href: "".into(),
+        name: "".into(),
+        items: vec![Item::Code(block)],
+    };
+    let rules = find_rules(std::slice::from_ref(&page), &config);
+    let ctx = Context {
+        config: &config,
+        rules: &rules,
+        page: Some(&page),
+        block: None,
+        params: &[],
+    };
+
+    let Item::Code(block) = &page.items[0] else {
+        unreachable!()
+    };
+    parse_code(&ctx, block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_grammar() {
+        let html = render_grammar("a: b; b: ;", &Config::default());
+        assert!(html.starts_with("<pre><code class=\"syntax\">"));
+        assert!(
+            html.contains("<a class=\"syntax-link\" href=\"#syntax-rule-b\">")
+        );
+    }
+}
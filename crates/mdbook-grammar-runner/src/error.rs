@@ -0,0 +1,91 @@
+use crate::Diagnostic;
+use ecow::EcoString;
+use std::{
+    fmt::{Display, Formatter},
+    io,
+};
+
+/// An error of the preprocessor, mapped to the exit code of the command.
+#[derive(Debug)]
+pub enum GrammarError {
+    /// The command line is invalid.
+    Usage(EcoString),
+    /// Reading the input or writing the output failed.
+    Io(io::Error),
+    /// mdbook sent an invalid request, or the book could not be loaded.
+    MdbookProtocol(EcoString),
+    /// The configuration of the preprocessor has errors.
+    Config {
+        /// The problems found, including the errors.
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// The grammar blocks have errors, or denied lints were triggered.
+    Strict {
+        /// The problems found, including the errors.
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+impl GrammarError {
+    /// The exit code of the command failing with the error.
+    ///
+    /// A crash of the preprocessor exits with code 101, like any panic.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            | Self::Strict { .. } => 1,
+            | Self::Usage(_) => 2,
+            | Self::Config { .. } => 3,
+            | Self::MdbookProtocol(_) => 4,
+            | Self::Io(_) => 5,
+        }
+    }
+
+    /// The problems found, empty if the error is not about the book.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            | Self::Config { diagnostics } | Self::Strict { diagnostics } => {
+                diagnostics
+            },
+            | _ => &[],
+        }
+    }
+}
+
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let errors = |diagnostics: &[Diagnostic]| {
+            diagnostics.iter().filter(|d| d.is_error()).count()
+        };
+        match self {
+            | Self::Usage(message) | Self::MdbookProtocol(message) => {
+                write!(f, "{message}")
+            },
+            | Self::Io(err) => write!(f, "{err}"),
+            | Self::Config { diagnostics } => write!(
+                f,
+                "{} error(s) found in the configuration",
+                errors(diagnostics)
+            ),
+            | Self::Strict { diagnostics } => write!(
+                f,
+                "{} error(s) found in grammar blocks",
+                errors(diagnostics)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            | Self::Io(err) => Some(err),
+            | _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GrammarError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
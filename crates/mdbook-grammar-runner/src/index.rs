@@ -0,0 +1,224 @@
+use crate::{
+    Config,
+    book::Page,
+    code::{Context, page_rule_nodes, wrap_node_raw},
+    config::Position,
+    iter::RecursiveIterableMut,
+    lint::rule_references,
+};
+use ecow::EcoString;
+use html_escape::encode_safe;
+use mdbook::{
+    BookItem,
+    book::{Book, Chapter},
+};
+use std::{collections::HashMap, path::Path};
+
+/// Render the chapter listing the public rules of the book alphabetically,
+/// with links to their definitions and to the chapters referencing them.
+pub fn rule_index(ctx: &Context, pages: &[Page]) -> String {
+    let config = ctx.config;
+    let rules = ctx.rules;
+    let names = pages
+        .iter()
+        .map(|page| (&page.href, &page.name))
+        .collect::<HashMap<_, _>>();
+    let references = chapter_references(pages);
+    let chapter = |href: &EcoString, anchor: Option<&str>| {
+        let name = encode_safe(names[href].as_str()).into_owned();
+        let link = match anchor {
+            | Some(anchor) => Some(config.link(href, anchor)),
+            | None => config.chapter_link(href),
+        };
+        match link {
+            | Some(link) => {
+                format!("<a class=\"syntax-link\" href=\"{link}\">{name}</a>")
+            },
+            | None => name,
+        }
+    };
+
+    let mut symbols = rules.chapters.keys().copied().collect::<Vec<_>>();
+    symbols.sort_by_cached_key(|&symbol| {
+        let name = rules.symbols.name(symbol);
+        (name.to_lowercase(), name.clone())
+    });
+
+    let mut out = format!("# {}\n\n", config.index_title);
+    if symbols.is_empty() {
+        return out;
+    }
+    out += "<ul class=\"syntax-index\">\n";
+    for symbol in symbols {
+        let name = rules.symbols.name(symbol);
+        let definitions = &rules.chapters[&symbol];
+        let (href, anchor) = &definitions[0];
+        let referencing = references.get(name).map_or_else(
+            || "not referenced".into(),
+            |pages| {
+                let pages = pages
+                    .iter()
+                    .map(|page| chapter(&page.href, None))
+                    .collect::<Vec<_>>();
+                format!("referenced in {}", pages.join(", "))
+            },
+        );
+        out += &format!(
+            "<li><a class=\"syntax-link\" href=\"{link}\">{name}</a>: defined \
+             in {defined}; {referencing}</li>\n",
+            link = config.link(href, anchor),
+            name = wrap_node_raw(name, "identifier"),
+            defined = definitions
+                .iter()
+                .map(|(href, anchor)| chapter(href, Some(anchor)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+    out += "</ul>\n";
+
+    out
+}
+
+/// The chapters referencing each rule by name, in the order of the book.
+pub fn chapter_references(pages: &[Page]) -> HashMap<&EcoString, Vec<&Page>> {
+    let mut references: HashMap<_, Vec<&Page>> = HashMap::new();
+    for page in pages {
+        for name in page_rule_nodes(page).flat_map(rule_references) {
+            let chapters = references.entry(name).or_default();
+            if chapters.last().is_none_or(|last| last.href != page.href) {
+                chapters.push(page);
+            }
+        }
+    }
+    references
+}
+
+/// Add the index chapter to the book at its configured position, or replace
+/// the content of the chapter at its path if the summary already lists it.
+pub fn add_index(
+    book: &mut Book,
+    config: &Config,
+    path: &str,
+    content: String,
+) {
+    let mut content = Some(content);
+    book.recur_for_each_mut(&mut |chapter| {
+        if chapter.path.as_deref() == Some(Path::new(path)) {
+            if let Some(content) = content.take() {
+                chapter.content = content;
+            }
+        }
+    });
+    let Some(content) = content else {
+        return;
+    };
+
+    let chapter = Chapter::new(&config.index_title, content, path, Vec::new());
+    let index = match config.index_position {
+        | Position::Start => 0,
+        | Position::End => book.sections.len(),
+    };
+    book.sections.insert(index, BookItem::Chapter(chapter));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run;
+
+    fn sample_book() -> Book {
+        let mut book = Book::new();
+        for (name, content, path) in [
+            (
+                "Expressions",
+                "```syntax\nexpr: Term | call;\n```\n",
+                "expr.md",
+            ),
+            (
+                "Calls",
+                "```syntax\ncall: expr \"(\" _args \")\";\n```\n",
+                "call.md",
+            ),
+            (
+                "Terms",
+                "```syntax\n_args: expr;\nTerm: ;\nunused: ;\n```\n",
+                "term.md",
+            ),
+        ] {
+            book.push_item(BookItem::Chapter(Chapter::new(
+                name,
+                content.into(),
+                path,
+                Vec::new(),
+            )));
+        }
+        book
+    }
+
+    fn chapter(item: &BookItem) -> &Chapter {
+        match item {
+            | BookItem::Chapter(chapter) => chapter,
+            | _ => panic!("expected a chapter"),
+        }
+    }
+
+    #[test]
+    fn test_rule_index() {
+        let mut book = sample_book();
+        let config = Config {
+            index: Some("index.md".into()),
+            ..Config::default()
+        };
+        run(&mut book, &config);
+
+        assert_eq!(book.sections.len(), 4);
+        let index = chapter(&book.sections[3]);
+        assert_eq!(index.name, "Rule Index");
+        assert_eq!(index.path.as_deref(), Some(Path::new("index.md")));
+
+        let lines = index.content.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "# Rule Index");
+        assert_eq!(lines.len(), 8);
+        assert_eq!(
+            lines[3],
+            "<li><a class=\"syntax-link\" \
+             href=\"/call.md#syntax-rule-call\"><span \
+             class=\"syntax-identifier\">call</span></a>: defined in <a \
+             class=\"syntax-link\" \
+             href=\"/call.md#syntax-rule-call\">Calls</a>; referenced in <a \
+             class=\"syntax-link\" href=\"/expr.md\">Expressions</a></li>"
+        );
+        assert!(lines[4].contains(">expr</span>"));
+        assert!(lines[4].ends_with(
+            "referenced in <a class=\"syntax-link\" \
+             href=\"/call.md\">Calls</a>, <a class=\"syntax-link\" \
+             href=\"/term.md\">Terms</a></li>"
+        ));
+        assert!(lines[5].contains(">Term</span>"));
+        assert!(lines[5].ends_with(">Expressions</a></li>"));
+        assert!(lines[6].ends_with("not referenced</li>"));
+    }
+
+    #[test]
+    fn test_rule_index_position() {
+        let mut book = sample_book();
+        let mut config = Config {
+            index: Some("rules.md".into()),
+            index_title: "Rules".into(),
+            index_position: Position::Start,
+            ..Config::default()
+        };
+        run(&mut book, &config);
+        let index = chapter(&book.sections[0]);
+        assert_eq!(index.name, "Rules");
+        assert!(index.content.starts_with("# Rules\n"));
+
+        // A chapter of the summary at the path of the index is filled.
+        let mut book = sample_book();
+        config.index = Some("term.md".into());
+        run(&mut book, &config);
+        assert_eq!(book.sections.len(), 3);
+        assert!(chapter(&book.sections[2]).content.starts_with("# Rules\n"));
+    }
+}
@@ -3,18 +3,34 @@ use mdbook::{
     book::{Book, Chapter},
 };
 use mdbook_grammar_syntax::SyntaxNode;
-use std::mem::transmute;
 
 pub trait RecursiveIterable {
     type Item;
 
+    /// Iterate over the items in document order: each tree before its
+    /// children, and the children in order.
     fn recur_iter(&self) -> impl Iterator<Item = &Self::Item>;
+}
 
-    fn recur_iter_mut(&mut self) -> impl Iterator<Item = &mut Self::Item>;
+/// Mutable counterpart of [`RecursiveIterable`].
+///
+/// A mutable iterator would hand out a parent while its children are still to
+/// be borrowed, so items are visited with a callback instead.
+pub trait RecursiveIterableMut: RecursiveIterable {
+    /// Call `f` on each item, in the same order as
+    /// [`RecursiveIterable::recur_iter`].
+    fn recur_for_each_mut(&mut self, f: &mut impl FnMut(&mut Self::Item));
 }
 
 pub trait Tree: Sized {
     fn children(&self) -> Vec<&Self>;
+}
+
+/// A tree whose children can be borrowed mutably.
+///
+/// Syntax nodes are not: their cached state must be updated after changing
+/// their children, with [`SyntaxNode::map_children`].
+pub trait TreeMut: Tree {
     fn children_mut(&mut self) -> Vec<&mut Self>;
 }
 
@@ -23,7 +39,9 @@ struct TreeIter<'s, T: Tree> {
 }
 
 impl<'s, T: Tree> TreeIter<'s, T> {
-    fn new(stack: Vec<&'s T>) -> Self {
+    /// Iterate over the trees and their descendants, in document order.
+    fn new(mut stack: Vec<&'s T>) -> Self {
+        stack.reverse();
         Self { stack }
     }
 
@@ -37,33 +55,16 @@ impl<'s, T: Tree> Iterator for TreeIter<'s, T> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let tree = self.stack.pop()?;
-        self.stack.append(&mut tree.children());
+        self.stack.extend(tree.children().into_iter().rev());
         Some(tree)
     }
 }
 
-struct TreeIterMut<'s, T: Tree> {
-    stack: Vec<&'s mut T>,
-}
-
-impl<'s, T: Tree> TreeIterMut<'s, T> {
-    fn new(stack: Vec<&'s mut T>) -> Self {
-        Self { stack }
-    }
-
-    fn empty() -> Self {
-        Self { stack: Vec::new() }
-    }
-}
-
-impl<'s, T: Tree> Iterator for TreeIterMut<'s, T> {
-    type Item = &'s mut T;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let tree = self.stack.pop()?;
-        let copy = unsafe { transmute::<&mut T, &mut T>(tree) };
-        self.stack.append(&mut copy.children_mut());
-        Some(tree)
+/// Call `f` on a tree, then on its descendants, in document order.
+fn for_each_mut<T: TreeMut>(tree: &mut T, f: &mut impl FnMut(&mut T)) {
+    f(tree);
+    for child in tree.children_mut() {
+        for_each_mut(child, f);
     }
 }
 
@@ -73,9 +74,11 @@ impl<T: Tree> RecursiveIterable for T {
     fn recur_iter(&self) -> impl Iterator<Item = &Self::Item> {
         TreeIter::new(vec![self])
     }
+}
 
-    fn recur_iter_mut(&mut self) -> impl Iterator<Item = &mut Self::Item> {
-        TreeIterMut::new(vec![self])
+impl<T: TreeMut> RecursiveIterableMut for T {
+    fn recur_for_each_mut(&mut self, f: &mut impl FnMut(&mut Self::Item)) {
+        for_each_mut(self, f);
     }
 }
 
@@ -85,9 +88,13 @@ impl<T: Tree> RecursiveIterable for Vec<T> {
     fn recur_iter(&self) -> impl Iterator<Item = &Self::Item> {
         TreeIter::new(self.iter().collect())
     }
+}
 
-    fn recur_iter_mut(&mut self) -> impl Iterator<Item = &mut Self::Item> {
-        TreeIterMut::new(self.iter_mut().collect())
+impl<T: TreeMut> RecursiveIterableMut for Vec<T> {
+    fn recur_for_each_mut(&mut self, f: &mut impl FnMut(&mut Self::Item)) {
+        for tree in self {
+            for_each_mut(tree, f);
+        }
     }
 }
 
@@ -95,7 +102,9 @@ impl Tree for Chapter {
     fn children(&self) -> Vec<&Self> {
         self.sub_items.iter().filter_map(item_to_chapter).collect()
     }
+}
 
+impl TreeMut for Chapter {
     fn children_mut(&mut self) -> Vec<&mut Self> {
         self.sub_items
             .iter_mut()
@@ -112,11 +121,13 @@ impl RecursiveIterable for BookItem {
             TreeIter::new(vec![chapter])
         })
     }
+}
 
-    fn recur_iter_mut(&mut self) -> impl Iterator<Item = &mut Self::Item> {
-        item_to_chapter_mut(self).map_or_else(TreeIterMut::empty, |chapter| {
-            TreeIterMut::new(vec![chapter])
-        })
+impl RecursiveIterableMut for BookItem {
+    fn recur_for_each_mut(&mut self, f: &mut impl FnMut(&mut Self::Item)) {
+        if let Some(chapter) = item_to_chapter_mut(self) {
+            for_each_mut(chapter, f);
+        }
     }
 }
 
@@ -128,14 +139,14 @@ impl RecursiveIterable for Book {
             self.sections.iter().filter_map(item_to_chapter).collect(),
         )
     }
+}
 
-    fn recur_iter_mut(&mut self) -> impl Iterator<Item = &mut Self::Item> {
-        TreeIterMut::new(
-            self.sections
-                .iter_mut()
-                .filter_map(item_to_chapter_mut)
-                .collect(),
-        )
+impl RecursiveIterableMut for Book {
+    fn recur_for_each_mut(&mut self, f: &mut impl FnMut(&mut Self::Item)) {
+        for chapter in self.sections.iter_mut().filter_map(item_to_chapter_mut)
+        {
+            for_each_mut(chapter, f);
+        }
     }
 }
 
@@ -143,10 +154,6 @@ impl Tree for SyntaxNode {
     fn children(&self) -> Vec<&Self> {
         self.children().collect()
     }
-
-    fn children_mut(&mut self) -> Vec<&mut Self> {
-        unimplemented!()
-    }
 }
 
 #[inline]
@@ -168,3 +175,57 @@ fn item_to_chapter_mut(item: &mut BookItem) -> Option<&mut Chapter> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook_grammar_syntax::{SyntaxKind, parse};
+
+    fn chapter(name: &str, sub_items: Vec<BookItem>) -> BookItem {
+        let mut chapter = Chapter::new(name, String::new(), name, Vec::new());
+        chapter.sub_items = sub_items;
+        BookItem::Chapter(chapter)
+    }
+
+    #[test]
+    fn test_recur_for_each_mut() {
+        let mut book = Book::new();
+        book.push_item(chapter("a", vec![
+            chapter("b", vec![chapter("c", Vec::new())]),
+            chapter("d", Vec::new()),
+        ]));
+        book.push_item(BookItem::Chapter(Chapter::new_draft(
+            "draft",
+            Vec::new(),
+        )));
+        book.push_item(chapter("e", Vec::new()));
+
+        let mut names = Vec::new();
+        book.recur_for_each_mut(&mut |chapter| {
+            chapter.content = chapter.name.repeat(2);
+            names.push(chapter.name.clone());
+        });
+        assert_eq!(names, ["a", "b", "c", "d", "e"]);
+
+        let contents = book.recur_iter().map(|c| c.content.as_str());
+        assert!(contents.eq(["aa", "bb", "cc", "dd", "ee"]));
+    }
+
+    #[test]
+    fn test_recur_iter_order() {
+        let code = "a: (b | c) d;\ne: f;";
+        let root = parse(code);
+        let text = root
+            .recur_iter()
+            .map(|node| node.text().as_str())
+            .collect::<String>();
+        assert_eq!(text, code);
+
+        let roots = vec![parse("a: b;"), parse("c: d;")];
+        let names = roots
+            .recur_iter()
+            .filter(|node| node.kind() == SyntaxKind::Identifier)
+            .map(|node| node.text().as_str());
+        assert!(names.eq(["a", "b", "c", "d"]));
+    }
+}
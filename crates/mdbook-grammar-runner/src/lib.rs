@@ -1,6 +1,36 @@
 mod book;
+mod cache;
 mod code;
+mod config;
+mod detect;
+mod embed;
+mod error;
+mod index;
 mod iter;
+mod lint;
+mod metas;
 mod mode;
+mod preprocessor;
+mod preview;
+mod railroad;
+mod registry;
+mod search;
+mod server;
+mod snapshot;
+mod style;
+mod symbol;
+mod timings;
+mod tokens;
 
-pub use book::run;
+pub use self::{
+    book::run,
+    config::Config,
+    embed::render_grammar,
+    error::GrammarError,
+    lint::{Diagnostic, Level, Lint},
+    preprocessor::GrammarPreprocessor,
+    registry::{RuleEntry, registry},
+    server::Server,
+    snapshot::{Snapshot, SnapshotRule},
+    style::{Palette, stylesheet},
+};
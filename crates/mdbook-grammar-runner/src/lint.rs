@@ -0,0 +1,1393 @@
+use crate::{
+    Config,
+    book::{Block, Item, Page},
+    code::{
+        Imports, page_imports, page_pragma_rules, rule_lines,
+        unresolved_message,
+    },
+    config::{Case, Complexity, Scope, Unresolved},
+    detect::untagged_grammars,
+    iter::RecursiveIterable,
+    metas::find_metas,
+    mode::declared_modes,
+    tokens::describe_layout,
+};
+use ecow::{EcoString, eco_format};
+use mdbook_grammar_syntax::{
+    SyntaxKind, SyntaxNode, Visitor,
+    ast::{AstNode, Rule},
+    walk,
+};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Display, Formatter},
+};
+
+/// The annotations of rules understood by the preprocessor.
+const ANNOTATIONS: &[&str] =
+    &["deprecated", "hidden", "inline", "since", "token"];
+
+/// The prefix of comments changing lint levels.
+const DIRECTIVE: &str = "grammar-lint:";
+
+/// A check on the grammar of the book.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Lint {
+    /// A rule is never referenced by other rules.
+    UnusedRule,
+    /// An identifier does not refer to any rule of the book.
+    UndefinedRule,
+    /// A rule is defined more than once.
+    DuplicateRule,
+    /// A condition uses a name which is not a declared mode.
+    UnknownMode,
+    /// A rule name breaks the naming conventions of the book.
+    RuleNaming,
+    /// An action seems to contain the next rule, after a missing `;`.
+    SwallowedRule,
+    /// A code block without a tag looks like a grammar.
+    UntaggedGrammar,
+    /// A meta is not defined in the chapter of metas.
+    UndefinedMeta,
+    /// A rule exceeds the complexity limits of the book.
+    ComplexRule,
+    /// A rule of a chapter shadows a rule of another chapter, when rules are
+    /// scoped to chapters.
+    ShadowedRule,
+    /// A rule has an annotation the preprocessor does not know.
+    UnknownAnnotation,
+}
+
+impl Lint {
+    /// All the lints.
+    pub const ALL: &[Lint] = &[
+        Lint::UnusedRule,
+        Lint::UndefinedRule,
+        Lint::DuplicateRule,
+        Lint::UnknownMode,
+        Lint::RuleNaming,
+        Lint::SwallowedRule,
+        Lint::UntaggedGrammar,
+        Lint::UndefinedMeta,
+        Lint::ComplexRule,
+        Lint::ShadowedRule,
+        Lint::UnknownAnnotation,
+    ];
+
+    /// Find a lint by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+
+    /// The name of the lint, as used in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            | Lint::UnusedRule => "unused-rule",
+            | Lint::UndefinedRule => "undefined-rule",
+            | Lint::DuplicateRule => "duplicate-rule",
+            | Lint::UnknownMode => "unknown-mode",
+            | Lint::RuleNaming => "rule-naming",
+            | Lint::SwallowedRule => "swallowed-rule",
+            | Lint::UntaggedGrammar => "untagged-grammar",
+            | Lint::UndefinedMeta => "undefined-meta",
+            | Lint::ComplexRule => "complex-rule",
+            | Lint::ShadowedRule => "shadowed-rule",
+            | Lint::UnknownAnnotation => "unknown-annotation",
+        }
+    }
+
+    /// The level of the lint if it is not configured.
+    pub fn default_level(self) -> Level {
+        match self {
+            | Lint::UnusedRule
+            | Lint::UndefinedRule
+            | Lint::UnknownMode
+            | Lint::UntaggedGrammar
+            | Lint::ComplexRule => Level::Allow,
+            | Lint::DuplicateRule
+            | Lint::RuleNaming
+            | Lint::SwallowedRule
+            | Lint::UndefinedMeta
+            | Lint::ShadowedRule
+            | Lint::UnknownAnnotation => Level::Warn,
+        }
+    }
+}
+
+/// How a lint is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Not reported.
+    Allow,
+    /// Reported as a warning.
+    Warn,
+    /// Reported as an error.
+    Deny,
+}
+
+impl Level {
+    /// Find a level by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "allow" => Some(Level::Allow),
+            | "warn" => Some(Level::Warn),
+            | "deny" => Some(Level::Deny),
+            | _ => None,
+        }
+    }
+}
+
+/// A problem found in the book.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    /// How severe the problem is, either [`Level::Warn`] or [`Level::Deny`],
+    /// or [`Level::Allow`] for a note that is not a problem, like timings.
+    pub level: Level,
+    /// The lint reporting the problem, if any.
+    pub lint: Option<Lint>,
+    /// The description of the problem.
+    pub message: EcoString,
+    /// The path of the chapter containing the problem, empty if none does.
+    pub path: EcoString,
+    /// The line of the problem in the chapter, if known.
+    pub line: Option<usize>,
+}
+
+impl Diagnostic {
+    /// Whether the problem is an error.
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Deny
+    }
+
+    /// The name of the level, as printed.
+    fn level_name(&self) -> &'static str {
+        match self.level {
+            | Level::Allow => "note",
+            | Level::Warn => "warning",
+            | Level::Deny => "error",
+        }
+    }
+
+    /// The problem as a JSON object.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "level": self.level_name(),
+            "lint": self.lint.map(Lint::name),
+            "message": self.message.as_str(),
+            "path": self.path.as_str(),
+            "line": self.line,
+        })
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.level_name())?;
+        if let Some(lint) = self.lint {
+            write!(f, "[{}]", lint.name())?;
+        }
+        write!(f, ": {}", self.message)?;
+        if self.path.is_empty() {
+            return Ok(());
+        }
+        write!(f, " (in {}", self.path)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Lint levels overriding the configuration of the book.
+type Levels = HashMap<Lint, Level>;
+
+/// A rule definition found in the book.
+struct RuleInfo<'a> {
+    name: &'a EcoString,
+    path: &'a EcoString,
+    line: usize,
+    terminal: bool,
+    /// Whether the rule adds alternatives to another, like `a += b;`.
+    extension: bool,
+    /// Whether the rule only composes tokens, like `fragment a: b;`.
+    fragment: bool,
+    levels: Levels,
+    references: Vec<&'a EcoString>,
+    /// The names used in conditions.
+    conditions: Vec<&'a str>,
+    actions: Vec<&'a str>,
+    metas: Vec<&'a str>,
+    annotations: Vec<&'a str>,
+    complexity: Complexity,
+}
+
+/// Run the lints on the grammar blocks of the pages, given the grammars they
+/// import.
+pub fn check(
+    pages: &[Page],
+    imports: &Imports,
+    config: &Config,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut rules = Vec::new();
+
+    for page in pages {
+        for item in &page.items {
+            if let Item::Code(block) = item {
+                collect_rules(block, &page.href, &mut rules, &mut diagnostics);
+            }
+        }
+    }
+
+    let modes = pages
+        .iter()
+        .flat_map(|page| &page.items)
+        .filter_map(|item| match item {
+            | Item::Text(text) => Some(declared_modes(text)),
+            | _ => None,
+        })
+        .flatten()
+        .collect::<HashSet<_>>();
+
+    let metas = find_metas(pages, config);
+
+    let mut definitions: HashMap<&EcoString, Vec<usize>> = HashMap::new();
+    for (i, rule) in rules.iter().enumerate() {
+        definitions.entry(rule.name).or_default().push(i);
+    }
+
+    // Rules imported from files may be referenced, but are not checked.
+    let mut imported = HashSet::new();
+    for (path, grammar) in imports {
+        let message = match grammar {
+            | Ok(root) if !root.erroneous() => {
+                imported.extend(
+                    root.children()
+                        .filter(|n| n.kind() == SyntaxKind::Rule)
+                        .map(|rule| rule_name(rule).clone()),
+                );
+                continue;
+            },
+            | Ok(_) => eco_format!("imported grammar `{path}` has errors"),
+            | Err(message) => message.clone(),
+        };
+        let page = pages
+            .iter()
+            .find(|page| page_imports(page).any(|p| p == *path));
+        diagnostics.push(Diagnostic {
+            level: Level::Warn,
+            lint: None,
+            message,
+            path: page.map(|page| page.href.clone()).unwrap_or_default(),
+            line: None,
+        });
+    }
+
+    let mut skipped = HashSet::new();
+    for page in pages {
+        skipped.extend(page_pragma_rules(page, "skip"));
+        for (pragma, kind) in [("start", "start"), ("skip", "skipped")] {
+            for name in page_pragma_rules(page, pragma) {
+                if !definitions.contains_key(name) && !imported.contains(name) {
+                    diagnostics.push(Diagnostic {
+                        level: Level::Warn,
+                        lint: None,
+                        message: eco_format!(
+                            "{kind} rule `{name}` is not defined"
+                        ),
+                        path: page.href.clone(),
+                        line: None,
+                    });
+                }
+            }
+        }
+    }
+    let reachable = reachable_rules(pages, &rules, &definitions);
+
+    let mut report = |rule: &RuleInfo, lint: Lint, message: EcoString| {
+        let level = rule
+            .levels
+            .get(&lint)
+            .or_else(|| config.lints.get(&lint))
+            .copied()
+            .unwrap_or_else(|| lint.default_level());
+        if level > Level::Allow {
+            diagnostics.push(Diagnostic {
+                level,
+                lint: Some(lint),
+                message,
+                path: rule.path.clone(),
+                line: Some(rule.line),
+            });
+        }
+    };
+
+    // Rules scoped to chapters may be defined again in other chapters.
+    let scoped = config.scope == Scope::Chapter;
+
+    for (i, rule) in rules.iter().enumerate() {
+        // Extensions add alternatives to a rule defined elsewhere.
+        let others = &definitions[rule.name];
+        let first = others
+            .iter()
+            .find(|&&j| {
+                !rules[j].extension && (!scoped || rules[j].path == rule.path)
+            })
+            .copied();
+        if rule.extension {
+            if others.iter().all(|&j| rules[j].extension) {
+                report(
+                    rule,
+                    Lint::UndefinedRule,
+                    eco_format!(
+                        "rule `{}` is extended but never defined",
+                        rule.name
+                    ),
+                );
+            }
+        } else if first != Some(i) {
+            report(
+                rule,
+                Lint::DuplicateRule,
+                eco_format!("rule `{}` is defined more than once", rule.name),
+            );
+        } else if let Some(&j) = others.iter().find(|&&j| {
+            scoped && j < i && !rules[j].extension && rules[j].path != rule.path
+        }) {
+            report(
+                rule,
+                Lint::ShadowedRule,
+                eco_format!(
+                    "rule `{}` shadows its definition in `{}`",
+                    rule.name,
+                    rules[j].path
+                ),
+            );
+        }
+
+        // Skipped rules are used between all tokens, and fragments by the
+        // tokens they compose.
+        let composing = rule.fragment
+            && rules.iter().any(|other| {
+                other.name != rule.name
+                    && other.terminal
+                    && other.references.contains(&rule.name)
+            });
+        match &reachable {
+            | _ if rule.extension || composing || skipped.contains(rule.name) =>
+                {},
+            | Some(reachable) if !reachable.contains(rule.name) => report(
+                rule,
+                Lint::UnusedRule,
+                eco_format!(
+                    "rule `{}` is not reachable from the start rules",
+                    rule.name
+                ),
+            ),
+            | Some(_) => {},
+            | None => {
+                let used = rules.iter().any(|other| {
+                    other.name != rule.name
+                        && other.references.contains(&rule.name)
+                });
+                if !used {
+                    report(
+                        rule,
+                        Lint::UnusedRule,
+                        eco_format!("rule `{}` is never used", rule.name),
+                    );
+                }
+            },
+        }
+
+        for &name in &rule.references {
+            if !definitions.contains_key(name) && !imported.contains(name) {
+                report(
+                    rule,
+                    Lint::UndefinedRule,
+                    unresolved_message(name).into(),
+                );
+            }
+        }
+
+        for name in rule.actions.iter().filter_map(|a| swallowed_rule(a)) {
+            report(
+                rule,
+                Lint::SwallowedRule,
+                eco_format!(
+                    "action of rule `{}` seems to contain the rule `{name}`, \
+                     consider ending the rule with `;` before it",
+                    rule.name
+                ),
+            );
+        }
+
+        if let Some(chapter) = &config.metas {
+            for &meta in &rule.metas {
+                let layout = config.layout && describe_layout(meta).is_some();
+                if !layout && !metas.contains_key(meta) {
+                    report(
+                        rule,
+                        Lint::UndefinedMeta,
+                        eco_format!(
+                            "meta `{meta}` of rule `{}` is not defined in \
+                             `{chapter}`",
+                            rule.name
+                        ),
+                    );
+                }
+            }
+        }
+
+        for &name in &rule.annotations {
+            if !ANNOTATIONS.contains(&name) {
+                report(
+                    rule,
+                    Lint::UnknownAnnotation,
+                    eco_format!(
+                        "annotation `@{name}` of rule `{}` is unknown",
+                        rule.name
+                    ),
+                );
+            }
+        }
+
+        let excess = complexity_excess(rule.complexity, config.complexity);
+        if !excess.is_empty() {
+            report(
+                rule,
+                Lint::ComplexRule,
+                eco_format!(
+                    "rule `{}` has {}, consider splitting it",
+                    rule.name,
+                    excess.join(", ")
+                ),
+            );
+        }
+
+        if !rule.extension {
+            for message in naming_violations(rule, config) {
+                report(rule, Lint::RuleNaming, message);
+            }
+        }
+
+        for &name in &rule.conditions {
+            if !modes.contains(name) {
+                report(
+                    rule,
+                    Lint::UnknownMode,
+                    eco_format!(
+                        "condition of rule `{}` uses `{name}`, which is not a \
+                         declared mode",
+                        rule.name
+                    ),
+                );
+            }
+        }
+    }
+
+    // Parsing every other code block is only worth it when asked for.
+    let lint = Lint::UntaggedGrammar;
+    let level = config.level(lint);
+    if level > Level::Allow {
+        for page in pages {
+            for line in untagged_grammars(page) {
+                diagnostics.push(Diagnostic {
+                    level,
+                    lint: Some(lint),
+                    message: "code block looks like a grammar, consider \
+                              tagging it with `syntax`"
+                        .into(),
+                    path: page.href.clone(),
+                    line: Some(line),
+                });
+            }
+        }
+    }
+
+    if config.strict {
+        // Errors rendered in the book fail the build.
+        for page in pages {
+            for item in &page.items {
+                let Item::Code(block) = item else { continue };
+                for error in
+                    block.code.recur_iter().filter_map(SyntaxNode::as_error)
+                {
+                    diagnostics.push(Diagnostic {
+                        level: Level::Deny,
+                        lint: None,
+                        message: error.message.clone(),
+                        path: page.href.clone(),
+                        line: None,
+                    });
+                }
+            }
+        }
+
+        if config.unresolved == Unresolved::Error {
+            for rule in &rules {
+                for &name in &rule.references {
+                    if !definitions.contains_key(name) {
+                        diagnostics.push(Diagnostic {
+                            level: Level::Deny,
+                            lint: None,
+                            message: unresolved_message(name).into(),
+                            path: rule.path.clone(),
+                            line: Some(rule.line),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Collect the rules of a grammar block, with their lint levels.
+///
+/// A directive applies to the rule following it, or to the whole block if it
+/// is separated from the first rule by a blank line.
+fn collect_rules<'a>(
+    block: &'a Block,
+    path: &'a EcoString,
+    rules: &mut Vec<RuleInfo<'a>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let code = &block.code;
+    debug_assert_eq!(code.kind(), SyntaxKind::Root);
+
+    let mut lines = rule_lines(block).into_iter();
+    let mut block = Levels::new();
+    let mut pending = Levels::new();
+    let mut first = true;
+
+    for node in code.children() {
+        match node.kind() {
+            | SyntaxKind::Comment => {
+                if let Err(message) = parse_directive(node.text(), &mut pending)
+                {
+                    diagnostics.push(Diagnostic {
+                        level: Level::Warn,
+                        lint: None,
+                        message,
+                        path: path.clone(),
+                        line: None,
+                    });
+                }
+            },
+            | SyntaxKind::Whitespace
+                if first && node.text().matches('\n').count() >= 2 =>
+            {
+                block.extend(pending.drain());
+            },
+            | SyntaxKind::Rule => {
+                first = false;
+                let mut levels = block.clone();
+                levels.extend(pending.drain());
+                if !node.erroneous() {
+                    // Named groups are rules defined on the line of their
+                    // parent.
+                    let line = lines.next().unwrap_or_default();
+                    for node in std::iter::once(node).chain(nested_rules(node))
+                    {
+                        let references = rule_references(node);
+                        rules.push(RuleInfo {
+                            name: rule_name(node),
+                            path,
+                            line,
+                            terminal: references.is_empty()
+                                || has_annotation(node, "token")
+                                || is_fragment(node),
+                            extension: is_extension(node),
+                            fragment: is_fragment(node),
+                            levels: levels.clone(),
+                            references,
+                            conditions: rule_conditions(node),
+                            actions: rule_actions(node),
+                            metas: rule_metas(node),
+                            annotations: rule_annotations(node)
+                                .into_iter()
+                                .map(|(name, _)| name)
+                                .collect(),
+                            complexity: rule_complexity(node),
+                        });
+                    }
+                }
+            },
+            | _ => {},
+        }
+    }
+}
+
+/// The naming conventions broken by a rule.
+///
+/// Helper rules are checked without their leading underscores.
+fn naming_violations(rule: &RuleInfo, config: &Config) -> Vec<EcoString> {
+    let naming = &config.naming;
+    let mut violations = Vec::new();
+
+    let name = local_name(rule.name).trim_start_matches('_');
+    let (case, kind) = if rule.terminal && naming.uppercase_terminals {
+        (Some(Case::Upper), "terminal rule")
+    } else {
+        (naming.case, "rule")
+    };
+    if let Some(case) = case {
+        if !case.matches(name) {
+            violations.push(eco_format!(
+                "{kind} `{}` should be {}",
+                rule.name,
+                case.name()
+            ));
+        }
+    }
+
+    for prefix in &naming.forbidden_prefixes {
+        if rule.name.starts_with(prefix.as_str()) {
+            violations.push(eco_format!(
+                "rule `{}` uses the forbidden prefix `{prefix}`",
+                rule.name
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Parse a comment like `// grammar-lint: allow(unused-rule)`.
+///
+/// Comments which are not directives are ignored.
+fn parse_directive(
+    comment: &str,
+    levels: &mut Levels,
+) -> Result<(), EcoString> {
+    let Some(directive) = comment
+        .strip_prefix("//")
+        .and_then(|text| text.trim().strip_prefix(DIRECTIVE))
+    else {
+        return Ok(());
+    };
+
+    let mut rest = directive.trim();
+    while !rest.is_empty() {
+        let (level, tail) = rest.split_once('(').ok_or_else(|| {
+            eco_format!("malformed lint directive `{comment}`")
+        })?;
+        let (lints, tail) = tail.split_once(')').ok_or_else(|| {
+            eco_format!("malformed lint directive `{comment}`")
+        })?;
+
+        let level = level.trim();
+        let level = Level::from_name(level)
+            .ok_or_else(|| eco_format!("unknown lint level `{level}`"))?;
+        for lint in lints.split(',').map(str::trim) {
+            let lint = Lint::from_name(lint)
+                .ok_or_else(|| eco_format!("unknown lint `{lint}`"))?;
+            levels.insert(lint, level);
+        }
+
+        rest = tail.trim_start_matches([' ', ',']);
+    }
+
+    Ok(())
+}
+
+/// The name of a rule.
+pub fn rule_name(rule: &SyntaxNode) -> &EcoString {
+    rule.find_by_kind(SyntaxKind::Identifier).unwrap().text()
+}
+
+/// The names of the rules referenced in the definition of a rule, without its
+/// parameters.
+pub fn rule_references(rule: &SyntaxNode) -> Vec<&EcoString> {
+    let params = rule_params(rule);
+    rule.children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+        .flat_map(|definition| definition.recur_iter())
+        .filter(|n| n.kind() == SyntaxKind::Identifier)
+        .map(SyntaxNode::text)
+        .filter(|name| !params.contains(name))
+        .collect()
+}
+
+/// The groups named in the definition of a rule, like `(sub: "a" | "b")`,
+/// which are rules of their own.
+pub fn nested_rules(rule: &SyntaxNode) -> Vec<&SyntaxNode> {
+    rule.children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+        .flat_map(|definition| definition.recur_iter())
+        .filter(|n| n.kind() == SyntaxKind::NamedGroup && !n.erroneous())
+        .collect()
+}
+
+/// The annotations of a rule, as pairs of their name without the `@` and their
+/// arguments, if any.
+pub fn rule_annotations(rule: &SyntaxNode) -> Vec<(&str, Option<&str>)> {
+    Rule::from_untyped(rule)
+        .into_iter()
+        .flat_map(Rule::annotations)
+        .map(|annotation| (annotation.name(), annotation.args()))
+        .collect()
+}
+
+/// Whether a rule has an annotation, like `inline` for `@inline`.
+pub fn has_annotation(rule: &SyntaxNode, name: &str) -> bool {
+    rule_annotations(rule).iter().any(|&(n, _)| n == name)
+}
+
+/// The version a rule was added in, like `1.2` for `@since("1.2")`.
+pub fn rule_since(rule: &SyntaxNode) -> Option<&str> {
+    rule_annotations(rule)
+        .into_iter()
+        .find(|&(name, _)| name == "since")
+        .and_then(|(_, version)| version)
+        .map(|version| version.trim().trim_matches('"'))
+        .filter(|version| !version.is_empty())
+}
+
+/// Whether a rule is a helper, not linked from other rules, either because
+/// its name, without its namespace, starts with `_` or because it is annotated
+/// with `@hidden`.
+pub fn is_helper(rule: &SyntaxNode) -> bool {
+    local_name(rule_name(rule)).starts_with('_')
+        || has_annotation(rule, "hidden")
+}
+
+/// Whether a rule adds alternatives to a rule defined elsewhere, like
+/// `a += b;`.
+pub fn is_extension(rule: &SyntaxNode) -> bool {
+    Rule::from_untyped(rule).is_some_and(Rule::is_extension)
+}
+
+/// Whether a rule only composes tokens, like `fragment a: b;`.
+pub fn is_fragment(rule: &SyntaxNode) -> bool {
+    Rule::from_untyped(rule).is_some_and(Rule::is_fragment)
+}
+
+/// The name of a rule without its namespace, like `Identifier` for
+/// `lexer::Identifier`.
+fn local_name(name: &str) -> &str {
+    name.rsplit_once("::").map_or(name, |(_, local)| local)
+}
+
+/// The documentation of a rule, from its doc comments without the slashes.
+pub fn rule_doc(rule: &SyntaxNode) -> Option<String> {
+    let lines = Rule::from_untyped(rule)
+        .into_iter()
+        .flat_map(Rule::docs)
+        .collect::<Vec<_>>();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// The parameters of a rule written in parentheses, like `item` in
+/// `list(item): ...;`.
+pub fn rule_params(rule: &SyntaxNode) -> Vec<&EcoString> {
+    Rule::from_untyped(rule)
+        .into_iter()
+        .flat_map(Rule::params)
+        .collect()
+}
+
+/// The names used in the conditions of a rule, like `a` in `b if a`, except
+/// literals.
+fn rule_conditions(rule: &SyntaxNode) -> Vec<&str> {
+    rule.children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+        .flat_map(|definition| definition.recur_iter())
+        .filter(|n| n.kind() == SyntaxKind::Action)
+        .filter(|action| action.find_by_kind(SyntaxKind::If).is_some())
+        .flat_map(|action| action.children())
+        .flat_map(|condition| match condition.kind() {
+            | SyntaxKind::Operation => {
+                condition_names(condition.text()).collect::<Vec<_>>()
+            },
+            | _ => condition
+                .recur_iter()
+                .filter(|n| n.kind() == SyntaxKind::Variable)
+                .map(|n| n.text().as_str())
+                .filter(|name| !["true", "false"].contains(name))
+                .collect(),
+        })
+        .collect()
+}
+
+/// The complexity of the definition of a rule.
+pub fn rule_complexity(rule: &SyntaxNode) -> Complexity {
+    let definitions = rule
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+        .collect::<Vec<_>>();
+    let tokens = definitions
+        .iter()
+        .flat_map(|definition| definition.recur_iter())
+        .filter(|n| n.children().len() == 0 && !n.kind().is_trivia())
+        .collect::<Vec<_>>();
+
+    Complexity {
+        alternatives: 1 + tokens
+            .iter()
+            .filter(|n| n.kind() == SyntaxKind::Bar)
+            .count(),
+        depth: definitions
+            .iter()
+            .map(|&d| group_depth(d))
+            .max()
+            .unwrap_or(0),
+        tokens: tokens.len(),
+    }
+}
+
+/// The nesting depth of the groups in a node.
+fn group_depth(node: &SyntaxNode) -> usize {
+    #[derive(Default)]
+    struct Depth {
+        current: usize,
+        max: usize,
+    }
+
+    impl Visitor<'_> for Depth {
+        fn enter(&mut self, node: &SyntaxNode) -> bool {
+            if is_group(node) {
+                self.current += 1;
+                self.max = self.max.max(self.current);
+            }
+            true
+        }
+
+        fn leave(&mut self, node: &SyntaxNode) {
+            if is_group(node) {
+                self.current -= 1;
+            }
+        }
+    }
+
+    fn is_group(node: &SyntaxNode) -> bool {
+        matches!(node.kind(), SyntaxKind::Group | SyntaxKind::NamedGroup)
+    }
+
+    let mut depth = Depth::default();
+    walk(node, &mut depth);
+    depth.max
+}
+
+/// How the complexity of a rule exceeds the limits, like `14 alternatives`.
+pub fn complexity_excess(
+    complexity: Complexity,
+    limits: Complexity,
+) -> Vec<String> {
+    let mut excess = Vec::new();
+    if complexity.alternatives > limits.alternatives {
+        excess.push(format!("{} alternatives", complexity.alternatives));
+    }
+    if complexity.depth > limits.depth {
+        excess.push(format!("groups nested {} deep", complexity.depth));
+    }
+    if complexity.tokens > limits.tokens {
+        excess.push(format!("{} tokens", complexity.tokens));
+    }
+    excess
+}
+
+/// The metas in the definition of a rule.
+fn rule_metas(rule: &SyntaxNode) -> Vec<&str> {
+    rule.children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+        .flat_map(|definition| definition.recur_iter())
+        .filter(|n| n.kind() == SyntaxKind::Meta)
+        .map(|n| n.text().as_str())
+        .collect()
+}
+
+/// The names of the rules reachable from the start and skipped rules of the
+/// book, or `None` if there is no `%start` pragma.
+fn reachable_rules<'a>(
+    pages: &'a [Page],
+    rules: &[RuleInfo<'a>],
+    definitions: &HashMap<&EcoString, Vec<usize>>,
+) -> Option<HashSet<&'a EcoString>> {
+    let pragma_rules = |pragma| {
+        pages
+            .iter()
+            .flat_map(move |page| page_pragma_rules(page, pragma))
+    };
+    let mut queue = pragma_rules("start").collect::<Vec<_>>();
+    if queue.is_empty() {
+        return None;
+    }
+    queue.extend(pragma_rules("skip"));
+
+    let mut reachable = HashSet::new();
+    while let Some(name) = queue.pop() {
+        if !reachable.insert(name) {
+            continue;
+        }
+        for &i in definitions.get(name).into_iter().flatten() {
+            queue.extend(rules[i].references.iter().copied());
+        }
+    }
+    Some(reachable)
+}
+
+/// The code of the actions and conditions in the definition of a rule.
+fn rule_actions(rule: &SyntaxNode) -> Vec<&str> {
+    rule.children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+        .flat_map(|definition| definition.recur_iter())
+        .filter(|n| n.kind() == SyntaxKind::Action)
+        .flat_map(|action| action.children())
+        .filter(|n| n.kind() == SyntaxKind::Operation)
+        .map(|n| n.text().as_str())
+        .collect()
+}
+
+/// The name of the rule header in the code of an action, like `b` in
+/// `x\nb: c`, which is what an action swallowing the next rule looks like.
+///
+/// Headers only start a line, outside of braces and parentheses, so struct
+/// literals like `Point { x: a }` and type ascriptions like `let v: T` are not
+/// headers.
+fn swallowed_rule(action: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    for (i, c) in action.char_indices() {
+        match c {
+            | '(' | '{' => depth += 1,
+            | ')' | '}' => depth = depth.saturating_sub(1),
+            | '\n' if depth == 0 => {
+                if let Some(name) = rule_header(&action[i + 1..]) {
+                    return Some(name);
+                }
+            },
+            | _ => {},
+        }
+    }
+    None
+}
+
+/// The name of the rule header at the start of a line, like `b` in `b: c` or
+/// `b[p] : c`, but not in the path `b::c`.
+fn rule_header(line: &str) -> Option<&str> {
+    let line = line.trim_start_matches([' ', '\t']);
+    let start =
+        |text: &str| text.starts_with(|c: char| c.is_alphabetic() || c == '_');
+    let word = |text: &str| {
+        text.find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(text.len())
+    };
+    if !start(line) {
+        return None;
+    }
+    let mut end = word(line);
+    // Qualified names, like `lexer::Identifier`.
+    while line[end..].strip_prefix("::").is_some_and(start) {
+        end += 2 + word(&line[end + 2..]);
+    }
+    let name = &line[..end];
+
+    let mut rest = line[end..].trim_start_matches([' ', '\t']);
+    if let Some(param) = rest.strip_prefix('[') {
+        rest = param[param.find(']')? + 1..].trim_start_matches([' ', '\t']);
+    }
+    let rest = rest.strip_prefix(':')?;
+    (!rest.starts_with(':')).then_some(name)
+}
+
+/// The names used in the code of a condition, except literals and logical
+/// operators.
+fn condition_names(condition: &str) -> impl Iterator<Item = &str> {
+    condition
+        .split(|c: char| !c.is_alphanumeric() && c != '_' && c != '-')
+        .map(|word| word.trim_matches('-'))
+        .filter(|word| {
+            word.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        })
+        .filter(|word| !["true", "false", "and", "or", "not"].contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::import_grammars;
+    use mdbook_grammar_syntax::parse;
+
+    fn lint(codes: &[&str]) -> Vec<(Level, Option<Lint>)> {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: codes
+                .iter()
+                .map(|code| {
+                    Item::Code(Block {
+                        code: parse(code),
+                        options: Vec::new(),
+                        line: 1,
+                    })
+                })
+                .collect(),
+        }];
+
+        let mut config = Config::default();
+        config.lints.insert(Lint::UnusedRule, Level::Warn);
+        check(&pages, &Imports::new(), &config)
+            .into_iter()
+            .map(|d| (d.level, d.lint))
+            .collect()
+    }
+
+    #[test]
+    fn test_lints() {
+        assert_eq!(lint(&["a: b; b: a;"]), []);
+        assert_eq!(lint(&["a: a;"]), [(Level::Warn, Some(Lint::UnusedRule))]);
+        assert_eq!(lint(&["a: b c; b: a;", "c: a;"]), []);
+        assert_eq!(lint(&["a: b; b: a; b: a;"]), [(
+            Level::Warn,
+            Some(Lint::DuplicateRule)
+        )]);
+    }
+
+    #[test]
+    fn test_unknown_mode() {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![
+                Item::Text("{{#mode strict, loose}}".into()),
+                Item::Code(Block {
+                    code: parse("a: a if strict\n | a if !lose && true\n ;"),
+                    options: Vec::new(),
+                    line: 1,
+                }),
+            ],
+        }];
+
+        let mut config = Config::default();
+        config.lints.insert(Lint::UnknownMode, Level::Warn);
+        let diagnostics = check(&pages, &Imports::new(), &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "condition of rule `a` uses `lose`, which is not a declared mode"
+        );
+    }
+
+    #[test]
+    fn test_rule_naming() {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(
+                    "expr: Term tmp_a _helper;\nTerm: DIGIT;\nDIGIT: \
+                     \"0\"..\"9\";\ntmp_a: digit;\ndigit: \"0\";",
+                ),
+                options: Vec::new(),
+                line: 3,
+            })],
+        }];
+
+        let mut config = Config::default();
+        config.naming.case = Some(Case::Snake);
+        config.naming.uppercase_terminals = true;
+        config.naming.forbidden_prefixes = vec!["tmp_".into()];
+        let diagnostics = check(&pages, &Imports::new(), &config)
+            .into_iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(diagnostics, [
+            "warning[rule-naming]: rule `Term` should be snake_case (in \
+             page.md:4)",
+            "warning[rule-naming]: rule `tmp_a` uses the forbidden prefix \
+             `tmp_` (in page.md:6)",
+            "warning[rule-naming]: terminal rule `digit` should be UPPER_CASE \
+             (in page.md:7)",
+        ]);
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let code =
+            "a: lexer::Ident lexer::_ws;\nlexer::Ident: \"x\";\nlexer::_ws: ;";
+        assert_eq!(lint(&[code]), [(Level::Warn, Some(Lint::UnusedRule))]);
+        assert_eq!(local_name("lexer::Ident"), "Ident");
+        assert_eq!(local_name("Ident"), "Ident");
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert_eq!(lint(&["a: b; b: ;", "b += \"x\"; b += c; c: ;"]), [(
+            Level::Warn,
+            Some(Lint::UnusedRule)
+        )]);
+        assert_eq!(lint(&["a: b; b += ;"]), [(
+            Level::Warn,
+            Some(Lint::UnusedRule)
+        )]);
+    }
+
+    #[test]
+    fn test_swallowed_rule() {
+        assert_eq!(swallowed_rule(" x\nb: c"), Some("b"));
+        assert_eq!(swallowed_rule(" x\n  b[p] : c"), Some("b"));
+        assert_eq!(swallowed_rule(" x b: c"), None);
+        assert_eq!(swallowed_rule(" Expr::new(x)\nb::c"), None);
+        assert_eq!(swallowed_rule(" x\nlexer::b: c"), Some("lexer::b"));
+        assert_eq!(swallowed_rule(" a ? b : c"), None);
+        assert_eq!(swallowed_rule(" f(1): c"), None);
+        assert_eq!(swallowed_rule(" Point { x: a }"), None);
+        assert_eq!(swallowed_rule(" Point {\n  x: a,\n}"), None);
+        assert_eq!(swallowed_rule(" f(\n  x: a,\n)"), None);
+        assert_eq!(swallowed_rule(" { let v: Vec<u8> = x; v }"), None);
+        assert_eq!(swallowed_rule(" x\nlet v: T = x"), None);
+
+        let code = "a: b -> Point { x: a };";
+        assert_eq!(lint(&[code]), [(Level::Warn, Some(Lint::UnusedRule))]);
+    }
+
+    #[test]
+    fn test_shadowed_rule() {
+        let page = |href: &str, code: &str| Page {
+            href: href.into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(code),
+                options: Vec::new(),
+                line: 1,
+            })],
+        };
+        let pages = vec![page("a.md", "x: y; y: ;"), page("b.md", "y: ; y: ;")];
+        let lints = |config: &Config| {
+            check(&pages, &Imports::new(), config)
+                .into_iter()
+                .map(|d| (d.lint, d.path))
+                .collect::<Vec<_>>()
+        };
+
+        let mut config = Config::default();
+        assert_eq!(lints(&config), [
+            (Some(Lint::DuplicateRule), "b.md".into()),
+            (Some(Lint::DuplicateRule), "b.md".into()),
+        ]);
+
+        config.scope = Scope::Chapter;
+        assert_eq!(lints(&config), [
+            (Some(Lint::ShadowedRule), "b.md".into()),
+            (Some(Lint::DuplicateRule), "b.md".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_rule_complexity() {
+        let rule = parse("a: b (c | (d e)*) | f;");
+        let rule = rule.children().next().unwrap();
+        assert_eq!(rule_complexity(rule), Complexity {
+            alternatives: 3,
+            depth: 2,
+            tokens: 12,
+        });
+
+        let limits = Complexity {
+            alternatives: 3,
+            depth: 1,
+            tokens: 10,
+        };
+        assert_eq!(complexity_excess(rule_complexity(rule), limits), [
+            "groups nested 2 deep",
+            "12 tokens",
+        ]);
+    }
+
+    #[test]
+    fn test_undefined_meta() {
+        let pages = vec![
+            Page {
+                href: "grammar.md".into(),
+                name: "Grammar".into(),
+                items: vec![Item::Code(Block {
+                    code: parse("a: <digit> <letter> <eof>;"),
+                    options: Vec::new(),
+                    line: 1,
+                })],
+            },
+            Page {
+                href: "metas.md".into(),
+                name: "Metas".into(),
+                items: vec![Item::Text("- `<digit>`: a digit\n".into())],
+            },
+        ];
+
+        let mut config = Config {
+            layout: true,
+            ..Config::default()
+        };
+        assert_eq!(check(&pages, &Imports::new(), &config), []);
+
+        config.metas = Some("metas.md".into());
+        let diagnostics = check(&pages, &Imports::new(), &config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "meta `<letter>` of rule `a` is not defined in `metas.md`"
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse("a: b; c: %;"),
+                options: Vec::new(),
+                line: 1,
+            })],
+        }];
+
+        let mut config = Config {
+            strict: true,
+            ..Config::default()
+        };
+        assert_eq!(check(&pages, &Imports::new(), &config).len(), 1);
+
+        config.unresolved = Unresolved::Error;
+        let diagnostics = check(&pages, &Imports::new(), &config);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(Diagnostic::is_error));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(
+                    "@token id: LETTER;\nLETTER: \"a\";\n@other b: id;",
+                ),
+                options: Vec::new(),
+                line: 1,
+            })],
+        }];
+
+        let mut config = Config::default();
+        config.naming.uppercase_terminals = true;
+        let messages = check(&pages, &Imports::new(), &config)
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>();
+        assert_eq!(messages, [
+            "terminal rule `id` should be UPPER_CASE",
+            "annotation `@other` of rule `b` is unknown",
+        ]);
+    }
+
+    #[test]
+    fn test_start_rules() {
+        assert_eq!(lint(&["%start a;", "a: b; b: ; c: d; d: c;"]), [
+            (Level::Warn, Some(Lint::UnusedRule)),
+            (Level::Warn, Some(Lint::UnusedRule))
+        ]);
+        assert_eq!(lint(&["%start a; %skip ws;", "a: ; ws: c; c: ;"]), []);
+        assert_eq!(lint(&["%skip ws; a: a; ws: ;"]), [(
+            Level::Warn,
+            Some(Lint::UnusedRule)
+        )]);
+        assert_eq!(lint(&["%start b; a: a;"]), [
+            (Level::Warn, None),
+            (Level::Warn, Some(Lint::UnusedRule))
+        ]);
+    }
+
+    #[test]
+    fn test_fragments() {
+        // Only the unreachable token is reported, not the fragment it uses.
+        let code = "@token\nNUMBER: DIGIT+;\nfragment DIGIT: \"0\";";
+        assert_eq!(lint(&["%start a; a: ;", code]), [(
+            Level::Warn,
+            Some(Lint::UnusedRule)
+        )]);
+        assert_eq!(lint(&["%start a; a: ; fragment HEX: ;"]), [(
+            Level::Warn,
+            Some(Lint::UnusedRule)
+        )]);
+    }
+
+    #[test]
+    fn test_named_groups() {
+        assert_eq!(lint(&["a: (sub: \"x\") sub;"]), [(
+            Level::Warn,
+            Some(Lint::UnusedRule)
+        )]);
+        assert_eq!(lint(&["a: (b: \"x\") a;", "b: a;"]), [(
+            Level::Warn,
+            Some(Lint::DuplicateRule)
+        )]);
+    }
+
+    #[test]
+    fn test_imports() {
+        let dir = std::env::temp_dir()
+            .join(format!("mdbook-grammar-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lexer.syntax"), "ident: \"x\";").unwrap();
+
+        let page = Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(
+                    "%import \"lexer.syntax\";\n%import \
+                     \"missing.syntax\";\na: ident;",
+                ),
+                options: Vec::new(),
+                line: 1,
+            })],
+        };
+        let mut config = Config {
+            src: Some(dir.clone()),
+            ..Config::default()
+        };
+        config.lints.insert(Lint::UndefinedRule, Level::Deny);
+        let pages = [page];
+        let diagnostics =
+            check(&pages, &import_grammars(&pages, &config), &config);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "page.md");
+        assert!(
+            diagnostics[0]
+                .message
+                .starts_with("cannot import `missing.syntax`")
+        );
+    }
+
+    #[test]
+    fn test_rule_params() {
+        let code = "list(item): item; a: list(\"x\");";
+        assert_eq!(lint(&[code]), [(Level::Warn, Some(Lint::UnusedRule))]);
+    }
+
+    #[test]
+    fn test_directive_rule() {
+        let code =
+            "a: b; b: a;\n// grammar-lint: allow(unused-rule)\nc: ;\nd: ;";
+        assert_eq!(lint(&[code]), [(Level::Warn, Some(Lint::UnusedRule))]);
+    }
+
+    #[test]
+    fn test_directive_block() {
+        let code = "// grammar-lint: allow(unused-rule), \
+                    deny(undefined-rule)\n\nc: ;\nd: e;";
+        assert_eq!(lint(&[code]), [(Level::Deny, Some(Lint::UndefinedRule))]);
+    }
+
+    #[test]
+    fn test_directive_malformed() {
+        let code = "// grammar-lint: forbid(unused-rule)\na: a;";
+        assert_eq!(lint(&[code]), [
+            (Level::Warn, None),
+            (Level::Warn, Some(Lint::UnusedRule))
+        ]);
+    }
+}
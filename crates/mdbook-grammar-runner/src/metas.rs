@@ -0,0 +1,117 @@
+use crate::{
+    Config,
+    book::{Item, Page},
+    code::slug,
+};
+use ecow::EcoString;
+use std::collections::HashMap;
+
+/// Find the metas defined in the chapter configured with `metas`, with the
+/// links to their definitions.
+pub fn find_metas(
+    pages: &[Page],
+    config: &Config,
+) -> HashMap<EcoString, EcoString> {
+    let Some(chapter) = &config.metas else {
+        return HashMap::new();
+    };
+
+    pages
+        .iter()
+        .filter(|page| page.href == *chapter)
+        .flat_map(|page| &page.items)
+        .filter_map(|item| match item {
+            | Item::Text(text) => Some(text),
+            | _ => None,
+        })
+        .flat_map(|text| text.lines())
+        .filter_map(defined_meta)
+        .map(|(_, meta)| {
+            let href = config.link(chapter, &meta_hash(meta));
+            (meta.into(), href.into())
+        })
+        .collect()
+}
+
+/// Add anchors to the definitions of metas in some text of a chapter.
+pub fn anchor_metas(config: &Config, chapter: &str, text: &str) -> String {
+    let mut anchored = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        if let Some((offset, meta)) = defined_meta(line) {
+            anchored += &line[..offset];
+            let anchor = config.anchor(chapter, &meta_hash(meta));
+            anchored += &format!("<a id=\"{anchor}\"></a>");
+            anchored += &line[offset..];
+        } else {
+            anchored += line;
+        }
+    }
+    anchored
+}
+
+/// The meta defined by a line, with the offset of its definition.
+///
+/// A meta is defined by a line, or a list item, starting with the meta as
+/// inline code, like ``- `<digit>`: an ASCII digit``.
+fn defined_meta(line: &str) -> Option<(usize, &str)> {
+    let rest = line.trim_start();
+    let rest = rest
+        .strip_prefix(['-', '*', '+'])
+        .map_or(rest, str::trim_start);
+    let offset = line.len() - rest.len();
+
+    let code = rest.strip_prefix('`')?;
+    let meta = &code[..code.find('`')?];
+    (meta.len() > 2 && meta.starts_with('<') && meta.ends_with('>'))
+        .then_some((offset, meta))
+}
+
+/// The anchor of the definition of a meta, like `syntax-meta-digit`.
+pub fn meta_hash(meta: &str) -> String {
+    format!("syntax-meta-{}", slug(meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defined_meta() {
+        assert_eq!(defined_meta("`<digit>`: a digit"), Some((0, "<digit>")));
+        assert_eq!(
+            defined_meta("  - `<any char>` ..."),
+            Some((4, "<any char>"))
+        );
+        assert_eq!(defined_meta("See `<digit>`."), None);
+        assert_eq!(defined_meta("`digit`"), None);
+        assert_eq!(defined_meta("`<>`"), None);
+    }
+
+    #[test]
+    fn test_metas() {
+        let page = |href: &str| Page {
+            href: href.into(),
+            name: "Page".into(),
+            items: vec![Item::Text(
+                "# Metas\n\n- `<any Unicode scalar value>`: any character\n"
+                    .into(),
+            )],
+        };
+        let config = Config {
+            metas: Some("metas.md".into()),
+            ..Config::default()
+        };
+
+        let metas = find_metas(&[page("other.md"), page("metas.md")], &config);
+        assert_eq!(metas.len(), 1);
+        assert_eq!(
+            metas["<any Unicode scalar value>"],
+            "/metas.md#syntax-meta-any-unicode-scalar-value"
+        );
+
+        assert_eq!(
+            anchor_metas(&config, "metas.md", "Text\n- `<digit>`: a digit\n"),
+            "Text\n- <a id=\"syntax-meta-digit\"></a>`<digit>`: a digit\n"
+        );
+    }
+}
@@ -1,12 +1,12 @@
 use unscanny::Scanner;
 
-pub fn parse_mode(text: &str) -> String {
+/// Render the mode annotations of some text, appending it to the content.
+pub fn parse_mode(text: &str, content: &mut String) {
     let mut s = Scanner::new(text);
-    let mut content = String::new();
 
     loop {
         // Treat as normal text until we find a "{{"
-        content += s.eat_until("{{");
+        *content += s.eat_until("{{");
         let start = s.cursor();
 
         if !s.eat_if("{{") {
@@ -18,7 +18,7 @@ pub fn parse_mode(text: &str) -> String {
         if s.eat_if("#mode") {
             // If we find "#mode", we expect a list of modes
             s.eat_until("}}").split(",").for_each(|mode: &str| {
-                content += &format!(
+                *content += &format!(
                     "<span class=\"syntax-mode\" mode=\"{mode}\">{mode}</span>",
                     mode = mode.trim()
                 )
@@ -26,9 +26,28 @@ pub fn parse_mode(text: &str) -> String {
             s.eat_if("}}");
         } else {
             // This is not a mode, so just treat as normal text
-            content += s.from(start);
+            *content += s.from(start);
+        }
+    }
+}
+
+/// Find the names of the modes declared with `{{#mode a, b}}` in some text.
+pub fn declared_modes(text: &str) -> Vec<&str> {
+    let mut s = Scanner::new(text);
+    let mut modes = Vec::new();
+
+    while !s.done() {
+        s.eat_until("{{");
+        if !s.eat_if("{{") {
+            break;
+        }
+
+        s.eat_whitespace();
+        if s.eat_if("#mode") {
+            modes.extend(s.eat_until("}}").split(',').map(str::trim));
+            s.eat_if("}}");
         }
     }
 
-    content
+    modes
 }
@@ -0,0 +1,83 @@
+use crate::{Config, Diagnostic, GrammarError, Level, config::NAME, run};
+use mdbook::{
+    book::Book,
+    errors::{Error, Result},
+    preprocess::{Preprocessor, PreprocessorContext},
+};
+
+/// The grammar preprocessor, for books built with mdbook as a library.
+///
+/// The options are read from the context of every build. Diagnostics are
+/// logged through mdbook, and the build fails if any is an error.
+/// [`process`](Self::process) returns them instead, with the failure as a
+/// [`GrammarError`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GrammarPreprocessor {
+    timings: bool,
+}
+
+impl GrammarPreprocessor {
+    /// Create the preprocessor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the time spent in each phase, even if the book does not enable
+    /// `timings`.
+    pub fn timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    /// Process the book, returning the problems found, and telling errors of
+    /// the configuration apart from errors in grammar blocks.
+    ///
+    /// The book is not processed if the configuration has errors. Otherwise,
+    /// the error holds the warnings of the configuration too.
+    pub fn process(
+        &self,
+        ctx: &PreprocessorContext,
+        mut book: Book,
+    ) -> Result<(Book, Vec<Diagnostic>), GrammarError> {
+        let mut diagnostics = Config::validate(&ctx.config);
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(GrammarError::Config { diagnostics });
+        }
+
+        let mut config = Config::from_context(ctx);
+        config.timings |= self.timings;
+        diagnostics.extend(run(&mut book, &config));
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Err(GrammarError::Strict { diagnostics });
+        }
+
+        Ok((book, diagnostics))
+    }
+}
+
+impl Preprocessor for GrammarPreprocessor {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, book: Book) -> Result<Book> {
+        let result = self.process(ctx, book);
+        let diagnostics = match &result {
+            | Ok((_, diagnostics)) => diagnostics.as_slice(),
+            | Err(err) => err.diagnostics(),
+        };
+        for diagnostic in diagnostics {
+            match diagnostic.level {
+                | Level::Allow => log::info!("{diagnostic}"),
+                | Level::Warn => log::warn!("{diagnostic}"),
+                | Level::Deny => log::error!("{diagnostic}"),
+            }
+        }
+
+        result.map(|(book, _)| book).map_err(Error::new)
+    }
+
+    fn supports_renderer(&self, _renderer: &str) -> bool {
+        true
+    }
+}
@@ -0,0 +1,64 @@
+//! Previews of rule definitions, shown when hovering links to rules.
+//!
+//! Links carry the definition of their rule in `data-preview`, and its
+//! documentation in `data-doc`, which a small script shows in a popover.
+
+/// The style of the popover.
+const STYLE: &str = "\
+.syntax-preview { position: absolute; z-index: 100; max-width: 40em; padding: \
+                     0.5em 0.75em; border: 1px solid rgba(127, 127, 127, \
+                     0.5); border-radius: 0.25em; background: var(--bg, \
+                     #fff); color: var(--fg, #000); box-shadow: 0 2px 6px \
+                     rgba(0, 0, 0, 0.2); pointer-events: none; }
+.syntax-preview code { white-space: pre-wrap; }
+.syntax-preview p { margin: 0.5em 0 0; font-size: 0.875em; }
+";
+
+/// The script showing the popover, installed once per page.
+const SCRIPT: &str = r#"(() => {
+  if (window.syntaxPreview) return;
+  window.syntaxPreview = true;
+  const selector = ".syntax-link[data-preview]";
+  let popover;
+  document.addEventListener("mouseover", (event) => {
+    const link = event.target.closest(selector);
+    if (!link) return;
+    if (!popover) {
+      popover = document.body.appendChild(document.createElement("div"));
+      popover.className = "syntax-preview";
+    }
+    const code = document.createElement("code");
+    code.textContent = link.dataset.preview;
+    popover.replaceChildren(code);
+    if (link.dataset.doc) {
+      const doc = popover.appendChild(document.createElement("p"));
+      doc.textContent = link.dataset.doc;
+    }
+    const rect = link.getBoundingClientRect();
+    popover.style.left = `${rect.left + window.scrollX}px`;
+    popover.style.top = `${rect.bottom + window.scrollY + 4}px`;
+    popover.hidden = false;
+  });
+  document.addEventListener("mouseout", (event) => {
+    if (popover && event.target.closest(selector)) popover.hidden = true;
+  });
+})();
+"#;
+
+/// The style and script of the previews, added to chapters with grammars.
+pub fn preview_assets() -> String {
+    format!("<style>\n{STYLE}</style>\n<script>\n{SCRIPT}</script>\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_assets() {
+        // A blank line would end the HTML block in the chapter.
+        let assets = preview_assets();
+        assert!(!assets.trim_end().contains("\n\n"));
+        assert!(assets.contains(".syntax-preview {"));
+    }
+}
@@ -0,0 +1,490 @@
+use crate::{
+    book::Block,
+    code::Context,
+    lint::{is_helper, rule_name, rule_params},
+    search::flat_text,
+};
+use ecow::EcoString;
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use mdbook_grammar_syntax::{Repeat, SyntaxKind, SyntaxNode};
+
+/// The radius of the arcs, also used as the spacing between items.
+const R: i32 = 10;
+/// The width of a character in the boxes, for a 13px monospace font.
+const CHAR: i32 = 8;
+/// The distance from the track to the top and bottom of a box.
+const HALF: i32 = 11;
+/// The margin around a diagram.
+const PAD: i32 = 10;
+
+/// Render a railroad diagram of each public rule of a block, shown after it.
+pub fn railroad_diagrams(ctx: &Context, block: &Block) -> String {
+    let mut out = String::new();
+    let rules = block.code.children().filter(|node| {
+        node.kind() == SyntaxKind::Rule && !node.erroneous() && !is_helper(node)
+    });
+    for rule in rules {
+        let Some(definition) = rule.find_by_kind(SyntaxKind::Definition) else {
+            continue;
+        };
+        let params = rule_params(rule);
+        let builder = Builder { ctx, params };
+        let diagram = builder.alternatives(definition.children());
+        out += &format!(
+            "\n\n<div class=\"syntax-railroad\">{}</div>\n\n",
+            svg(rule_name(rule), &diagram)
+        );
+    }
+    out
+}
+
+/// A railroad diagram, read from left to right along its track.
+#[derive(Debug, PartialEq)]
+enum Diagram {
+    /// A track without items.
+    Skip,
+    /// A literal, in a rounded box.
+    Terminal(String),
+    /// A rule or any other item, in a square box, linking to the rule if any.
+    NonTerminal(String, Option<EcoString>),
+    /// Items one after the other.
+    Sequence(Vec<Diagram>),
+    /// Alternatives stacked below the first one.
+    Choice(Vec<Diagram>),
+    /// An item taken once or more, with the separator on the way back and a
+    /// label below it.
+    Repeat {
+        item: Box<Diagram>,
+        separator: Box<Diagram>,
+        label: Option<String>,
+    },
+}
+
+/// Converts the items of a definition to diagrams.
+struct Builder<'a> {
+    ctx: &'a Context<'a>,
+    /// The parameters of the rule, which do not link to rules.
+    params: Vec<&'a EcoString>,
+}
+
+impl Builder<'_> {
+    /// The diagram of items separated by `|`, ignoring delimiters.
+    fn alternatives<'n>(
+        &self,
+        nodes: impl Iterator<Item = &'n SyntaxNode>,
+    ) -> Diagram {
+        let mut alternatives = vec![Vec::new()];
+        for node in nodes {
+            match node.kind() {
+                | SyntaxKind::Bar => alternatives.push(Vec::new()),
+                | SyntaxKind::LeftParen
+                | SyntaxKind::RightParen
+                | SyntaxKind::LeftBracket
+                | SyntaxKind::RightBracket => {},
+                | kind if kind.is_trivia() => {},
+                | _ => alternatives.last_mut().unwrap().extend(self.item(node)),
+            }
+        }
+
+        let mut alternatives =
+            alternatives.into_iter().map(sequence).collect::<Vec<_>>();
+        if alternatives.len() == 1 {
+            alternatives.pop().unwrap()
+        } else {
+            Diagram::Choice(alternatives)
+        }
+    }
+
+    /// The diagram of an item, or `None` if it matches nothing, like an
+    /// action.
+    fn item(&self, node: &SyntaxNode) -> Option<Diagram> {
+        let diagram = match node.kind() {
+            | SyntaxKind::Identifier => {
+                let name = node.text();
+                let link = if self.params.contains(&name) {
+                    None
+                } else {
+                    self.link(name)
+                };
+                Diagram::NonTerminal(name.to_string(), link)
+            },
+            | SyntaxKind::Reference => {
+                let name = node.find_by_kind(SyntaxKind::Identifier)?.text();
+                Diagram::NonTerminal(text(node), self.link(name))
+            },
+            | SyntaxKind::Empty => Diagram::Skip,
+            | SyntaxKind::Group | SyntaxKind::Definition => {
+                self.alternatives(node.children())
+            },
+            | SyntaxKind::NamedGroup => self.alternatives(
+                node.find_by_kind(SyntaxKind::Definition)?.children(),
+            ),
+            | SyntaxKind::Optional => {
+                optional(self.alternatives(node.children()))
+            },
+            | SyntaxKind::Constraint => {
+                self.item(node.children().find(|n| !n.kind().is_trivia())?)?
+            },
+            | SyntaxKind::Repeating => {
+                let item = self.item(node.children().next()?)?;
+                repeat(item, Repeat::of(node))
+            },
+            | SyntaxKind::Separated => {
+                let mut parts = node.children().filter(|n| {
+                    !n.kind().is_trivia() && n.kind() != SyntaxKind::Percent
+                });
+                let item = self.item(parts.next()?)?;
+                let separator = self.item(parts.next()?)?;
+                Diagram::Repeat {
+                    item: Box::new(item),
+                    separator: Box::new(separator),
+                    label: None,
+                }
+            },
+            | SyntaxKind::Action | SyntaxKind::Label => return None,
+            | SyntaxKind::Difference
+            | SyntaxKind::Looking
+            | SyntaxKind::Converse => Diagram::NonTerminal(text(node), None),
+            | _ => Diagram::Terminal(text(node)),
+        };
+        Some(diagram)
+    }
+
+    fn link(&self, name: &str) -> Option<EcoString> {
+        let chapter = self.ctx.page.map(|page| &page.href);
+        self.ctx.rules.link(self.ctx.config, chapter, name)
+    }
+}
+
+/// The diagram of items one after the other.
+fn sequence(mut items: Vec<Diagram>) -> Diagram {
+    match items.len() {
+        | 0 => Diagram::Skip,
+        | 1 => items.pop().unwrap(),
+        | _ => Diagram::Sequence(items),
+    }
+}
+
+/// The diagram of an item which may be skipped.
+fn optional(item: Diagram) -> Diagram {
+    Diagram::Choice(vec![item, Diagram::Skip])
+}
+
+/// The diagram of a repeated item, labeled with its bounds unless they are
+/// those of `?`, `*` or `+`.
+fn repeat(item: Diagram, bounds: Option<Repeat>) -> Diagram {
+    let (min, max) = bounds.map_or((1, None), |b| (b.min, b.max));
+    let label = match (min, max) {
+        | (0 | 1, None) => None,
+        | (min, Some(max)) if min == max => Some(format!("{min} times")),
+        | (min, Some(max)) => Some(format!("{min} to {max} times")),
+        | (min, None) => Some(format!("{min} or more times")),
+    };
+    match (min, max) {
+        | (0, Some(1)) => return optional(item),
+        | (1, Some(1)) => return item,
+        | _ => {},
+    }
+    let repeat = Diagram::Repeat {
+        item: Box::new(item),
+        separator: Box::new(Diagram::Skip),
+        label,
+    };
+    if min == 0 { optional(repeat) } else { repeat }
+}
+
+/// The text of an item on a single line.
+fn text(node: &SyntaxNode) -> String {
+    let mut text = String::new();
+    flat_text(node, &mut text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Render a diagram as a standalone SVG image.
+fn svg(name: &str, diagram: &Diagram) -> String {
+    let (width, up, down) = diagram.size();
+    let width = width + 2 * R + 2 * PAD;
+    let height = up + down + 2 * PAD;
+    let y = PAD + up;
+
+    let mut out = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" \
+         height=\"{height}\" viewBox=\"0 0 {width} {height}\" role=\"img\" \
+         aria-label=\"railroad diagram of {label}\" fill=\"none\" \
+         stroke=\"currentColor\" font-family=\"monospace\" font-size=\"13\" \
+         text-anchor=\"middle\">",
+        label = encode_double_quoted_attribute(name),
+    );
+    out += &format!("<path d=\"M{PAD} {top}v14M{PAD} {y}h{R}\"/>", top = y - 7);
+    diagram.draw(&mut out, PAD + R, y);
+    let end = width - PAD;
+    out += &format!(
+        "<path d=\"M{start} {y}H{end}M{end} {top}v14\"/>",
+        start = end - R,
+        top = y - 7,
+    );
+    out += "</svg>";
+    out
+}
+
+impl Diagram {
+    /// The width of the diagram, and its extent above and below the track.
+    fn size(&self) -> (i32, i32, i32) {
+        match self {
+            | Diagram::Skip => (0, 0, 0),
+            | Diagram::Terminal(text) | Diagram::NonTerminal(text, _) => {
+                (text.chars().count() as i32 * CHAR + 2 * R, HALF, HALF)
+            },
+            | Diagram::Sequence(items) => {
+                let sizes = items.iter().map(Diagram::size).collect::<Vec<_>>();
+                let width = sizes.iter().map(|s| s.0).sum::<i32>()
+                    + R * (sizes.len() as i32 - 1);
+                let up = sizes.iter().map(|s| s.1).max().unwrap_or(0);
+                let down = sizes.iter().map(|s| s.2).max().unwrap_or(0);
+                (width, up, down)
+            },
+            | Diagram::Choice(alternatives) => {
+                let offsets = choice_offsets(alternatives);
+                let last = alternatives.last().map_or(0, |a| a.size().2);
+                let width =
+                    alternatives.iter().map(|a| a.size().0).max().unwrap_or(0);
+                let up = alternatives.first().map_or(0, |a| a.size().1);
+                (width + 4 * R, up, offsets.last().unwrap_or(&0) + last)
+            },
+            | Diagram::Repeat {
+                item,
+                separator,
+                label,
+            } => {
+                let (item_width, up, _) = item.size();
+                let (separator_width, _, separator_down) = separator.size();
+                let label = if label.is_some() { 16 } else { 0 };
+                let down = repeat_offset(item, separator) + separator_down;
+                (item_width.max(separator_width) + 2 * R, up, down + label)
+            },
+        }
+    }
+
+    /// Draw the diagram with its track starting at `(x, y)`.
+    fn draw(&self, out: &mut String, x: i32, y: i32) {
+        let (width, ..) = self.size();
+        match self {
+            | Diagram::Skip => line(out, x, y, width),
+            | Diagram::Terminal(text) | Diagram::NonTerminal(text, _) => {
+                let rounded = if let Diagram::Terminal(_) = self {
+                    R
+                } else {
+                    0
+                };
+                let mut shape = format!(
+                    "<rect x=\"{x}\" y=\"{top}\" width=\"{width}\" \
+                     height=\"{height}\" rx=\"{rounded}\"/><text x=\"{mid}\" \
+                     y=\"{base}\" fill=\"currentColor\" \
+                     stroke=\"none\">{text}</text>",
+                    top = y - HALF,
+                    height = 2 * HALF,
+                    mid = x + width / 2,
+                    base = y + 4,
+                    text = encode_text(text),
+                );
+                if let Diagram::NonTerminal(_, Some(href)) = self {
+                    shape = format!(
+                        "<a href=\"{}\">{shape}</a>",
+                        encode_double_quoted_attribute(href)
+                    );
+                }
+                *out += &shape;
+            },
+            | Diagram::Sequence(items) => {
+                let mut x = x;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        line(out, x, y, R);
+                        x += R;
+                    }
+                    item.draw(out, x, y);
+                    x += item.size().0;
+                }
+            },
+            | Diagram::Choice(alternatives) => {
+                let offsets = choice_offsets(alternatives);
+                for (alternative, &dy) in alternatives.iter().zip(&offsets) {
+                    let inner = alternative.size().0;
+                    let rest = width - 4 * R - inner;
+                    if dy == 0 {
+                        line(out, x, y, 2 * R);
+                    } else {
+                        *out += &format!(
+                            "<path d=\"M{x} {y}a{R} {R} 0 0 1 {R} {R}v{v}a{R} \
+                             {R} 0 0 0 {R} {R}\"/>",
+                            v = dy - 2 * R,
+                        );
+                    }
+                    alternative.draw(out, x + 2 * R, y + dy);
+                    let end = x + 2 * R + inner;
+                    if dy == 0 {
+                        line(out, end, y, rest + 2 * R);
+                    } else {
+                        *out += &format!(
+                            "<path d=\"M{end} {ay}h{rest}a{R} {R} 0 0 0 {R} \
+                             -{R}v-{v}a{R} {R} 0 0 1 {R} -{R}\"/>",
+                            ay = y + dy,
+                            v = dy - 2 * R,
+                        );
+                    }
+                }
+            },
+            | Diagram::Repeat {
+                item,
+                separator,
+                label,
+            } => {
+                let dy = repeat_offset(item, separator);
+                line(out, x, y, R);
+                item.draw(out, x + R, y);
+                let end = x + R + item.size().0;
+                line(out, end, y, x + width - end);
+
+                let (separator_width, _, separator_down) = separator.size();
+                *out += &format!(
+                    "<path d=\"M{right} {y}a{R} {R} 0 0 1 {R} {R}v{v}a{R} {R} \
+                     0 0 1 -{R} {R}H{after}\"/>",
+                    right = x + width - R,
+                    v = dy - 2 * R,
+                    after = x + R + separator_width,
+                );
+                separator.draw(out, x + R, y + dy);
+                *out += &format!(
+                    "<path d=\"M{left} {ay}a{R} {R} 0 0 1 -{R} -{R}v-{v}a{R} \
+                     {R} 0 0 1 {R} -{R}\"/>",
+                    left = x + R,
+                    ay = y + dy,
+                    v = dy - 2 * R,
+                );
+                if let Some(label) = label {
+                    *out += &format!(
+                        "<text x=\"{mid}\" y=\"{base}\" font-size=\"11\" \
+                         fill=\"currentColor\" stroke=\"none\">{}</text>",
+                        encode_text(label),
+                        mid = x + width / 2,
+                        base = y + dy + separator_down + 14,
+                    );
+                }
+            },
+        }
+    }
+}
+
+/// The offsets of the tracks of alternatives below the first one.
+fn choice_offsets(alternatives: &[Diagram]) -> Vec<i32> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    let mut previous_down = 0;
+    for (i, alternative) in alternatives.iter().enumerate() {
+        let (_, up, down) = alternative.size();
+        if i > 0 {
+            offset += (previous_down + R + up).max(2 * R);
+        }
+        offsets.push(offset);
+        previous_down = down;
+    }
+    offsets
+}
+
+/// The offset of the track going back below a repeated item.
+fn repeat_offset(item: &Diagram, separator: &Diagram) -> i32 {
+    let (_, _, down) = item.size();
+    let (_, up, _) = separator.size();
+    (down + R + up).max(2 * R)
+}
+
+fn line(out: &mut String, x: i32, y: i32, length: i32) {
+    if length > 0 {
+        *out += &format!("<path d=\"M{x} {y}h{length}\"/>");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, code::Rules};
+    use mdbook_grammar_syntax::parse;
+
+    fn diagram(code: &str) -> Diagram {
+        let config = Config::default();
+        let rules = Rules::default();
+        let ctx = Context {
+            config: &config,
+            rules: &rules,
+            page: None,
+            block: None,
+            params: &[],
+        };
+        let root = parse(code);
+        let rule = root.children().next().unwrap();
+        let builder = Builder {
+            ctx: &ctx,
+            params: rule_params(rule),
+        };
+        builder.alternatives(
+            rule.find_by_kind(SyntaxKind::Definition)
+                .unwrap()
+                .children(),
+        )
+    }
+
+    fn terminal(text: &str) -> Diagram {
+        Diagram::Terminal(text.into())
+    }
+
+    fn non_terminal(text: &str) -> Diagram {
+        Diagram::NonTerminal(text.into(), None)
+    }
+
+    #[test]
+    fn test_diagram() {
+        assert_eq!(
+            diagram("a: b \"c\" | [ \",\" d ] -> act;"),
+            Diagram::Choice(vec![
+                Diagram::Sequence(vec![non_terminal("b"), terminal("\"c\"")]),
+                Diagram::Choice(vec![
+                    Diagram::Sequence(vec![
+                        terminal("\",\""),
+                        non_terminal("d")
+                    ]),
+                    Diagram::Skip,
+                ]),
+            ])
+        );
+        assert_eq!(diagram("list(item): item % \",\";"), Diagram::Repeat {
+            item: Box::new(non_terminal("item")),
+            separator: Box::new(terminal("\",\"")),
+            label: None,
+        });
+        assert_eq!(diagram("a: ;"), Diagram::Skip);
+    }
+
+    #[test]
+    fn test_diagram_repeat() {
+        let Diagram::Choice(choice) = diagram("a: b*;") else {
+            panic!("expected a choice");
+        };
+        assert!(matches!(choice[0], Diagram::Repeat { label: None, .. }));
+        assert_eq!(choice[1], Diagram::Skip);
+
+        let Diagram::Repeat { label, .. } = diagram("a: b{2,4};") else {
+            panic!("expected a repetition");
+        };
+        assert_eq!(label.as_deref(), Some("2 to 4 times"));
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = svg("a", &diagram("a: (b | c) d+;"));
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert!(svg.contains("aria-label=\"railroad diagram of a\""));
+        assert!(!svg.contains("\n\n"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    Config,
+    book::read_pages,
+    code::{find_rules, page_rule_nodes, rule_anchor},
+    lint::{is_helper, rule_annotations, rule_name, rule_references},
+    timings::Timings,
+};
+use ecow::EcoString;
+use mdbook::book::Book;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+/// A rule definition of the book, as resolved for links.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleEntry {
+    /// The name of the rule.
+    pub name: EcoString,
+    /// The path of the chapter defining the rule.
+    pub chapter: EcoString,
+    /// The anchor of the definition, or `None` for helper rules.
+    pub anchor: Option<String>,
+    /// Where references to the rule link to, if anywhere.
+    pub link: Option<EcoString>,
+    /// How many times the rule is referenced in the book.
+    pub references: usize,
+    /// The names of the annotations of the definition, without the `@`, for
+    /// tools analyzing the grammar, like `inline`.
+    pub annotations: Vec<EcoString>,
+}
+
+impl RuleEntry {
+    /// The entry as a JSON object.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name.as_str(),
+            "chapter": self.chapter.as_str(),
+            "anchor": self.anchor,
+            "link": self.link.as_deref(),
+            "references": self.references,
+            "annotations": self
+                .annotations
+                .iter()
+                .map(EcoString::as_str)
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// List the rule definitions of the book, in the order of the chapters.
+///
+/// Rules defined more than once have an entry for each definition.
+pub fn registry(book: &Book, config: &Config) -> Vec<RuleEntry> {
+    let pages = read_pages(book, config, &mut Timings::default());
+    let rules = find_rules(&pages, config);
+
+    let mut references: HashMap<&EcoString, usize> = HashMap::new();
+    for rule in pages.iter().flat_map(page_rule_nodes) {
+        for name in rule_references(rule) {
+            *references.entry(name).or_default() += 1;
+        }
+    }
+
+    let mut entries = Vec::new();
+    for page in &pages {
+        for rule in page_rule_nodes(page) {
+            let name = rule_name(rule);
+            entries.push(RuleEntry {
+                name: name.clone(),
+                chapter: page.href.clone(),
+                anchor: (!is_helper(rule)).then(|| rule_anchor(config, rule)),
+                link: rules.link(config, Some(&page.href), name),
+                references: references.get(name).copied().unwrap_or_default(),
+                annotations: rule_annotations(rule)
+                    .into_iter()
+                    .map(|(name, _)| name.into())
+                    .collect(),
+            });
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook::{BookItem, book::Chapter};
+
+    #[test]
+    fn test_registry() {
+        let mut book = Book::new();
+        book.push_item(BookItem::Chapter(Chapter::new(
+            "Grammar",
+            "```syntax\na: b _c b;\n@inline b: ;\n_c: ;\n```\n".into(),
+            "grammar.md",
+            Vec::new(),
+        )));
+
+        let entries = registry(&book, &Config::default());
+        assert_eq!(entries, [
+            RuleEntry {
+                name: "a".into(),
+                chapter: "grammar.md".into(),
+                anchor: Some("syntax-rule-a".into()),
+                link: Some("/grammar.md#syntax-rule-a".into()),
+                references: 0,
+                annotations: Vec::new(),
+            },
+            RuleEntry {
+                name: "b".into(),
+                chapter: "grammar.md".into(),
+                anchor: Some("syntax-rule-b".into()),
+                link: Some("/grammar.md#syntax-rule-b".into()),
+                references: 2,
+                annotations: vec!["inline".into()],
+            },
+            RuleEntry {
+                name: "_c".into(),
+                chapter: "grammar.md".into(),
+                anchor: None,
+                link: None,
+                references: 1,
+                annotations: Vec::new(),
+            },
+        ]);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{
+    Config,
+    book::{Item, Page},
+    code::{rule_anchor, sectioned_rules},
+    lint::{is_fragment, is_helper, rule_name},
+};
+use ecow::EcoString;
+use mdbook_grammar_syntax::{SyntaxKind, SyntaxNode, signature};
+use serde_json::{Value, json};
+use std::{fs, io, path::Path};
+
+/// Build the search index of the rules defined in the pages.
+///
+/// Each entry gives the name and kind of a rule, the chapter, section and
+/// anchor where it is defined, and its definition on a single line, so that
+/// themes can implement a quick search without scraping the rendered pages. The
+/// signature of the rule lets other tools detect which rules changed between
+/// builds.
+pub fn search_index(pages: &[Page], config: &Config) -> Value {
+    let mut entries = Vec::new();
+
+    for page in pages {
+        for item in &page.items {
+            let Item::Code(block) = item else { continue };
+            // The start rule of the block comes first.
+            let mut rules = sectioned_rules(&block.code).collect::<Vec<_>>();
+            let start = block.value("start");
+            rules.sort_by_key(|&(_, rule)| start != Some(rule_name(rule)));
+            for (section, rule) in rules {
+                let Some(entry) =
+                    search_entry(&page.href, section, rule, config)
+                else {
+                    continue;
+                };
+                entries.push(entry);
+            }
+        }
+    }
+
+    Value::Array(entries)
+}
+
+/// Write the search index to the file, unless it is already up to date.
+///
+/// Leaving the file untouched avoids rebuild loops under `mdbook serve`.
+pub fn write_search_index(
+    path: &Path,
+    pages: &[Page],
+    config: &Config,
+) -> io::Result<()> {
+    let content = serde_json::to_string(&search_index(pages, config))?;
+    if fs::read_to_string(path).is_ok_and(|old| old == content) {
+        return Ok(());
+    }
+    fs::write(path, content)
+}
+
+/// The entry of a rule, or `None` for rules hidden from links.
+fn search_entry(
+    chapter: &EcoString,
+    section: Option<&str>,
+    rule: &SyntaxNode,
+    config: &Config,
+) -> Option<Value> {
+    let name = rule.find_by_kind(SyntaxKind::Identifier)?.text();
+    if is_helper(rule) {
+        return None;
+    }
+
+    let kind = if is_fragment(rule) {
+        "fragment"
+    } else if rule.find_by_kind(SyntaxKind::Param).is_some() {
+        "parameterized-rule"
+    } else {
+        "rule"
+    };
+
+    let definition = definition_line(rule);
+
+    Some(json!({
+        "name": name.as_str(),
+        "kind": kind,
+        "chapter": chapter.as_str(),
+        "section": section,
+        "anchor": rule_anchor(config, rule),
+        "definition": definition,
+        "signature": format!("{:016x}", signature(rule)),
+    }))
+}
+
+/// The metadata of the rules defined in a page, as a JSON-LD script of
+/// `DefinedTerm` entries for search engines, or `None` if it defines none.
+pub fn page_metadata(page: &Page, config: &Config) -> Option<String> {
+    let terms = search_index(std::slice::from_ref(page), config)
+        .as_array()?
+        .iter()
+        .map(|entry| {
+            let anchor = entry["anchor"].as_str().unwrap_or_default();
+            json!({
+                "@type": "DefinedTerm",
+                "name": entry["name"],
+                "description": entry["definition"],
+                "url": config.link(&page.href, anchor),
+            })
+        })
+        .collect::<Vec<_>>();
+    if terms.is_empty() {
+        return None;
+    }
+
+    let metadata = json!({
+        "@context": "https://schema.org",
+        "@type": "DefinedTermSet",
+        "name": page.name.as_str(),
+        "hasDefinedTerm": terms,
+    });
+    // The script must not be closed by the content of a rule.
+    let metadata = metadata.to_string().replace('<', "\\u003c");
+    Some(format!(
+        "<script type=\"application/ld+json\">{metadata}</script>\n"
+    ))
+}
+
+/// The definition of a rule on a single line, without comments.
+pub fn definition_line(rule: &SyntaxNode) -> String {
+    let mut definition = String::new();
+    for node in rule
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::Definition)
+    {
+        flat_text(node, &mut definition);
+    }
+    definition.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Append the text of a node, without comments.
+pub fn flat_text(node: &SyntaxNode, out: &mut String) {
+    if node.kind() == SyntaxKind::Comment {
+        out.push(' ');
+        return;
+    }
+    out.push_str(node.text());
+    for child in node.children() {
+        flat_text(child, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::Block;
+    use mdbook_grammar_syntax::parse;
+
+    #[test]
+    fn test_search_index() {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(
+                    "a: b // comment\n | c;\n_b: ;\n//! Params\nc[x]: x;",
+                ),
+                options: Vec::new(),
+                line: 1,
+            })],
+        }];
+
+        assert_eq!(
+            search_index(&pages, &Config::default()),
+            json!([
+                {
+                    "name": "a",
+                    "kind": "rule",
+                    "chapter": "page.md",
+                    "section": null,
+                    "anchor": "syntax-rule-a",
+                    "definition": "b | c",
+                    "signature": "931b3e13cf579af0",
+                },
+                {
+                    "name": "c",
+                    "kind": "parameterized-rule",
+                    "chapter": "page.md",
+                    "section": "Params",
+                    "anchor": "syntax-rule-c",
+                    "definition": "x",
+                    "signature": "c239edd49f01e493",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_page_metadata() {
+        let page = |code: &str| Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(code),
+                options: Vec::new(),
+                line: 1,
+            })],
+        };
+
+        assert_eq!(
+            page_metadata(&page("a: \"<\" b;"), &Config::default()).unwrap(),
+            "<script type=\"application/ld+json\">{\"@context\":\"https://\
+             schema.org\",\"@type\":\"DefinedTermSet\",\"hasDefinedTerm\":\
+             [{\"@type\":\"DefinedTerm\",\"description\":\"\\\"\\u003c\\\" \
+             b\",\"name\":\"a\",\"url\":\"/page.md#syntax-rule-a\"}],\"name\":\
+             \"Page\"}</script>\n"
+        );
+        assert_eq!(page_metadata(&page("_a: ;"), &Config::default()), None);
+    }
+}
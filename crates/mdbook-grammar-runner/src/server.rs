@@ -0,0 +1,240 @@
+use crate::{
+    Config, Diagnostic,
+    book::{Page, read_pages, render_page},
+    code::{Context, Rules, find_rules},
+    lint::check,
+    timings::Timings,
+};
+use mdbook::MDBook;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// A long-running server keeping several books in memory, answering requests
+/// to check and render them without parsing them again.
+///
+/// Requests and responses are JSON-RPC 2.0 messages, one per line. Every
+/// method takes the root directory of a book as the `book` parameter:
+///
+/// - `load` reads the book from disk again and returns its chapters;
+/// - `check` returns the problems found in the book;
+/// - `render` returns the rendered chapters, or only the one given as
+///   `chapter`;
+/// - `unload` forgets the book.
+///
+/// Books are loaded on their first request.
+#[derive(Default)]
+pub struct Server {
+    books: HashMap<PathBuf, LoadedBook>,
+}
+
+/// A book parsed by the server.
+struct LoadedBook {
+    config: Config,
+    diagnostics: Vec<Diagnostic>,
+    pages: Vec<Page>,
+    rules: Rules,
+}
+
+/// The code of JSON-RPC errors.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The book could not be loaded.
+const LOAD_ERROR: i64 = -32000;
+
+impl Server {
+    /// Create a server without any book.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the requests read from the input, one per line, until it ends.
+    pub fn serve(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str(&line) {
+                | Ok(request) => self.handle(&request),
+                | Err(err) => error(Value::Null, PARSE_ERROR, err.to_string()),
+            };
+            writeln!(output, "{response}")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Answer a single request.
+    pub fn handle(&mut self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(Value::as_str);
+        let params = request.get("params");
+        let Some(path) = params
+            .and_then(|params| params.get("book"))
+            .and_then(Value::as_str)
+        else {
+            return error(id, INVALID_PARAMS, "missing parameter `book`");
+        };
+        let path = PathBuf::from(path);
+
+        let result = match method {
+            | Some("load") => {
+                self.books.remove(&path);
+                self.book(&path).map(|book| {
+                    let chapters = book.pages.iter().map(|p| p.href.as_str());
+                    json!({ "chapters": chapters.collect::<Vec<_>>() })
+                })
+            },
+            | Some("check") => self.book(&path).map(|book| {
+                let diagnostics =
+                    book.diagnostics.iter().map(Diagnostic::to_json).collect();
+                Value::Array(diagnostics)
+            }),
+            | Some("render") => {
+                let chapter = params
+                    .and_then(|params| params.get("chapter"))
+                    .and_then(Value::as_str);
+                self.book(&path).map(|book| book.render(chapter))
+            },
+            | Some("unload") => {
+                Ok(Value::Bool(self.books.remove(&path).is_some()))
+            },
+            | _ => {
+                return error(id, METHOD_NOT_FOUND, "unknown method");
+            },
+        };
+
+        match result {
+            | Ok(result) => {
+                json!({ "jsonrpc": "2.0", "id": id, "result": result })
+            },
+            | Err(message) => error(id, LOAD_ERROR, message),
+        }
+    }
+
+    /// The book at the path, loading it if needed.
+    fn book(&mut self, path: &Path) -> Result<&LoadedBook, String> {
+        if !self.books.contains_key(path) {
+            let book = LoadedBook::load(path)?;
+            self.books.insert(path.to_path_buf(), book);
+        }
+        Ok(&self.books[path])
+    }
+}
+
+impl LoadedBook {
+    /// Load and parse the book at the path.
+    fn load(path: &Path) -> Result<Self, String> {
+        let md = MDBook::load(path).map_err(|err| err.to_string())?;
+        let mut config = Config::from_book(&md.config, "html");
+        config.src = Some(path.join(&md.config.book.src));
+        let pages = read_pages(&md.book, &config, &mut Timings::default());
+
+        let mut diagnostics = Config::validate(&md.config);
+        let rules = find_rules(&pages, &config);
+        diagnostics.extend(check(&pages, &rules.imports, &config));
+
+        Ok(Self {
+            config,
+            diagnostics,
+            pages,
+            rules,
+        })
+    }
+
+    /// Render the chapters of the book, or only the given one, as an object
+    /// of their contents by path.
+    fn render(&self, chapter: Option<&str>) -> Value {
+        let ctx = Context {
+            config: &self.config,
+            rules: &self.rules,
+            page: None,
+            block: None,
+            params: &[],
+        };
+        let chapters = self
+            .pages
+            .iter()
+            .filter(|page| chapter.is_none_or(|c| page.href == c))
+            .map(|page| (page.href.to_string(), render_page(&ctx, page).into()))
+            .collect();
+        Value::Object(chapters)
+    }
+}
+
+/// A JSON-RPC error response.
+fn error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server() {
+        let dir = std::env::temp_dir()
+            .join(format!("mdbook-grammar-server-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/SUMMARY.md"), "- [Grammar](grammar.md)\n")
+            .unwrap();
+        std::fs::write(
+            dir.join("src/grammar.md"),
+            "```syntax\na: b;\na: ;\n```\n",
+        )
+        .unwrap();
+
+        let request = |id: u64, method: &str, params: Value| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            })
+        };
+        let input = format!(
+            "{}\n{}\n{}\nnot json\n",
+            request(1, "check", json!({ "book": dir })),
+            request(
+                2,
+                "render",
+                json!({ "book": dir, "chapter": "grammar.md" })
+            ),
+            request(3, "compile", json!({ "book": dir })),
+        );
+        let mut output = Vec::new();
+        let mut server = Server::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let responses = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(responses[0]["result"][0]["lint"], "duplicate-rule");
+        assert!(
+            responses[1]["result"]["grammar.md"]
+                .as_str()
+                .unwrap()
+                .contains("syntax-rule-a")
+        );
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[3]["error"]["code"], PARSE_ERROR);
+        assert_eq!(server.books.len(), 1);
+    }
+}
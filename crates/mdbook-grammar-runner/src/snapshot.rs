@@ -0,0 +1,220 @@
+use crate::{
+    Config, Diagnostic, Level, book::read_pages, code::page_rule_nodes,
+    lint::rule_name, search::definition_line, timings::Timings,
+};
+use ecow::{EcoString, eco_format};
+use mdbook::book::Book;
+use mdbook_grammar_syntax::signature;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+/// The version of the format of snapshots.
+const FORMAT: u64 = 1;
+
+/// A frozen grammar of a book, to check later builds against.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The version of the grammar, like `0.4`, if given.
+    pub version: Option<EcoString>,
+    /// The rules of the book, in the order of the chapters.
+    pub rules: Vec<SnapshotRule>,
+}
+
+/// A rule of a [`Snapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotRule {
+    /// The name of the rule.
+    pub name: EcoString,
+    /// The path of the chapter defining the rule.
+    pub chapter: EcoString,
+    /// The definition on a single line, without comments.
+    pub definition: String,
+    /// The signature of the rule, ignoring whitespace and comments.
+    pub signature: u64,
+}
+
+impl Snapshot {
+    /// Take a snapshot of the grammar of a book.
+    pub fn of(book: &Book, config: &Config) -> Self {
+        let pages = read_pages(book, config, &mut Timings::default());
+        let rules = pages
+            .iter()
+            .flat_map(|page| {
+                page_rule_nodes(page).map(|rule| SnapshotRule {
+                    name: rule_name(rule).clone(),
+                    chapter: page.href.clone(),
+                    definition: definition_line(rule),
+                    signature: signature(rule),
+                })
+            })
+            .collect();
+
+        Self {
+            version: None,
+            rules,
+        }
+    }
+
+    /// The snapshot as a JSON object.
+    pub fn to_json(&self) -> Value {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| {
+                json!({
+                    "name": rule.name.as_str(),
+                    "chapter": rule.chapter.as_str(),
+                    "definition": rule.definition,
+                    "signature": format!("{:016x}", rule.signature),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "format": FORMAT,
+            "generator": concat!("mdbook-grammar ", env!("CARGO_PKG_VERSION")),
+            "version": self.version.as_deref(),
+            "rules": rules,
+        })
+    }
+
+    /// Read a snapshot from a JSON object, or return `None` if it is not a
+    /// snapshot in a known format.
+    pub fn from_json(value: &Value) -> Option<Self> {
+        if value.get("format")?.as_u64()? != FORMAT {
+            return None;
+        }
+
+        let rules = value
+            .get("rules")?
+            .as_array()?
+            .iter()
+            .map(|rule| {
+                let field = |key| rule.get(key)?.as_str();
+                Some(SnapshotRule {
+                    name: field("name")?.into(),
+                    chapter: field("chapter")?.into(),
+                    definition: field("definition")?.into(),
+                    signature: u64::from_str_radix(field("signature")?, 16)
+                        .ok()?,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            version: value
+                .get("version")
+                .and_then(Value::as_str)
+                .map(Into::into),
+            rules,
+        })
+    }
+
+    /// Check that the grammar of a book still matches the snapshot, returning
+    /// an error for each rule added, removed or changed since.
+    ///
+    /// Moving a rule to another chapter does not change the grammar.
+    pub fn verify(&self, current: &Snapshot) -> Vec<Diagnostic> {
+        let (old, new) = (self.by_name(), current.by_name());
+
+        let error = |rule: &SnapshotRule, message| Diagnostic {
+            level: Level::Deny,
+            lint: None,
+            message,
+            path: rule.chapter.clone(),
+            line: None,
+        };
+
+        let mut diagnostics = Vec::new();
+        for (name, rules) in &old {
+            match new.get(name) {
+                | None => diagnostics.push(error(
+                    rules[0],
+                    eco_format!("rule `{name}` was removed since the snapshot"),
+                )),
+                | Some(current) => {
+                    let signatures = |rules: &[&SnapshotRule]| {
+                        let mut signatures = rules
+                            .iter()
+                            .map(|r| r.signature)
+                            .collect::<Vec<_>>();
+                        signatures.sort_unstable();
+                        signatures
+                    };
+                    if signatures(rules) != signatures(current) {
+                        diagnostics.push(error(
+                            current[0],
+                            eco_format!(
+                                "rule `{name}` changed since the snapshot"
+                            ),
+                        ));
+                    }
+                },
+            }
+        }
+        for (name, rules) in &new {
+            if !old.contains_key(name) {
+                diagnostics.push(error(
+                    rules[0],
+                    eco_format!("rule `{name}` was added since the snapshot"),
+                ));
+            }
+        }
+
+        diagnostics
+    }
+
+    /// The rules of the snapshot, grouped by name.
+    fn by_name(&self) -> BTreeMap<&EcoString, Vec<&SnapshotRule>> {
+        let mut rules: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for rule in &self.rules {
+            rules.entry(&rule.name).or_default().push(rule);
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mdbook::{BookItem, book::Chapter};
+
+    fn snapshot(content: &str) -> Snapshot {
+        let mut book = Book::new();
+        book.push_item(BookItem::Chapter(Chapter::new(
+            "Grammar",
+            content.into(),
+            "grammar.md",
+            Vec::new(),
+        )));
+        Snapshot::of(&book, &Config::default())
+    }
+
+    #[test]
+    fn test_snapshot_json() {
+        let mut old =
+            snapshot("```syntax\na: b // comment\n  | c;\n_b: ;\n```\n");
+        old.version = Some("0.4".into());
+        assert_eq!(old.rules[0].definition, "b | c");
+        assert_eq!(Snapshot::from_json(&old.to_json()), Some(old));
+        assert_eq!(Snapshot::from_json(&json!({ "format": 0 })), None);
+    }
+
+    #[test]
+    fn test_snapshot_verify() {
+        let old = snapshot("```syntax\na: b;\nb: ;\nc: ;\n```\n");
+        let new = snapshot("```syntax\na: b;\nb: \"x\";\nd: ;\n```\n");
+        assert_eq!(old.verify(&snapshot("```syntax\na:b;b: ;c: ;\n```\n")), []);
+
+        let messages = old
+            .verify(&new)
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>();
+        assert_eq!(messages, [
+            "rule `b` changed since the snapshot",
+            "rule `c` was removed since the snapshot",
+            "rule `d` was added since the snapshot",
+        ]);
+    }
+}
@@ -0,0 +1,117 @@
+/// The colors of rendered grammars.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Palette {
+    /// The default colors.
+    #[default]
+    Default,
+    /// Colors told apart with the common forms of color blindness.
+    ColorBlind,
+    /// Colors with a high contrast against the background.
+    HighContrast,
+}
+
+impl Palette {
+    /// Find a palette by its name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            | "default" => Some(Palette::Default),
+            | "color-blind" => Some(Palette::ColorBlind),
+            | "high-contrast" => Some(Palette::HighContrast),
+            | _ => None,
+        }
+    }
+
+    /// The values of the color variables used by the stylesheet.
+    fn colors(self) -> &'static str {
+        match self {
+            | Palette::Default => {
+                "--syntax-comment: #6a737d; --syntax-string: #032f62; \
+                 --syntax-integer: #005cc5; --syntax-keyword: #d73a49; \
+                 --syntax-operator: #d73a49; --syntax-action: #6f42c1; \
+                 --syntax-identifier: inherit; --syntax-warning: #b08800; \
+                 --syntax-error: #cb2431;"
+            },
+            // The Okabe-Ito palette, avoiding red against green.
+            | Palette::ColorBlind => {
+                "--syntax-comment: #767676; --syntax-string: #0072b2; \
+                 --syntax-integer: #009e73; --syntax-keyword: #cc79a7; \
+                 --syntax-operator: #d55e00; --syntax-action: #56b4e9; \
+                 --syntax-identifier: inherit; --syntax-warning: #e69f00; \
+                 --syntax-error: #d55e00;"
+            },
+            | Palette::HighContrast => {
+                "--syntax-comment: #404040; --syntax-string: #00008b; \
+                 --syntax-integer: #00008b; --syntax-keyword: #000000; \
+                 --syntax-operator: #000000; --syntax-action: #4b0082; \
+                 --syntax-identifier: #000000; --syntax-warning: #8b4500; \
+                 --syntax-error: #b00000;"
+            },
+        }
+    }
+}
+
+/// The rules of the stylesheet, given the color variables.
+///
+/// Errors, warnings and the kinds of tokens are also told apart by style, so
+/// that no distinction relies on color alone.
+const RULES: &str = "\
+pre > code.syntax { display: block; overflow-x: auto; padding: 1em; }
+.syntax-comment { color: var(--syntax-comment); font-style: italic; }
+.syntax-string, .syntax-range, .syntax-class { color: var(--syntax-string); }
+.syntax-integer { color: var(--syntax-integer); }
+.syntax-meta, .syntax-keyword, .syntax-annotation { color: \
+                     var(--syntax-keyword); font-weight: bold; }
+.syntax-operator, .syntax-repetition { color: var(--syntax-operator); }
+.syntax-action, .syntax-variable { color: var(--syntax-action); }
+.syntax-function { color: var(--syntax-action); font-weight: bold; }
+.syntax-identifier { color: var(--syntax-identifier); }
+.syntax-token { color: var(--syntax-string); }
+.syntax-fragment { font-style: italic; }
+.syntax-param { color: var(--syntax-identifier); font-style: italic; }
+.syntax-modifier { color: var(--syntax-keyword); font-style: italic; }
+.syntax-empty { color: var(--syntax-keyword); font-style: italic; }
+.syntax-pragma { display: inline-block; padding: 0 0.5em; border-left: 3px \
+                     solid var(--syntax-keyword); background: color-mix(in \
+                     srgb, var(--syntax-keyword) 10%, transparent); }
+.syntax-link { text-decoration: none; }
+.syntax-link:hover { text-decoration: underline; }
+.syntax-unresolved { text-decoration: underline dashed var(--syntax-warning); }
+.syntax-error { font-weight: bold; text-decoration: underline wavy \
+                     var(--syntax-error); }
+.syntax-badge { font-size: 0.75em; vertical-align: super; }
+.syntax-start { background: rgba(127, 127, 127, 0.15); border-radius: 0.25em; }
+.syntax-constraint { font-size: 0.75em; font-style: italic; }
+.syntax-section { color: inherit; font-weight: bold; text-decoration: none; }
+.syntax-label { color: var(--syntax-comment); text-decoration: none; }
+.syntax-layout { border: 1px solid; border-radius: 0.25em; padding: 0 0.2em; }
+.syntax-complex { opacity: 0.5; cursor: help; }
+.syntax-deprecated-notice, .syntax-deprecated-use { color: \
+                     var(--syntax-warning); cursor: help; }
+.syntax-since { border: 1px solid; border-radius: 0.25em; padding: 0 0.2em; \
+                     text-decoration: none; opacity: 0.75; }
+.syntax-railroad { overflow-x: auto; margin: 0.5em 0 1em; }
+.syntax-railroad a { color: inherit; }
+.syntax-source { font-size: 0.75em; text-decoration: none; opacity: 0.5; }
+.syntax-used-by { display: block; font-size: 0.75em; opacity: 0.75; \
+                     user-select: none; }
+";
+
+/// The stylesheet of rendered grammars with the given palette.
+pub fn stylesheet(palette: Palette) -> String {
+    format!(":root {{ {} }}\n{RULES}", palette.colors())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stylesheet() {
+        for name in ["default", "color-blind", "high-contrast"] {
+            let css = stylesheet(Palette::from_name(name).unwrap());
+            // A blank line would end the HTML block in the chapter.
+            assert!(!css.contains("\n\n"));
+            assert!(css.contains("--syntax-error:"));
+        }
+    }
+}
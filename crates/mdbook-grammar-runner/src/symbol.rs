@@ -0,0 +1,53 @@
+use ecow::EcoString;
+use std::collections::HashMap;
+
+/// A rule name interned in a [`SymbolTable`], cheap to copy, hash and compare.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+/// The names of the rules of a book, each stored once.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    symbols: HashMap<EcoString, Symbol>,
+    names: Vec<EcoString>,
+}
+
+impl SymbolTable {
+    /// The symbol of a name, added to the table if it is new.
+    pub fn intern(&mut self, name: &EcoString) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.clone());
+        self.symbols.insert(name.clone(), symbol);
+        symbol
+    }
+
+    /// The symbol of a name, if it was interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The name of a symbol.
+    pub fn name(&self, symbol: Symbol) -> &EcoString {
+        &self.names[symbol.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut table = SymbolTable::default();
+        let a = table.intern(&"a".into());
+        let b = table.intern(&"b".into());
+        assert_ne!(a, b);
+        assert_eq!(table.intern(&"a".into()), a);
+        assert_eq!(table.get("b"), Some(b));
+        assert_eq!(table.get("c"), None);
+        assert_eq!(table.name(a), "a");
+    }
+}
@@ -0,0 +1,116 @@
+use ecow::EcoString;
+use std::{
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+
+/// The time spent in each phase of a run, reported with `--timings`.
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    /// The phases of each chapter.
+    pub chapters: Vec<ChapterTimings>,
+    /// Running the lints on the whole book.
+    pub linting: Duration,
+    /// Collecting the rules of the whole book.
+    pub registry: Duration,
+}
+
+/// The time spent on a chapter.
+#[derive(Clone, Debug, Default)]
+pub struct ChapterTimings {
+    /// The path of the chapter.
+    pub path: EcoString,
+    /// Finding the grammar blocks in the content.
+    pub scanning: Duration,
+    /// Parsing the grammar blocks.
+    pub parsing: Duration,
+    /// Rendering the content, including cache lookups.
+    pub rendering: Duration,
+}
+
+/// Measure the time taken by a function, adding it to a total.
+pub fn timed<T>(total: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let output = f();
+    *total += start.elapsed();
+    output
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .chapters
+            .iter()
+            .map(|chapter| chapter.path.chars().count())
+            .chain(["total".len()])
+            .max()
+            .unwrap_or_default();
+
+        let row = |f: &mut Formatter<'_>, name: &str, times: [Duration; 3]| {
+            write!(f, "{name:width$}")?;
+            for time in times {
+                write!(f, " {:>10.3}ms", time.as_secs_f64() * 1000.0)?;
+            }
+            writeln!(f)
+        };
+
+        writeln!(
+            f,
+            "{:width$} {:>12} {:>12} {:>12}",
+            "chapter", "scanning", "parsing", "rendering"
+        )?;
+
+        let mut total = [Duration::ZERO; 3];
+        for chapter in &self.chapters {
+            let times = [chapter.scanning, chapter.parsing, chapter.rendering];
+            for (total, time) in total.iter_mut().zip(times) {
+                *total += time;
+            }
+            row(f, &chapter.path, times)?;
+        }
+        row(f, "total", total)?;
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "linting  {:>10.3}ms",
+            self.linting.as_secs_f64() * 1000.0
+        )?;
+        write!(
+            f,
+            "registry {:>10.3}ms",
+            self.registry.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let timings = Timings {
+            chapters: vec![ChapterTimings {
+                path: "grammar.md".into(),
+                scanning: Duration::from_micros(1500),
+                parsing: Duration::from_millis(2),
+                rendering: Duration::from_millis(3),
+            }],
+            linting: Duration::from_millis(4),
+            registry: Duration::from_millis(5),
+        };
+
+        let text = timings.to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[1],
+            "grammar.md      1.500ms      2.000ms      3.000ms"
+        );
+        assert_eq!(
+            lines[2],
+            "total           1.500ms      2.000ms      3.000ms"
+        );
+        assert_eq!(lines[5], "registry      5.000ms");
+    }
+}
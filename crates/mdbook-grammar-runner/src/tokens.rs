@@ -0,0 +1,181 @@
+use crate::book::{Item, Page};
+use ecow::EcoString;
+use html_escape::encode_text;
+use mdbook_grammar_syntax::{SyntaxKind, SyntaxNode, unescape};
+use std::collections::BTreeSet;
+
+/// The directive replaced by the table of the tokens of the book.
+pub const TOKENS: &str = "{{#grammar-tokens}}";
+
+/// The metas standing for layout tokens, with their descriptions.
+const LAYOUT: &[(&str, &str)] = &[
+    ("<indent>", "the indentation increases"),
+    ("<dedent>", "the indentation decreases"),
+    ("<newline>", "a line ends"),
+    ("<eof>", "the input ends"),
+];
+
+/// The description of a layout meta, like `<indent>`, or `None` for other
+/// metas.
+pub fn describe_layout(meta: &str) -> Option<&'static str> {
+    LAYOUT
+        .iter()
+        .find(|&&(name, _)| name == meta)
+        .map(|&(_, description)| description)
+}
+
+/// Find the terminals used in the grammar blocks of the pages, as written:
+/// string literals like `"if"` and character ranges like `"a".."z"`, and
+/// layout metas like `<indent>` if they are recognized.
+pub fn find_terminals(pages: &[Page], layout: bool) -> BTreeSet<EcoString> {
+    let mut terminals = BTreeSet::new();
+
+    for page in pages {
+        for item in &page.items {
+            let Item::Code(block) = item else { continue };
+            collect_terminals(&block.code, layout, &mut terminals);
+        }
+    }
+
+    terminals
+}
+
+fn collect_terminals(
+    node: &SyntaxNode,
+    layout: bool,
+    terminals: &mut BTreeSet<EcoString>,
+) {
+    match node.kind() {
+        | SyntaxKind::String
+        | SyntaxKind::RawString
+        | SyntaxKind::CharClass => {
+            terminals.insert(node.text().clone());
+        },
+        | SyntaxKind::Insensitive if !node.erroneous() => {
+            let text = node.children().map(|n| n.text().as_str());
+            terminals.insert(text.collect::<String>().into());
+        },
+        | SyntaxKind::Meta => {
+            if layout && describe_layout(node.text()).is_some() {
+                terminals.insert(node.text().clone());
+            }
+        },
+        | SyntaxKind::Range if !node.erroneous() => {
+            let text = node
+                .children()
+                .filter(|n| !n.kind().is_trivia())
+                .map(|n| n.text().as_str())
+                .collect::<String>();
+            terminals.insert(text.into());
+        },
+        | _ => {
+            for child in node.children() {
+                collect_terminals(child, layout, terminals);
+            }
+        },
+    }
+}
+
+/// Render the table of the terminals, grouped into keywords, punctuation,
+/// other literals and layout tokens.
+pub fn token_table(terminals: &BTreeSet<EcoString>) -> String {
+    let mut groups: [(&str, Vec<&EcoString>); 4] = [
+        ("Keywords", Vec::new()),
+        ("Punctuation", Vec::new()),
+        ("Literals", Vec::new()),
+        ("Layout", Vec::new()),
+    ];
+
+    for terminal in terminals {
+        // Case-insensitive keywords are still keywords.
+        let literal = terminal.strip_prefix('i').unwrap_or(terminal);
+        let group = match unescape(literal) {
+            | _ if describe_layout(terminal).is_some() => 3,
+            | Some(text) if is_keyword(&text) => 0,
+            | Some(text)
+                if !text.is_empty()
+                    && text.chars().all(|c| c.is_ascii_punctuation()) =>
+            {
+                1
+            },
+            | _ => 2,
+        };
+        groups[group].1.push(terminal);
+    }
+
+    let mut out = String::from(
+        "<table class=\"syntax-tokens\">\n<thead><tr><th>Kind</th><th>Tokens</\
+         th></tr></thead>\n<tbody>\n",
+    );
+    for (kind, terminals) in groups {
+        if terminals.is_empty() {
+            continue;
+        }
+        let tokens = terminals
+            .iter()
+            .map(|terminal| {
+                let cls = match describe_layout(terminal) {
+                    | Some(_) => "meta",
+                    | None => "string",
+                };
+                format!(
+                    "<code class=\"syntax-{cls}\">{}</code>",
+                    encode_text(terminal)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        out += &format!("<tr><td>{kind}</td><td>{tokens}</td></tr>\n");
+    }
+    out += "</tbody>\n</table>";
+
+    out
+}
+
+/// Whether a terminal is a keyword, like `if`.
+fn is_keyword(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::Block;
+    use mdbook_grammar_syntax::parse;
+
+    #[test]
+    fn test_token_table() {
+        let pages = vec![Page {
+            href: "page.md".into(),
+            name: "Page".into(),
+            items: vec![Item::Code(Block {
+                code: parse(
+                    r#"a: "if" "(" a ")" | "a".."z" | "0x" | i"if" | <indent> <x>;"#,
+                ),
+                options: Vec::new(),
+                line: 1,
+            })],
+        }];
+
+        assert_eq!(find_terminals(&pages, false).len(), 6);
+        let terminals = find_terminals(&pages, true);
+        assert_eq!(terminals.len(), 7);
+
+        let table = token_table(&terminals);
+        let rows = table.lines().filter(|line| line.starts_with("<tr><td>"));
+        assert_eq!(rows.collect::<Vec<_>>(), [
+            "<tr><td>Keywords</td><td><code \
+             class=\"syntax-string\">\"if\"</code> <code \
+             class=\"syntax-string\">i\"if\"</code></td></tr>",
+            "<tr><td>Punctuation</td><td><code \
+             class=\"syntax-string\">\"(\"</code> <code \
+             class=\"syntax-string\">\")\"</code></td></tr>",
+            "<tr><td>Literals</td><td><code \
+             class=\"syntax-string\">\"0x\"</code> <code \
+             class=\"syntax-string\">\"a\"..\"z\"</code></td></tr>",
+            "<tr><td>Layout</td><td><code \
+             class=\"syntax-meta\">&lt;indent&gt;</code></td></tr>",
+        ]);
+    }
+}
@@ -0,0 +1,27 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use mdbook_grammar_syntax::parse;
+use std::hint::black_box;
+
+/// A grammar of a thousand rules, in the style of a language reference.
+fn grammar() -> String {
+    (0..500)
+        .map(|i| {
+            format!(
+                "/// The statement {i}.\nstatement{i}: list{i}(expr) [\";\"] \
+                 | \"a\"..\"z\"+ (\"(\" statement{} \")\")*;\nlist{i}(item): \
+                 item (\",\" item)* | ;\n",
+                i + 1
+            )
+        })
+        .collect()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let grammar = grammar();
+    // Dropping the tree is measured too, since it frees what parsing
+    // allocated.
+    c.bench_function("parse", |b| b.iter(|| parse(black_box(&grammar))));
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,385 @@
+use crate::{SyntaxKind, SyntaxNode};
+use unscanny::Scanner;
+
+/// Parse the code of a condition after its `if`, like ` a && !b`, starting at
+/// the given offset of the block, with parts nested at most `max_depth` deep.
+///
+/// A condition is made of names, numbers and strings, compared with `==`,
+/// `!=`, `<`, `<=`, `>` and `>=`, and combined with `&&`, `||`, `!`, their
+/// spellings `and`, `or` and `not`, and parentheses. Return `None` if the code
+/// is not such a condition, to keep it as an opaque operation.
+pub(crate) fn condition(
+    code: &str,
+    offset: usize,
+    max_depth: usize,
+) -> Option<SyntaxNode> {
+    let mut p = ActionParser::new(code, offset, false, max_depth);
+    p.trivia();
+    p.or()?;
+    p.trivia();
+    p.s.done()
+        .then(|| SyntaxNode::inner(SyntaxKind::Condition, p.nodes))
+}
+
+/// Parse the code of an action after its `->`, like ` push(item, 1)`,
+/// starting at the given offset of the block, with parts nested at most
+/// `max_depth` deep.
+///
+/// The function may be a path, like `ast::node`, and its arguments are
+/// conditions or calls themselves. Return `None` if the code is not a single
+/// call, to keep it as an opaque operation.
+pub(crate) fn call(
+    code: &str,
+    offset: usize,
+    max_depth: usize,
+) -> Option<SyntaxNode> {
+    let mut p = ActionParser::new(code, offset, true, max_depth);
+    p.trivia();
+    p.primary()?;
+    let call = p.nodes.pop().filter(|n| n.kind() == SyntaxKind::Call)?;
+    p.nodes.extend(call.children().cloned());
+    p.trivia();
+    p.s.done()
+        .then(|| SyntaxNode::inner(SyntaxKind::Call, p.nodes))
+}
+
+/// Parses the code of an action into a flat list of nodes, wrapping them as
+/// it goes.
+struct ActionParser<'s> {
+    s: Scanner<'s>,
+    offset: usize,
+    nodes: Vec<SyntaxNode>,
+    /// Whether names followed by parentheses are calls.
+    calls: bool,
+    /// How many parts are being parsed inside each other.
+    depth: usize,
+    /// How deep parts may be nested before giving up.
+    max_depth: usize,
+}
+
+impl<'s> ActionParser<'s> {
+    fn new(
+        code: &'s str,
+        offset: usize,
+        calls: bool,
+        max_depth: usize,
+    ) -> Self {
+        Self {
+            s: Scanner::new(code),
+            offset,
+            nodes: Vec::new(),
+            calls,
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    /// Parse conditions joined with `||`.
+    fn or(&mut self) -> Option<()> {
+        self.joined(&["||", "or"], Self::and)
+    }
+
+    /// Parse conditions joined with `&&`.
+    fn and(&mut self) -> Option<()> {
+        self.joined(&["&&", "and"], Self::unary)
+    }
+
+    /// Parse operands joined with any of the operators, wrapping them into a
+    /// logical expression if there are several.
+    fn joined(
+        &mut self,
+        operators: &[&str],
+        operand: fn(&mut Self) -> Option<()>,
+    ) -> Option<()> {
+        let start = self.nodes.len();
+        operand(self)?;
+        let mut joined = false;
+        loop {
+            let end = self.nodes.len();
+            self.trivia();
+            if !operators.iter().any(|&op| self.operator(op)) {
+                self.restore(end);
+                break;
+            }
+            self.trivia();
+            operand(self)?;
+            joined = true;
+        }
+        if joined {
+            self.wrap(start, SyntaxKind::Logical);
+        }
+        Some(())
+    }
+
+    /// Parse a negated condition, or a comparison.
+    fn unary(&mut self) -> Option<()> {
+        let start = self.nodes.len();
+        if self.operator("!") || self.operator("not") {
+            self.trivia();
+            self.nested(Self::unary)?;
+            self.wrap(start, SyntaxKind::Negation);
+            return Some(());
+        }
+
+        self.primary()?;
+        let end = self.nodes.len();
+        self.trivia();
+        if !["==", "!=", "<=", ">=", "<", ">"]
+            .iter()
+            .any(|&op| self.operator(op))
+        {
+            self.restore(end);
+            return Some(());
+        }
+        self.trivia();
+        self.primary()?;
+        self.wrap(start, SyntaxKind::Comparison);
+        Some(())
+    }
+
+    /// Parse a name, a call, a number, a string or a parenthesized condition.
+    fn primary(&mut self) -> Option<()> {
+        let start = self.s.cursor();
+        let kind = match self.s.eat()? {
+            | '(' => {
+                let start = self.nodes.len();
+                self.leaf(SyntaxKind::LeftParen, self.s.cursor() - 1);
+                self.trivia();
+                self.nested(Self::or)?;
+                self.trivia();
+                self.s.eat_if(')').then_some(())?;
+                self.leaf(SyntaxKind::RightParen, self.s.cursor() - 1);
+                self.wrap(start, SyntaxKind::Condition);
+                return Some(());
+            },
+            | c if c.is_ascii_digit() => {
+                self.s.eat_while(|c: char| c.is_ascii_digit());
+                SyntaxKind::Integer
+            },
+            | '"' => {
+                while !self.s.eat_if('"') {
+                    if self.s.eat()? == '\\' {
+                        self.s.eat()?;
+                    }
+                }
+                SyntaxKind::String
+            },
+            | c if c.is_alphabetic() || c == '_' => {
+                self.s.eat_while(|c: char| {
+                    c.is_alphanumeric() || c == '_' || c == '-'
+                });
+                // A trailing `-` is not part of the name.
+                while self.s.before().ends_with('-') {
+                    self.s.uneat();
+                }
+                if matches!(self.s.from(start), "and" | "or" | "not") {
+                    return None;
+                }
+                if self.calls {
+                    return self.function(start);
+                }
+                SyntaxKind::Variable
+            },
+            | _ => return None,
+        };
+        self.leaf(kind, start);
+        Some(())
+    }
+
+    /// Parse the rest of a path after its first name, and the arguments of
+    /// the call if it is one.
+    fn function(&mut self, start: usize) -> Option<()> {
+        loop {
+            let end = self.s.cursor();
+            if !(self.s.eat_if("::") || self.s.eat_if('.'))
+                || !self.s.eat_if(|c: char| c.is_alphabetic() || c == '_')
+            {
+                self.s.jump(end);
+                break;
+            }
+            self.s.eat_while(|c: char| c.is_alphanumeric() || c == '_');
+        }
+
+        let path = self.s.from(start);
+        if !self.s.at('(') {
+            // Only the functions of calls can be paths.
+            let name = !path.contains(['.', ':']);
+            self.leaf(SyntaxKind::Variable, start);
+            return name.then_some(());
+        }
+
+        let call = self.nodes.len();
+        self.leaf(SyntaxKind::Function, start);
+        self.s.eat();
+        self.leaf(SyntaxKind::LeftParen, self.s.cursor() - 1);
+        self.trivia();
+        if !self.s.at(')') {
+            loop {
+                let argument = self.nodes.len();
+                self.nested(Self::or)?;
+                self.wrap(argument, SyntaxKind::Argument);
+                self.trivia();
+                if !self.s.eat_if(',') {
+                    break;
+                }
+                self.leaf(SyntaxKind::Comma, self.s.cursor() - 1);
+                self.trivia();
+            }
+        }
+        self.s.eat_if(')').then_some(())?;
+        self.leaf(SyntaxKind::RightParen, self.s.cursor() - 1);
+        self.wrap(call, SyntaxKind::Call);
+        Some(())
+    }
+
+    /// Parse a part inside another, giving up if they are nested too deeply.
+    fn nested(&mut self, part: fn(&mut Self) -> Option<()>) -> Option<()> {
+        if self.depth >= self.max_depth {
+            return None;
+        }
+        self.depth += 1;
+        let result = part(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Eat an operator, like `&&` or `and`, as a leaf.
+    fn operator(&mut self, op: &str) -> bool {
+        let start = self.s.cursor();
+        if !self.s.eat_if(op) {
+            return false;
+        }
+        // Words must not be the start of a longer name.
+        if op.starts_with(char::is_alphabetic)
+            && self
+                .s
+                .at(|c: char| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            self.s.jump(start);
+            return false;
+        }
+        self.leaf(SyntaxKind::Operator, start);
+        true
+    }
+
+    /// Eat whitespace as a leaf.
+    fn trivia(&mut self) {
+        let start = self.s.cursor();
+        if !self.s.eat_whitespace().is_empty() {
+            self.leaf(SyntaxKind::Whitespace, start);
+        }
+    }
+
+    /// Push a leaf from the given position to the cursor.
+    fn leaf(&mut self, kind: SyntaxKind, start: usize) {
+        let span = self.offset + start..self.offset + self.s.cursor();
+        self.nodes
+            .push(SyntaxNode::leaf(kind, self.s.from(start), span));
+    }
+
+    /// Forget the nodes after the given length, and move back before them.
+    fn restore(&mut self, len: usize) {
+        if let Some(node) = self.nodes.get(len) {
+            self.s.jump(node.span().start - self.offset);
+        }
+        self.nodes.truncate(len);
+    }
+
+    /// Wrap the nodes after the given length into a node of the kind.
+    fn wrap(&mut self, start: usize, kind: SyntaxKind) {
+        let children = self.nodes.split_off(start);
+        self.nodes.push(SyntaxNode::inner(kind, children));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_DEPTH;
+
+    /// The kinds of a node as a nested list, like `Logical(Variable)`.
+    fn write(node: &SyntaxNode) -> String {
+        let kind = format!("{:?}", node.kind());
+        if node.children().len() == 0 {
+            return kind;
+        }
+        let children = node
+            .children()
+            .filter(|n| n.kind() != SyntaxKind::Whitespace)
+            .map(write)
+            .collect::<Vec<_>>();
+        format!("{kind}({})", children.join(" "))
+    }
+
+    fn shape(code: &str) -> Option<String> {
+        Some(write(&condition(code, 0, MAX_DEPTH)?))
+    }
+
+    #[test]
+    fn test_condition() {
+        assert_eq!(shape(" strict ").unwrap(), "Condition(Variable)");
+        assert_eq!(
+            shape(" a && !b || c").unwrap(),
+            "Condition(Logical(Logical(Variable Operator Negation(Operator \
+             Variable)) Operator Variable))",
+        );
+        assert_eq!(
+            shape("not (depth >= 2 and in-template)").unwrap(),
+            "Condition(Negation(Operator Condition(LeftParen \
+             Logical(Comparison(Variable Operator Integer) Operator Variable) \
+             RightParen)))",
+        );
+        assert_eq!(
+            shape("mode == \"a\\\"b\"").unwrap(),
+            "Condition(Comparison(Variable Operator String))",
+        );
+        assert_eq!(shape("android").unwrap(), "Condition(Variable)");
+    }
+
+    #[test]
+    fn test_condition_opaque() {
+        for code in ["", "a b", "a.b()", "(a", "a &&", "self.mode == Mode::X"] {
+            assert_eq!(shape(code), None, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_call() {
+        let tree = |code| Some(write(&call(code, 0, MAX_DEPTH)?));
+        assert_eq!(
+            tree(" push(Ident) ").unwrap(),
+            "Call(Function LeftParen Argument(Variable) RightParen)",
+        );
+        assert_eq!(
+            tree("ast::node(\"add\", wrap(a), depth > 1)").unwrap(),
+            "Call(Function LeftParen Argument(String) Comma \
+             Argument(Call(Function LeftParen Argument(Variable) RightParen)) \
+             Comma Argument(Comparison(Variable Operator Integer)) RightParen)",
+        );
+        assert_eq!(tree("f()").unwrap(), "Call(Function LeftParen RightParen)");
+        for code in ["", "push", "a.b", "f(a.b)", "f(x) g", "f(x,)", "{ f(x) }"]
+        {
+            assert_eq!(tree(code), None, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert!(condition(" (a && (b || c))", 0, 2).is_some());
+        assert!(condition(" (a && (b || c))", 0, 1).is_none());
+        assert!(call(" f(g(h(x)))", 0, 3).is_some());
+        assert!(call(" f(g(h(x)))", 0, 2).is_none());
+    }
+
+    #[test]
+    fn test_condition_spans() {
+        let node = condition(" a || b", 10, MAX_DEPTH).unwrap();
+        assert_eq!(*node.span(), 10..17);
+        let text = node
+            .children()
+            .flat_map(|n| std::iter::once(n).chain(n.children()))
+            .map(|n| n.text().as_str())
+            .collect::<String>();
+        assert_eq!(text, " a || b");
+    }
+}
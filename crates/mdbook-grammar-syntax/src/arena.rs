@@ -0,0 +1,335 @@
+use crate::{
+    SyntaxKind, SyntaxNode,
+    node::{InnerNode, Repr},
+};
+use bumpalo::Bump;
+use std::{
+    alloc::Layout,
+    ops::Range,
+    ptr::{self, NonNull},
+    sync::{Arc, Mutex},
+};
+
+/// The memory of the inner nodes of a parsed tree, with the `arena` feature.
+///
+/// The parser allocates each inner node and its children in the arena of the
+/// tree, instead of a `Vec` per node. The nodes held outside of
+/// the arena count references to it, like an `Arc`, while the children stored
+/// in it do not, since they would keep it alive forever: a child counts one
+/// once cloned out of the arena. The nodes of the parser do not count either,
+/// since the parser holds the arena.
+///
+/// The arena is shared by the nodes of the tree, which may be sent to other
+/// threads, so its allocator and list of children are behind locks.
+#[derive(Default)]
+pub(crate) struct Arena {
+    bump: Mutex<Bump>,
+    /// The children stored in the arena, dropped with it since the bump
+    /// allocator does not run destructors.
+    children: Mutex<Vec<Children>>,
+}
+
+/// The children of an inner node, owned by the arena like a `Box<[_]>`.
+struct Children(NonNull<[SyntaxNode]>);
+
+// SAFETY: the children are owned by the arena and only reached through it,
+// like the contents of a `Box<[SyntaxNode]>`, which is `Send`.
+unsafe impl Send for Children {}
+
+impl Arena {
+    /// Move the nodes into the root of the tree, which outlives the parser.
+    pub(crate) fn root(
+        self: &Arc<Self>,
+        kind: SyntaxKind,
+        mut nodes: Vec<SyntaxNode>,
+    ) -> SyntaxNode {
+        let mut root = self.inner(kind, &mut nodes, 0);
+        if let Repr::Arena(node) = &mut root.0 {
+            node.count();
+        }
+        root
+    }
+
+    /// Move the nodes from the index on into a new inner node in the arena.
+    ///
+    /// The node does not count a reference to the arena, so the caller must
+    /// hold the arena while it holds the node.
+    pub(crate) fn inner(
+        self: &Arc<Self>,
+        kind: SyntaxKind,
+        nodes: &mut Vec<SyntaxNode>,
+        from: usize,
+    ) -> SyntaxNode {
+        debug_assert!(!kind.is_error());
+
+        for node in &mut nodes[from..] {
+            self.adopt(node);
+        }
+        let len = nodes.len() - from;
+        let layout = Layout::array::<SyntaxNode>(len).unwrap();
+        let children = self.bump().alloc_layout(layout).cast::<SyntaxNode>();
+        // SAFETY: the nodes are moved out of the vector, which forgets them.
+        unsafe {
+            ptr::copy_nonoverlapping(
+                nodes.as_ptr().add(from),
+                children.as_ptr(),
+                len,
+            );
+            nodes.set_len(from);
+        }
+        let children = NonNull::slice_from_raw_parts(children, len);
+        if len > 0 {
+            let mut list =
+                self.children.lock().unwrap_or_else(|e| e.into_inner());
+            list.push(Children(children));
+        }
+
+        // SAFETY: the children were just initialized.
+        let slice = unsafe { children.as_ref() };
+        let erroneous = slice.iter().any(SyntaxNode::erroneous);
+        let start = slice.first().map_or(0, |child| child.span().start);
+        let end = slice.last().map_or(0, |child| child.span().end);
+        let inner = NonNull::from(self.bump().alloc(ArenaInner {
+            kind,
+            span: start..end,
+            erroneous,
+            children,
+        }));
+        SyntaxNode(Repr::Arena(ArenaNode {
+            arena: Arc::as_ptr(self),
+            inner,
+            counted: false,
+        }))
+    }
+
+    /// Lock the allocator. The memory it allocates stays in place until the
+    /// arena is dropped, so it outlives the lock.
+    fn bump(&self) -> std::sync::MutexGuard<'_, Bump> {
+        self.bump.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Prepare a node to be stored in the arena as a child.
+    ///
+    /// Nodes of this arena stop counting references to it, and inner nodes on
+    /// the heap, which may hold counted nodes of this arena, move into it.
+    fn adopt(self: &Arc<Self>, node: &mut SyntaxNode) {
+        match &mut node.0 {
+            | Repr::Arena(node) if ptr::eq(node.arena, Arc::as_ptr(self)) => {
+                node.uncount();
+            },
+            | Repr::Inner(inner) => {
+                let kind = inner.kind;
+                let mut children = std::mem::take(&mut inner.children);
+                *node = self.inner(kind, &mut children, 0);
+            },
+            | _ => {},
+        }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let children = self.children.get_mut();
+        for Children(children) in
+            children.unwrap_or_else(|err| err.into_inner()).drain(..)
+        {
+            // SAFETY: the children were initialized when allocated, and are
+            // only dropped here, once. Those of this arena count no
+            // reference to it, so dropping them does not drop it again.
+            unsafe { ptr::drop_in_place(children.as_ptr()) };
+        }
+    }
+}
+
+/// An inner node allocated in an arena.
+pub(crate) struct ArenaNode {
+    /// The arena, from `Arc::as_ptr`.
+    arena: *const Arena,
+    inner: NonNull<ArenaInner>,
+    /// Whether the node holds a reference to the arena, unlike the children
+    /// stored in it.
+    counted: bool,
+}
+
+// SAFETY: a node shares the arena like an `Arc<Arena>`, and the arena is
+// `Send` and `Sync`. The node only reads the memory it points to in the arena,
+// which is not changed once allocated.
+unsafe impl Send for ArenaNode {}
+unsafe impl Sync for ArenaNode {}
+
+/// The data of an inner node in an arena.
+struct ArenaInner {
+    kind: SyntaxKind,
+    span: Range<usize>,
+    erroneous: bool,
+    children: NonNull<[SyntaxNode]>,
+}
+
+impl ArenaNode {
+    fn data(&self) -> &ArenaInner {
+        // SAFETY: the arena is alive while the node is, either counted by the
+        // node itself or storing it.
+        unsafe { self.inner.as_ref() }
+    }
+
+    pub(crate) fn kind(&self) -> SyntaxKind {
+        self.data().kind
+    }
+
+    pub(crate) fn span(&self) -> &Range<usize> {
+        &self.data().span
+    }
+
+    pub(crate) fn erroneous(&self) -> bool {
+        self.data().erroneous
+    }
+
+    pub(crate) fn children(&self) -> &[SyntaxNode] {
+        // SAFETY: as for the data, and the children are initialized.
+        unsafe { self.data().children.as_ref() }
+    }
+
+    /// Copy the node to the heap, sharing its children.
+    pub(crate) fn to_heap(&self) -> InnerNode {
+        InnerNode {
+            kind: self.kind(),
+            span: self.span().clone(),
+            erroneous: self.erroneous(),
+            children: self.children().to_vec(),
+        }
+    }
+
+    /// Count a reference to the arena, for the node to outlive the parser.
+    fn count(&mut self) {
+        if !self.counted {
+            self.counted = true;
+            // SAFETY: the parser holding the node holds the arena.
+            unsafe { Arc::increment_strong_count(self.arena) };
+        }
+    }
+
+    /// Stop counting a reference to the arena, once stored in it.
+    fn uncount(&mut self) {
+        if self.counted {
+            self.counted = false;
+            // SAFETY: the node held one of the references, and the arena
+            // storing the node holds another.
+            unsafe { Arc::decrement_strong_count(self.arena) };
+        }
+    }
+
+    /// Serialize the node like an inner node on the heap.
+    #[cfg(feature = "serde")]
+    pub(crate) fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("InnerNode", 4)?;
+        state.serialize_field("kind", &self.kind())?;
+        state.serialize_field("span", self.span())?;
+        state.serialize_field("erroneous", &self.erroneous())?;
+        state.serialize_field("children", self.children())?;
+        state.end()
+    }
+}
+
+impl Clone for ArenaNode {
+    /// Share the node, counting a reference to the arena even when cloning a
+    /// child stored in it.
+    fn clone(&self) -> Self {
+        // SAFETY: the arena is alive while this node is.
+        unsafe { Arc::increment_strong_count(self.arena) };
+        Self {
+            arena: self.arena,
+            inner: self.inner,
+            counted: true,
+        }
+    }
+}
+
+impl Drop for ArenaNode {
+    fn drop(&mut self) {
+        if self.counted {
+            // SAFETY: the node holds one of the references.
+            unsafe { Arc::decrement_strong_count(self.arena) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arena;
+    use crate::{SyntaxKind, SyntaxNode, parse};
+    use std::thread;
+
+    #[test]
+    fn test_arena_outlives_root() {
+        let root = parse("a: b (c | d);\ne: f;");
+        let rule = root.children().next().unwrap().clone();
+        drop(root);
+        assert_eq!(rule.kind(), SyntaxKind::Rule);
+        let expected = parse("a: b (c | d);");
+        assert_eq!(rule, *expected.children().next().unwrap());
+    }
+
+    #[test]
+    fn test_arena_change() {
+        let root = parse("a: b;");
+        let mut copy = root.clone();
+        copy.map_children(|rule| rule.convert_kind(SyntaxKind::Group));
+        assert_eq!(root.children().next().unwrap().kind(), SyntaxKind::Rule);
+        assert_eq!(copy.children().next().unwrap().kind(), SyntaxKind::Group);
+        assert_ne!(root, copy);
+        assert_eq!(root, parse("a: b;"));
+    }
+
+    #[test]
+    fn test_arena_threads() {
+        fn shared<T: Send + Sync>() {}
+        shared::<Arena>();
+        shared::<SyntaxNode>();
+
+        let code = "a: b (c | d);\ne: f [g];";
+        let root = parse(code);
+        let expected = root.to_string();
+        let children = root
+            .children()
+            .map(SyntaxNode::to_string)
+            .collect::<String>();
+
+        // Subtrees sent to other threads outlive the root.
+        let rules = root.children().cloned().collect::<Vec<_>>();
+        let handles = rules
+            .into_iter()
+            .map(|rule| {
+                thread::spawn(move || {
+                    let copy = rule.clone();
+                    drop(rule);
+                    copy.to_string()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // The tree is cloned and dropped from several threads at once.
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let copy = root.clone();
+                    let rule = copy.children().next().unwrap().clone();
+                    drop(copy);
+                    assert_eq!(rule.kind(), SyntaxKind::Rule);
+                    assert_eq!(parse(code).to_string(), expected);
+                });
+            }
+        });
+        drop(root);
+
+        let texts = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<String>();
+        assert_eq!(texts, children);
+    }
+}
@@ -0,0 +1,477 @@
+//! Typed views over the untyped syntax tree.
+//!
+//! Each wrapper checks the kind of a [`SyntaxNode`] once, then exposes its
+//! parts through accessors instead of matching on the kinds of its children.
+
+use crate::{Repeat, SyntaxKind, SyntaxNode, range_bounds};
+use ecow::EcoString;
+
+/// A typed view over a syntax node.
+pub trait AstNode<'a>: Sized {
+    /// Cast the node if it has the right kind.
+    fn from_untyped(node: &'a SyntaxNode) -> Option<Self>;
+
+    /// The underlying node.
+    fn to_untyped(self) -> &'a SyntaxNode;
+}
+
+macro_rules! node {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub struct $name<'a>(&'a SyntaxNode);
+
+        impl<'a> AstNode<'a> for $name<'a> {
+            fn from_untyped(node: &'a SyntaxNode) -> Option<Self> {
+                (node.kind() == SyntaxKind::$name).then_some(Self(node))
+            }
+
+            fn to_untyped(self) -> &'a SyntaxNode {
+                self.0
+            }
+        }
+    };
+}
+
+node! {
+    /// The whole grammar.
+    Root
+}
+
+impl<'a> Root<'a> {
+    /// The rules, in order.
+    pub fn rules(self) -> impl Iterator<Item = Rule<'a>> {
+        self.0.children().filter_map(Rule::from_untyped)
+    }
+
+    /// The pragmas, in order.
+    pub fn pragmas(self) -> impl Iterator<Item = Pragma<'a>> {
+        self.0.children().filter_map(Pragma::from_untyped)
+    }
+}
+
+node! {
+    /// A rule, like `list(item): item ("," item)*;`.
+    Rule
+}
+
+impl<'a> Rule<'a> {
+    /// The name of the rule, if it is not missing.
+    pub fn name(self) -> Option<&'a EcoString> {
+        self.0
+            .find_by_kind(SyntaxKind::Identifier)
+            .map(SyntaxNode::text)
+    }
+
+    /// The parameters written in parentheses, like `item` in `list(item)`.
+    pub fn params(self) -> impl Iterator<Item = &'a EcoString> {
+        self.0
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::Param)
+            .filter(|param| {
+                param.children().any(|n| n.kind() == SyntaxKind::LeftParen)
+            })
+            .flat_map(SyntaxNode::children)
+            .filter(|n| n.kind() == SyntaxKind::Identifier)
+            .map(SyntaxNode::text)
+    }
+
+    /// The annotations before the rule.
+    pub fn annotations(self) -> impl Iterator<Item = Annotation<'a>> {
+        self.0.children().filter_map(Annotation::from_untyped)
+    }
+
+    /// The lines of the doc comments, without the slashes.
+    pub fn docs(self) -> impl Iterator<Item = &'a str> {
+        self.0
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::DocComment)
+            .map(|n| {
+                let text = n.text().strip_prefix("///").unwrap_or(n.text());
+                text.strip_prefix(' ').unwrap_or(text).trim_end()
+            })
+    }
+
+    /// The definition after `:` or `+=`.
+    pub fn definition(self) -> Option<Definition<'a>> {
+        self.0.children().find_map(Definition::from_untyped)
+    }
+
+    /// Whether the rule adds alternatives to another, like `a += b;`.
+    pub fn is_extension(self) -> bool {
+        self.0.children().any(|n| n.kind() == SyntaxKind::PlusEq)
+    }
+
+    /// Whether the rule only composes tokens, like `fragment a: b;`.
+    pub fn is_fragment(self) -> bool {
+        self.0.children().any(|n| n.kind() == SyntaxKind::Fragment)
+    }
+}
+
+node! {
+    /// An annotation of a rule, like `@inline` or `@name(args)`.
+    Annotation
+}
+
+impl<'a> Annotation<'a> {
+    /// The name without the `@`.
+    pub fn name(self) -> &'a str {
+        let name = self.0.children().next().map_or("", |n| n.text());
+        name.strip_prefix('@').unwrap_or(name)
+    }
+
+    /// The arguments in parentheses, if any.
+    pub fn args(self) -> Option<&'a str> {
+        self.0
+            .find_by_kind(SyntaxKind::Operation)
+            .map(|n| n.text().as_str())
+    }
+}
+
+node! {
+    /// A pragma, like `%start program;`.
+    Pragma
+}
+
+impl<'a> Pragma<'a> {
+    /// The name without the `%`, if it is known.
+    pub fn name(self) -> Option<&'a str> {
+        self.0
+            .find_by_kind(SyntaxKind::PragmaName)
+            .map(|n| n.text().as_str())
+    }
+
+    /// The rules or files named by the pragma.
+    pub fn args(self) -> impl Iterator<Item = &'a SyntaxNode> {
+        self.0.children().filter(|n| {
+            matches!(n.kind(), SyntaxKind::Identifier | SyntaxKind::String)
+        })
+    }
+}
+
+node! {
+    /// The definition of a rule or a named group.
+    Definition
+}
+
+impl<'a> Definition<'a> {
+    /// The items, across all alternatives.
+    pub fn items(self) -> impl Iterator<Item = Expr<'a>> {
+        exprs(self.0)
+    }
+
+    /// The items of each alternative separated by `|`.
+    pub fn alternatives(self) -> Vec<Vec<Expr<'a>>> {
+        let mut alternatives = vec![Vec::new()];
+        for node in self.0.children() {
+            match node.kind() {
+                | SyntaxKind::Bar => alternatives.push(Vec::new()),
+                | kind if kind.is_trivia() => {},
+                | _ => alternatives.last_mut().unwrap().push(Expr::from(node)),
+            }
+        }
+        alternatives
+    }
+}
+
+node! {
+    /// A group in parentheses, like `("," item)`.
+    Group
+}
+
+impl<'a> Group<'a> {
+    /// The items between the parentheses.
+    pub fn body(self) -> impl Iterator<Item = Expr<'a>> {
+        exprs(self.0)
+    }
+}
+
+node! {
+    /// A group naming its content as a rule, like `(sub: "a" | "b")`.
+    NamedGroup
+}
+
+impl<'a> NamedGroup<'a> {
+    /// The name of the nested rule.
+    pub fn name(self) -> Option<&'a EcoString> {
+        self.0
+            .find_by_kind(SyntaxKind::Identifier)
+            .map(SyntaxNode::text)
+    }
+
+    /// The definition after `:`.
+    pub fn definition(self) -> Option<Definition<'a>> {
+        self.0.children().find_map(Definition::from_untyped)
+    }
+}
+
+node! {
+    /// An optional expression in brackets, like `[ "," item ]`.
+    Optional
+}
+
+impl<'a> Optional<'a> {
+    /// The items between the brackets.
+    pub fn body(self) -> impl Iterator<Item = Expr<'a>> {
+        exprs(self.0)
+    }
+}
+
+node! {
+    /// An item with a repetition, like `item*` or `digit{2,4}`.
+    Repeating
+}
+
+impl<'a> Repeating<'a> {
+    /// The repeated item.
+    pub fn item(self) -> Option<Expr<'a>> {
+        self.0.children().next().map(Expr::from)
+    }
+
+    /// The bounds of the repetition, if well-formed.
+    pub fn bounds(self) -> Option<Repeat> {
+        Repeat::of(self.0)
+    }
+}
+
+node! {
+    /// A range of characters, like `"a".."z"`.
+    Range
+}
+
+impl Range<'_> {
+    /// The inclusive bounds, if the range is valid and not empty.
+    pub fn bounds(self) -> Option<(char, char)> {
+        range_bounds(self.0)
+    }
+
+    /// Whether the range includes its end, unless written with `..<`.
+    pub fn is_inclusive(self) -> bool {
+        !self.0.children().any(|n| n.kind() == SyntaxKind::DotsLt)
+    }
+}
+
+node! {
+    /// A lookahead or lookbehind, like `(?= a)` or `!a`.
+    Looking
+}
+
+impl<'a> Looking<'a> {
+    /// Whether the assertion looks behind instead of ahead.
+    pub fn is_behind(self) -> bool {
+        matches!(
+            self.operator(),
+            Some(SyntaxKind::LookBehindPos | SyntaxKind::LookBehindNeg)
+        )
+    }
+
+    /// Whether the assertion succeeds when the body does not match.
+    pub fn is_negative(self) -> bool {
+        matches!(
+            self.operator(),
+            Some(SyntaxKind::LookAheadNeg | SyntaxKind::LookBehindNeg)
+        )
+    }
+
+    /// The items after the operator.
+    pub fn body(self) -> impl Iterator<Item = Expr<'a>> {
+        self.0
+            .children()
+            .skip_while(|n| !n.kind().is_looking())
+            .skip(1)
+            .filter(|n| {
+                !n.kind().is_trivia() && n.kind() != SyntaxKind::RightParen
+            })
+            .map(Expr::from)
+    }
+
+    fn operator(self) -> Option<SyntaxKind> {
+        self.0
+            .children()
+            .map(SyntaxNode::kind)
+            .find(|k| k.is_looking())
+    }
+}
+
+node! {
+    /// A rule reference with arguments, like `list(item)`.
+    Reference
+}
+
+impl<'a> Reference<'a> {
+    /// The name of the referenced rule.
+    pub fn name(self) -> Option<&'a EcoString> {
+        self.0
+            .find_by_kind(SyntaxKind::Identifier)
+            .map(SyntaxNode::text)
+    }
+
+    /// The arguments in parentheses.
+    pub fn args(self) -> impl Iterator<Item = &'a SyntaxNode> {
+        self.0
+            .children()
+            .filter(|n| n.kind() == SyntaxKind::Argument)
+    }
+}
+
+/// An item of a definition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Expr<'a> {
+    Group(Group<'a>),
+    NamedGroup(NamedGroup<'a>),
+    Optional(Optional<'a>),
+    Repeating(Repeating<'a>),
+    Range(Range<'a>),
+    Looking(Looking<'a>),
+    Reference(Reference<'a>),
+    /// Any other item, like an identifier, a string or an action.
+    Other(&'a SyntaxNode),
+}
+
+impl<'a> From<&'a SyntaxNode> for Expr<'a> {
+    fn from(node: &'a SyntaxNode) -> Self {
+        match node.kind() {
+            | SyntaxKind::Group => Self::Group(Group(node)),
+            | SyntaxKind::NamedGroup => Self::NamedGroup(NamedGroup(node)),
+            | SyntaxKind::Optional => Self::Optional(Optional(node)),
+            | SyntaxKind::Repeating => Self::Repeating(Repeating(node)),
+            | SyntaxKind::Range => Self::Range(Range(node)),
+            | SyntaxKind::Looking => Self::Looking(Looking(node)),
+            | SyntaxKind::Reference => Self::Reference(Reference(node)),
+            | _ => Self::Other(node),
+        }
+    }
+}
+
+impl<'a> Expr<'a> {
+    /// The underlying node.
+    pub fn to_untyped(self) -> &'a SyntaxNode {
+        match self {
+            | Self::Group(node) => node.to_untyped(),
+            | Self::NamedGroup(node) => node.to_untyped(),
+            | Self::Optional(node) => node.to_untyped(),
+            | Self::Repeating(node) => node.to_untyped(),
+            | Self::Range(node) => node.to_untyped(),
+            | Self::Looking(node) => node.to_untyped(),
+            | Self::Reference(node) => node.to_untyped(),
+            | Self::Other(node) => node,
+        }
+    }
+}
+
+/// The items of a node, without its trivia and delimiters.
+fn exprs(node: &SyntaxNode) -> impl Iterator<Item = Expr<'_>> {
+    node.children()
+        .filter(|n| {
+            !n.kind().is_trivia()
+                && !matches!(
+                    n.kind(),
+                    SyntaxKind::LeftParen
+                        | SyntaxKind::RightParen
+                        | SyntaxKind::LeftBracket
+                        | SyntaxKind::RightBracket
+                )
+        })
+        .map(Expr::from)
+}
+
+impl SyntaxNode {
+    /// Cast the node to a typed view, if it has the right kind.
+    pub fn cast<'a, T: AstNode<'a>>(&'a self) -> Option<T> {
+        T::from_untyped(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn first_rule(root: &SyntaxNode) -> Rule<'_> {
+        root.cast::<Root>().unwrap().rules().next().unwrap()
+    }
+
+    #[test]
+    fn test_rule() {
+        let root = parse(
+            "/// A list.\n@inline @since(\"1.2\")\nlist(item, sep): item;",
+        );
+        let rule = first_rule(&root);
+        assert_eq!(rule.name().unwrap(), "list");
+        assert_eq!(rule.params().collect::<Vec<_>>(), ["item", "sep"]);
+        assert_eq!(rule.docs().collect::<Vec<_>>(), ["A list."]);
+        let annotations = rule
+            .annotations()
+            .map(|a| (a.name(), a.args()))
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, [("inline", None), ("since", Some("\"1.2\""))]);
+        assert!(!rule.is_extension());
+        assert!(!rule.is_fragment());
+
+        assert!(first_rule(&parse("a += b;")).is_extension());
+        assert!(first_rule(&parse("fragment a: b;")).is_fragment());
+    }
+
+    #[test]
+    fn test_definition() {
+        let root = parse("a: b c | d;");
+        let definition = first_rule(&root).definition().unwrap();
+        let texts = definition
+            .alternatives()
+            .iter()
+            .map(|alt| {
+                alt.iter().map(|e| e.to_untyped().text().as_str()).collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+        assert_eq!(texts, [vec!["b", "c"], vec!["d"]]);
+        assert_eq!(definition.items().count(), 4);
+    }
+
+    #[test]
+    fn test_expr() {
+        let root = parse(
+            "a: [ \",\" e ] b{2,} \"a\"..=\"z\" (?<! c) (sub: d) f(g);\nf(x): \
+             ;",
+        );
+        let items = first_rule(&root)
+            .definition()
+            .unwrap()
+            .items()
+            .collect::<Vec<_>>();
+        let [
+            Expr::Optional(optional),
+            Expr::Repeating(repeating),
+            Expr::Range(range),
+            Expr::Looking(looking),
+            Expr::NamedGroup(group),
+            Expr::Reference(reference),
+        ] = items[..]
+        else {
+            panic!("{items:?}");
+        };
+
+        let bounds = repeating.bounds().unwrap();
+        assert_eq!((bounds.min, bounds.max), (2, None));
+        assert!(
+            matches!(repeating.item(), Some(Expr::Other(n)) if n.text() == "b")
+        );
+        assert_eq!(range.bounds(), Some(('a', 'z')));
+        assert!(range.is_inclusive());
+        assert!(looking.is_behind() && looking.is_negative());
+        assert_eq!(looking.body().count(), 1);
+        assert_eq!(group.name().unwrap(), "sub");
+        assert_eq!(group.definition().unwrap().items().count(), 1);
+        assert_eq!(optional.body().count(), 2);
+        assert_eq!(reference.name().unwrap(), "f");
+        assert_eq!(reference.args().count(), 1);
+    }
+
+    #[test]
+    fn test_pragma() {
+        let root = parse("%skip ws comment;");
+        let pragma = root.cast::<Root>().unwrap().pragmas().next().unwrap();
+        assert_eq!(pragma.name(), Some("skip"));
+        assert_eq!(pragma.args().count(), 2);
+        assert!(root.cast::<Rule>().is_none());
+    }
+}
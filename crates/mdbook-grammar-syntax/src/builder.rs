@@ -0,0 +1,120 @@
+use crate::{SyntaxError, SyntaxKind, SyntaxNode};
+use ecow::EcoString;
+
+/// A builder constructing a syntax tree without going through text.
+///
+/// Nodes are built in document order: inner nodes are opened with
+/// [`start_node`](Self::start_node), filled with tokens and other nodes, and
+/// closed with [`finish_node`](Self::finish_node). Spans are computed from the
+/// text of the tokens, so the built tree is the one [`parse`](crate::parse)
+/// returns for the same text.
+#[derive(Debug, Default)]
+pub struct TreeBuilder {
+    offset: usize,
+    stack: Vec<(SyntaxKind, Vec<SyntaxNode>)>,
+    nodes: Vec<SyntaxNode>,
+}
+
+impl TreeBuilder {
+    /// Create a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open an inner node of the given kind.
+    pub fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    /// Close the last opened node.
+    ///
+    /// Panics if there is no open node.
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().expect("no node to finish");
+        self.push(SyntaxNode::inner(kind, children));
+    }
+
+    /// Add a token of the given kind.
+    pub fn token(&mut self, kind: SyntaxKind, text: impl Into<EcoString>) {
+        let text = text.into();
+        let span = self.advance(&text);
+        self.push(SyntaxNode::leaf(kind, text, span));
+    }
+
+    /// Add an erroneous token.
+    pub fn error(&mut self, error: SyntaxError, text: impl Into<EcoString>) {
+        let text = text.into();
+        let span = self.advance(&text);
+        self.push(SyntaxNode::error(error, text, span));
+    }
+
+    /// Finish building and return the single top-level node.
+    ///
+    /// Panics if a node is still open or if there is not exactly one
+    /// top-level node.
+    pub fn finish(mut self) -> SyntaxNode {
+        assert!(self.stack.is_empty(), "unfinished node");
+        assert_eq!(self.nodes.len(), 1, "expected a single top-level node");
+        self.nodes.pop().unwrap()
+    }
+
+    fn advance(&mut self, text: &str) -> std::ops::Range<usize> {
+        let start = self.offset;
+        self.offset += text.len();
+        start..self.offset
+    }
+
+    fn push(&mut self, node: SyntaxNode) {
+        match self.stack.last_mut() {
+            | Some((_, children)) => children.push(node),
+            | None => self.nodes.push(node),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_builder() {
+        let mut builder = TreeBuilder::new();
+        builder.start_node(SyntaxKind::Root);
+        builder.start_node(SyntaxKind::Rule);
+        builder.token(SyntaxKind::Identifier, "a");
+        builder.token(SyntaxKind::Colon, ":");
+        builder.start_node(SyntaxKind::Definition);
+        builder.token(SyntaxKind::Whitespace, " ");
+        builder.start_node(SyntaxKind::Repeating);
+        builder.token(SyntaxKind::Identifier, "b");
+        builder.token(SyntaxKind::Star, "*");
+        builder.finish_node();
+        builder.finish_node();
+        builder.token(SyntaxKind::SemiColon, ";");
+        builder.finish_node();
+        builder.finish_node();
+
+        assert_eq!(builder.finish(), parse("a: b*;"));
+    }
+
+    #[test]
+    fn test_builder_error() {
+        let mut builder = TreeBuilder::new();
+        builder.start_node(SyntaxKind::Root);
+        builder.error(SyntaxError::new("unexpected semicolon"), ";");
+        builder.finish_node();
+
+        let root = builder.finish();
+        assert!(root.erroneous());
+        assert_eq!(root.span(), &(0..1));
+    }
+
+    #[test]
+    #[should_panic = "unfinished node"]
+    fn test_builder_unfinished() {
+        let mut builder = TreeBuilder::new();
+        builder.start_node(SyntaxKind::Root);
+        builder.finish();
+    }
+}
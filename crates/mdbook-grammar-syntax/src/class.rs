@@ -0,0 +1,159 @@
+use crate::{SyntaxKind, SyntaxNode, lexer::unescape_class, unescape};
+use ecow::EcoString;
+
+/// The complement of a set of characters, decoded from a converse expression.
+///
+/// `~"a".."z"` matches any character outside the range, `~"a"` any character
+/// but `a`, `~[a-z_]` any character outside the class, and `~<newline>` any
+/// character the meta does not match.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NegatedClass {
+    /// The complement of an inclusive range of characters.
+    Range(char, char),
+    /// The complement of a single character.
+    Char(char),
+    /// The complement of a character class, as inclusive ranges.
+    Class(Vec<(char, char)>),
+    /// The complement of the characters matched by a meta, as written.
+    Meta(EcoString),
+}
+
+impl NegatedClass {
+    /// Decode the class of a [`SyntaxKind::Converse`] node.
+    ///
+    /// Return `None` if the negated expression is not a character, a range,
+    /// a class or a meta, like `~rule` or `~"ab"`.
+    pub fn of(converse: &SyntaxNode) -> Option<Self> {
+        debug_assert_eq!(converse.kind(), SyntaxKind::Converse);
+
+        let operand = converse
+            .children()
+            .skip_while(|n| n.kind() != SyntaxKind::Tilde)
+            .skip(1)
+            .find(|n| !n.kind().is_trivia())?;
+
+        match operand.kind() {
+            | SyntaxKind::String => Some(Self::Char(single_char(operand)?)),
+            | SyntaxKind::CharClass => {
+                Some(Self::Class(unescape_class(operand.text())?))
+            },
+            | SyntaxKind::Meta => Some(Self::Meta(operand.text().clone())),
+            | SyntaxKind::Range => {
+                let (start, end) = range_bounds(operand)?;
+                Some(Self::Range(start, end))
+            },
+            | _ => None,
+        }
+    }
+
+    /// Whether the class contains a character, or `None` for metas, whose
+    /// characters are not known.
+    pub fn contains(&self, c: char) -> Option<bool> {
+        match self {
+            | Self::Range(start, end) => Some(!(*start..=*end).contains(&c)),
+            | Self::Char(excluded) => Some(c != *excluded),
+            | Self::Class(ranges) => Some(
+                !ranges
+                    .iter()
+                    .any(|&(start, end)| (start..=end).contains(&c)),
+            ),
+            | Self::Meta(_) => None,
+        }
+    }
+}
+
+/// The first and last characters of a [`SyntaxKind::Range`] node, like `a`
+/// and `z` for `"a".."z"` or `"a"..="z"`, or `a` and `y` for `"a"..<"z"`,
+/// which excludes its end.
+///
+/// Return `None` if the range is erroneous or empty.
+pub fn range_bounds(range: &SyntaxNode) -> Option<(char, char)> {
+    debug_assert_eq!(range.kind(), SyntaxKind::Range);
+    if range.erroneous() {
+        return None;
+    }
+
+    let mut bounds =
+        range.children().filter(|n| n.kind() == SyntaxKind::String);
+    let start = single_char(bounds.next()?)?;
+    let mut end = single_char(bounds.next()?)?;
+    if range.children().any(|n| n.kind() == SyntaxKind::DotsLt) {
+        // The character before the surrogates is the last one below them.
+        end = match end {
+            | '\u{E000}' => '\u{D7FF}',
+            | _ => char::from_u32((end as u32).checked_sub(1)?)?,
+        };
+    }
+    (start <= end).then_some((start, end))
+}
+
+/// The character of a string literal of exactly one character.
+fn single_char(string: &SyntaxNode) -> Option<char> {
+    let value = unescape(string.text())?;
+    let mut chars = value.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    /// Decode the class of the converse expression defining a rule.
+    fn class(expr: &str) -> Option<NegatedClass> {
+        let root = parse(&format!("a: {expr};"));
+        let rule = root.children().next().unwrap();
+        let definition = rule.find_by_kind(SyntaxKind::Definition)?;
+        let converse = definition.children().find(|n| !n.kind().is_trivia())?;
+        assert_eq!(converse.kind(), SyntaxKind::Converse);
+        NegatedClass::of(converse)
+    }
+
+    #[test]
+    fn test_class_range() {
+        let range = class("~\"a\"..\"z\"").unwrap();
+        assert_eq!(range, NegatedClass::Range('a', 'z'));
+        assert_eq!(range.contains('m'), Some(false));
+        assert_eq!(range.contains('A'), Some(true));
+        assert_eq!(
+            class("~\"a\"..=\"z\""),
+            Some(NegatedClass::Range('a', 'z'))
+        );
+        assert_eq!(
+            class("~\"a\"..<\"z\""),
+            Some(NegatedClass::Range('a', 'y'))
+        );
+        assert_eq!(
+            class("~\"\\u{20}\"..<\"\\u{E000}\""),
+            Some(NegatedClass::Range(' ', '\u{D7FF}'))
+        );
+    }
+
+    #[test]
+    fn test_class_char() {
+        assert_eq!(class("~\"\\n\""), Some(NegatedClass::Char('\n')));
+        assert_eq!(class("~\"ab\""), None);
+    }
+
+    #[test]
+    fn test_class_set() {
+        let class = class("~[a-c_]").unwrap();
+        assert_eq!(class, NegatedClass::Class(vec![('a', 'c'), ('_', '_')]));
+        assert_eq!(class.contains('b'), Some(false));
+        assert_eq!(class.contains('d'), Some(true));
+    }
+
+    #[test]
+    fn test_class_meta() {
+        let meta = class("~<newline>").unwrap();
+        assert_eq!(meta, NegatedClass::Meta("<newline>".into()));
+        assert_eq!(meta.contains('\n'), None);
+    }
+
+    #[test]
+    fn test_class_other() {
+        assert_eq!(class("~b"), None);
+        assert_eq!(class("~(\"a\" | \"b\")"), None);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{SyntaxKind, SyntaxNode};
+use std::{
+    ops::{Deref, Range},
+    rc::Rc,
+};
+
+/// A node of the syntax tree with links to its ancestors, to navigate to its
+/// parent and siblings.
+///
+/// The owned tree has no parent pointers: cursors are built on the fly while
+/// descending from the root, and share the links to their ancestors.
+#[derive(Clone)]
+pub struct SyntaxCursor<'a> {
+    node: &'a SyntaxNode,
+    parent: Option<Rc<Self>>,
+    index: usize,
+}
+
+impl<'a> SyntaxCursor<'a> {
+    /// Start a cursor at the root of a tree.
+    pub fn new(root: &'a SyntaxNode) -> Self {
+        Self {
+            node: root,
+            parent: None,
+            index: 0,
+        }
+    }
+
+    /// The node under the cursor.
+    pub fn get(&self) -> &'a SyntaxNode {
+        self.node
+    }
+
+    /// The index of the node in its parent.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The offsets of the node in the source, or the empty range where it
+    /// would be if it has no text.
+    pub fn range(&self) -> Range<usize> {
+        let span = self.node.span();
+        if !span.is_empty() {
+            return span.clone();
+        }
+        // Inner nodes without children have no span of their own.
+        let offset = match self.prev_sibling() {
+            | Some(prev) => prev.range().end,
+            | None => self.parent().map_or(span.start, |p| p.range().start),
+        };
+        offset..offset
+    }
+
+    /// The parent of the node, unless it is the root.
+    pub fn parent(&self) -> Option<&Self> {
+        self.parent.as_deref()
+    }
+
+    /// The ancestors of the node, from its parent to the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = &Self> {
+        std::iter::successors(self.parent(), |node| node.parent())
+    }
+
+    /// The closest ancestor of the given kind, like the rule containing an
+    /// identifier.
+    pub fn find_ancestor(&self, kind: SyntaxKind) -> Option<&Self> {
+        self.ancestors().find(|node| node.kind() == kind)
+    }
+
+    /// The children of the node.
+    pub fn children(&self) -> impl Iterator<Item = SyntaxCursor<'a>> + '_ {
+        let parent = Rc::new(self.clone());
+        self.node.children().enumerate().map(move |(index, node)| {
+            SyntaxCursor {
+                node,
+                parent: Some(parent.clone()),
+                index,
+            }
+        })
+    }
+
+    /// The sibling before the node.
+    pub fn prev_sibling(&self) -> Option<Self> {
+        let index = self.index.checked_sub(1)?;
+        self.sibling(index)
+    }
+
+    /// The sibling after the node.
+    pub fn next_sibling(&self) -> Option<Self> {
+        self.sibling(self.index + 1)
+    }
+
+    /// The deepest node containing the offset.
+    pub fn node_at(&self, offset: usize) -> Option<Self> {
+        let span = self.node.span();
+        if !(span.start <= offset && offset < span.end) {
+            return None;
+        }
+        Some(
+            self.children()
+                .find_map(|child| child.node_at(offset))
+                .unwrap_or_else(|| self.clone()),
+        )
+    }
+
+    fn sibling(&self, index: usize) -> Option<Self> {
+        let parent = self.parent.as_ref()?;
+        let node = parent.node.children().nth(index)?;
+        Some(Self {
+            node,
+            parent: Some(parent.clone()),
+            index,
+        })
+    }
+}
+
+impl Deref for SyntaxCursor<'_> {
+    type Target = SyntaxNode;
+
+    fn deref(&self) -> &Self::Target {
+        self.node
+    }
+}
+
+impl std::fmt::Debug for SyntaxCursor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Rule, parse};
+
+    #[test]
+    fn test_node_at() {
+        let root = parse("a: b;\nc: d e;");
+        let cursor = SyntaxCursor::new(&root);
+
+        let node = cursor.node_at(11).unwrap();
+        assert_eq!(node.kind(), SyntaxKind::Identifier);
+        assert_eq!(node.text(), "e");
+        assert_eq!(node.range(), 11..12);
+
+        let rule = node.find_ancestor(SyntaxKind::Rule).unwrap();
+        let rule = rule.get().cast::<Rule>().unwrap();
+        assert_eq!(rule.name().unwrap(), "c");
+        assert_eq!(node.ancestors().map(|n| n.kind()).collect::<Vec<_>>(), [
+            SyntaxKind::Definition,
+            SyntaxKind::Rule,
+            SyntaxKind::Root
+        ]);
+        assert!(cursor.node_at(100).is_none());
+    }
+
+    #[test]
+    fn test_siblings() {
+        let root = parse("a: b c;");
+        let cursor = SyntaxCursor::new(&root);
+        let c = cursor.node_at(5).unwrap();
+        assert_eq!(c.text(), "c");
+        assert_eq!(c.index(), 3);
+
+        let space = c.prev_sibling().unwrap();
+        assert_eq!(space.kind(), SyntaxKind::Whitespace);
+        assert_eq!(space.prev_sibling().unwrap().text(), "b");
+        assert!(c.next_sibling().is_none());
+        assert!(cursor.prev_sibling().is_none());
+        assert_eq!(cursor.children().count(), 1);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{SyntaxKind, SyntaxNode};
+
+/// Format a parsed grammar, putting one alternative per line with the bars
+/// aligned below the colon of the rule.
+///
+/// Only whitespace is changed. Rules containing errors or comments, and rules
+/// whose actions would swallow the following items once moved onto a single
+/// line, are kept as they are.
+pub fn format(root: &SyntaxNode) -> String {
+    debug_assert_eq!(root.kind(), SyntaxKind::Root);
+
+    let mut out = String::new();
+    let mut children = root.children().peekable();
+
+    while let Some(node) = children.next() {
+        match node.kind() {
+            | SyntaxKind::Whitespace => {
+                // Skip leading and trailing whitespace.
+                let Some(next) = children.peek() else { break };
+                if out.is_empty() {
+                    continue;
+                }
+
+                out += if node.text().matches('\n').count() >= 2 {
+                    "\n\n"
+                } else if next.kind() == SyntaxKind::Comment
+                    && !node.text().contains('\n')
+                {
+                    " "
+                } else {
+                    "\n"
+                };
+            },
+            | SyntaxKind::Rule => match format_rule(node) {
+                | Some(text) => out += &text,
+                | None => verbatim(node, &mut out),
+            },
+            | _ => verbatim(node, &mut out),
+        }
+    }
+
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Format a rule, or return `None` if it should be kept as it is.
+fn format_rule(rule: &SyntaxNode) -> Option<String> {
+    if rule.erroneous() || contains(rule, SyntaxKind::Comment) {
+        return None;
+    }
+
+    let mut docs = String::new();
+    let mut header = String::new();
+    let mut alternatives = vec![Vec::new()];
+
+    for doc in rule
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::DocComment)
+    {
+        docs += doc.text().trim_end();
+        docs.push('\n');
+    }
+    for annotation in rule
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::Annotation)
+    {
+        verbatim(annotation, &mut docs);
+        docs.push('\n');
+    }
+
+    for node in rule.children().filter(|n| !n.kind().is_trivia()) {
+        match node.kind() {
+            | SyntaxKind::Fragment => header += "fragment ",
+            | SyntaxKind::Identifier => verbatim(node, &mut header),
+            | SyntaxKind::Param => header += &params(node),
+            | SyntaxKind::Definition => {
+                for node in node.children().filter(|n| !n.kind().is_trivia()) {
+                    if node.kind() == SyntaxKind::Bar {
+                        alternatives.push(Vec::new());
+                    } else {
+                        alternatives.last_mut().unwrap().push(node);
+                    }
+                }
+            },
+            | _ => {},
+        }
+    }
+
+    // Actions run until the end of the line, so they must end an alternative.
+    let swallowing = alternatives.iter().any(|items| {
+        let tail = items.iter().rev().take_while(|n| is_action(n)).count();
+        items[..items.len() - tail]
+            .iter()
+            .any(|n| contains(n, SyntaxKind::Action))
+    });
+    if swallowing {
+        return None;
+    }
+
+    // Bars are aligned below the last character of the separator.
+    let separator = if rule.children().any(|n| n.kind() == SyntaxKind::PlusEq) {
+        " +="
+    } else {
+        ":"
+    };
+    let pad = " ".repeat(header.chars().count() + separator.len() - 1);
+    let mut out = header + separator;
+
+    for (i, items) in alternatives.iter().enumerate() {
+        if i > 0 {
+            out += &format!("\n{pad}|");
+        }
+
+        let items = items.iter().copied().map(expr).collect::<Vec<_>>();
+        if !items.is_empty() {
+            out += " ";
+            out += &items.join(" ");
+        }
+    }
+
+    if alternatives.len() == 1 && alternatives[0].is_empty() {
+        out.push(' ');
+    }
+
+    out.push(';');
+    Some(docs + &out)
+}
+
+/// Format an item of a definition.
+fn expr(node: &SyntaxNode) -> String {
+    let children = node.children().filter(|n| !n.kind().is_trivia());
+
+    match node.kind() {
+        | SyntaxKind::Group | SyntaxKind::Looking => {
+            let mut open = String::new();
+            let mut items = Vec::new();
+            for child in children {
+                match child.kind() {
+                    | SyntaxKind::LeftParen => open += child.text(),
+                    | k if k.is_looking() => open += child.text(),
+                    | SyntaxKind::RightParen => {},
+                    | _ => items.push(expr(child)),
+                }
+            }
+            // Predicates like `!a` have no parentheses.
+            let close = if open.starts_with('(') { ")" } else { "" };
+            format!("{open}{}{close}", items.join(" "))
+        },
+        | SyntaxKind::NamedGroup => {
+            let mut name = String::new();
+            let mut items = Vec::new();
+            for child in children {
+                match child.kind() {
+                    | SyntaxKind::Identifier => verbatim(child, &mut name),
+                    | SyntaxKind::Definition => items.extend(
+                        child
+                            .children()
+                            .filter(|n| !n.kind().is_trivia())
+                            .map(expr),
+                    ),
+                    | _ => {},
+                }
+            }
+            format!("({name}: {})", items.join(" "))
+        },
+        | SyntaxKind::Optional => {
+            let items = children
+                .filter(|n| {
+                    !matches!(
+                        n.kind(),
+                        SyntaxKind::LeftBracket | SyntaxKind::RightBracket
+                    )
+                })
+                .map(expr)
+                .collect::<Vec<_>>();
+            format!("[ {} ]", items.join(" "))
+        },
+        | SyntaxKind::Action => children
+            .map(|n| {
+                let mut text = String::new();
+                verbatim(n, &mut text);
+                text.trim().to_string()
+            })
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        | SyntaxKind::Constraint => {
+            children.map(expr).collect::<Vec<_>>().join(" ")
+        },
+        | SyntaxKind::Reference => {
+            let mut out = String::new();
+            for child in children {
+                match child.kind() {
+                    | SyntaxKind::Comma => out += ", ",
+                    | _ => out += &expr(child),
+                }
+            }
+            out
+        },
+        | SyntaxKind::Separated | SyntaxKind::Difference => {
+            children.map(expr).collect::<Vec<_>>().join(" ")
+        },
+        | SyntaxKind::Argument => {
+            children.map(expr).collect::<Vec<_>>().join(" ")
+        },
+        | SyntaxKind::Param => {
+            let mut out = String::new();
+            verbatim(node, &mut out);
+            out
+        },
+        | _ if node.children().len() == 0 => node.text().to_string(),
+        | _ => children.map(expr).collect(),
+    }
+}
+
+/// Format the parameters of a rule, keeping bracketed ones as they are.
+fn params(param: &SyntaxNode) -> String {
+    let mut children = param.children().filter(|n| !n.kind().is_trivia());
+    if !children.any(|n| n.kind() == SyntaxKind::LeftParen) {
+        let mut out = String::new();
+        verbatim(param, &mut out);
+        return out;
+    }
+
+    let names = param
+        .children()
+        .filter(|n| n.kind() == SyntaxKind::Identifier)
+        .map(|n| n.text().as_str())
+        .collect::<Vec<_>>();
+    format!("({})", names.join(", "))
+}
+
+/// Append the original text of a node.
+fn verbatim(node: &SyntaxNode, out: &mut String) {
+    out.push_str(node.text());
+    for child in node.children() {
+        verbatim(child, out);
+    }
+}
+
+/// Whether the node is or contains a node of the given kind.
+fn contains(node: &SyntaxNode, kind: SyntaxKind) -> bool {
+    node.kind() == kind || node.children().any(|n| contains(n, kind))
+}
+
+/// Whether the node is an action.
+fn is_action(node: &SyntaxNode) -> bool {
+    node.kind() == SyntaxKind::Action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[track_caller]
+    fn test(input: &str, expected: &str) {
+        let formatted = format(&parse(input));
+        assert_eq!(formatted, expected);
+        // Formatting must be stable.
+        assert_eq!(format(&parse(&formatted)), expected);
+    }
+
+    #[test]
+    fn test_format_alternatives() {
+        test(
+            "  expr :term|  expr \"+\"  term\n  ;",
+            "expr: term\n    | expr \"+\" term;\n",
+        );
+    }
+
+    #[test]
+    fn test_format_extension() {
+        test("expr+=a|b;", "expr += a\n      | b;\n");
+    }
+
+    #[test]
+    fn test_format_fragment() {
+        test(
+            "fragment  DIGIT:\"0\"|\"1\";",
+            "fragment DIGIT: \"0\"\n              | \"1\";\n",
+        );
+    }
+
+    #[test]
+    fn test_format_items() {
+        test(
+            "rule[p] : ~ ( a | b )* ? \"a\" .. \"z\" a[x] { 1 , 2 } (?= c);",
+            "rule[p]: ~(a | b)*? \"a\"..\"z\" a[x]{1,2} (?=c);\n",
+        );
+        test("a: b%\",\" c ;", "a: b % \",\" c;\n");
+        test("a: .-\"x\"-b;", "a: . - \"x\" - b;\n");
+        test("a: b [\n  \",\"   b ];", "a: b [ \",\" b ];\n");
+        test("a: [ <x>|b ];", "a: [ <x> | b ];\n");
+        test("a:#add b|#mul c;", "a: #add b\n | #mul c;\n");
+        test("a: i\"select\"  b;", "a: i\"select\" b;\n");
+        test("a:^b  $;", "a: ^ b $;\n");
+        test("a: ! \"}\"  & b;", "a: !\"}\" &b;\n");
+        test("a: ( sub :\"x\"|b )c;", "a: (sub: \"x\" | b) c;\n");
+        test("a:r#\"x\\\"\"#  b;", "a: r#\"x\\\"\"# b;\n");
+        test(
+            "list ( item ,sep ) : item (sep item)* | list(a b ,\"c\" | d );",
+            "list(item, sep): item (sep item)*\n               | list(a b, \
+             \"c\" | d);\n",
+        );
+        test(
+            "rule: a  [not a keyword] | \"b\"*[if note];",
+            "rule: a [not a keyword]\n    | \"b\"* [if note];\n",
+        );
+    }
+
+    #[test]
+    fn test_format_actions() {
+        test(
+            "rule: a if  x  -> y  \n | b;",
+            "rule: a if x -> y\n    | b;\n",
+        );
+        test("rule: (a if x\n | b);", "rule: (a if x\n | b);\n");
+    }
+
+    #[test]
+    fn test_format_between_rules() {
+        test(
+            "\n\na: ; b: ; // comment\n\n\n\n/* c */ c: ;\n\n",
+            "a: ;\nb: ; // comment\n\n/* c */\nc: ;\n",
+        );
+    }
+
+    #[test]
+    fn test_format_doc_comments() {
+        test(
+            "/// A list.\n\n///   Of items.   \nlist :item*;",
+            "/// A list.\n///   Of items.\nlist: item*;\n",
+        );
+        test(
+            "/// Doc.\n@token(x) @inline list :item*;",
+            "/// Doc.\n@token(x)\n@inline\nlist: item*;\n",
+        );
+    }
+
+    #[test]
+    fn test_format_verbatim() {
+        test("a: b // comment\n | c;", "a: b // comment\n | c;\n");
+        test("a: b )", "a: b )\n");
+    }
+}
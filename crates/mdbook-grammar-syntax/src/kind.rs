@@ -1,99 +1,254 @@
+use ecow::{EcoString, eco_format};
+use std::str::FromStr;
+
+/// The kind of a syntax node.
+///
+/// The numbers of the kinds are stable: they are never reused or changed, and
+/// new kinds get the next free number, so serialized kinds stay valid across
+/// releases.
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SyntaxKind {
     /// the root of the syntax tree
-    Root,
+    Root = 0,
     /// comment (`// ...` or `/* ... */`)
-    Comment,
+    Comment = 1,
+    /// doc comment of the following rule (`/// ...`)
+    DocComment = 2,
     /// white spaces
-    Whitespace,
+    Whitespace = 3,
     /// end of input
-    End,
+    End = 4,
     /// error
-    Error,
+    Error = 5,
 
     /// name of rules
-    Identifier,
+    Identifier = 6,
     /// string literal
-    String,
+    String = 7,
+    /// raw string literal, like `r"\d"` or `r#"a "quote""#`
+    RawString = 8,
     /// integer literal
-    Integer,
+    Integer = 9,
     /// meta description
-    Meta,
+    Meta = 10,
+    /// character class (`[a-z0-9_]`)
+    CharClass = 11,
     /// operation after `if` and `->`
-    Operation,
+    Operation = 12,
     /// `if`
-    If,
+    If = 13,
+    /// name of an annotation, like `@inline`
+    AnnotationName = 14,
+    /// label of an alternative, like `#add`
+    Label = 15,
+    /// modifier before a string literal, like the `i` of `i"select"`
+    Modifier = 16,
+    /// name in a condition, like `strict` in `if strict`
+    Variable = 17,
+    /// operator in a condition, like `&&`, `not` or `==`
+    Operator = 18,
+    /// function called by an action, like `push` in `-> push(item)`
+    Function = 19,
+    /// the empty production, `empty` or `ε`
+    Empty = 20,
+    /// name of a pragma, like `start` in `%start program;`
+    PragmaName = 21,
+    /// `fragment`, before a rule only composing tokens
+    Fragment = 22,
 
     /// `:`
-    Colon,
+    Colon = 23,
+    /// `+=`
+    PlusEq = 24,
     /// `;`
-    SemiColon,
+    SemiColon = 25,
     /// `->`
-    Arrow,
+    Arrow = 26,
     /// `[`
-    LeftBracket,
+    LeftBracket = 27,
     /// `]`
-    RightBracket,
+    RightBracket = 28,
     /// `(`
-    LeftParen,
+    LeftParen = 29,
     /// `)`
-    RightParen,
+    RightParen = 30,
     /// `{`
-    LeftBrace,
+    LeftBrace = 31,
     /// `}`
-    RightBrace,
+    RightBrace = 32,
     /// `,`
-    Comma,
+    Comma = 33,
     /// `|`
-    Bar,
+    Bar = 34,
     /// `~`
-    Tilde,
+    Tilde = 35,
     /// `.`
-    Dot,
+    Dot = 36,
     /// `?`
-    Question,
+    Question = 37,
     /// `*`
-    Star,
+    Star = 38,
     /// `+`
-    Plus,
+    Plus = 39,
     /// `..`
-    Dots,
+    Dots = 40,
+    /// `..=`
+    DotsEq = 41,
+    /// `%`
+    Percent = 42,
+    /// `-`
+    Minus = 43,
+    /// `^`, asserting the start of a line
+    Caret = 44,
+    /// `$`, asserting the end of a line
+    Dollar = 45,
     /// `?=`
-    LookAheadPos,
+    LookAheadPos = 46,
     /// `?!`
-    LookAheadNeg,
+    LookAheadNeg = 47,
     /// `?<=`
-    LookBehindPos,
+    LookBehindPos = 48,
     /// `?<!`
-    LookBehindNeg,
+    LookBehindNeg = 49,
 
     /// a grammar rule
-    Rule,
+    Rule = 50,
     /// the param of a rule
-    Param,
+    Param = 51,
+    /// an annotation of a rule, like `@inline` or `@name(args)`
+    Annotation = 52,
     /// the definition of a rule
-    Definition,
+    Definition = 53,
 
     /// a group expression
-    Group,
+    Group = 54,
     /// a converse expression
-    Converse,
+    Converse = 55,
     /// a range expression
-    Range,
+    Range = 56,
     /// a repeating expression
-    Repeating,
+    Repeating = 57,
+    /// a list of items with separators, like `arg % ","`
+    Separated = 58,
+    /// an item excluding the matches of another, like `char - "\""`
+    Difference = 59,
+    /// an optional expression in brackets, like `[ "," item ]`
+    Optional = 60,
+    /// a case-insensitive string literal, like `i"select"`
+    Insensitive = 61,
+    /// the condition of an `if`, or a parenthesized part of it
+    Condition = 62,
+    /// a comparison in a condition, like `depth > 2`
+    Comparison = 63,
+    /// conditions joined with `&&` or `||`
+    Logical = 64,
+    /// a condition negated with `!` or `not`
+    Negation = 65,
+    /// a function call in an action, like `push(item)`
+    Call = 66,
     /// the brace repeating indicator
-    BraceIndicator,
+    BraceIndicator = 67,
     /// a lookahead or lookbehind expression
-    Looking,
+    Looking = 68,
+    /// a group naming its content as a rule, like `(sub: "a" | "b")`
+    NamedGroup = 69,
     /// an action expression
-    Action,
+    Action = 70,
+    /// a pragma, like `%start program;` or `%skip whitespace;`
+    Pragma = 71,
     /// rule reference with argument
-    Reference,
+    Reference = 72,
+    /// an argument of a rule reference or a call, like `item` in `list(item)`
+    Argument = 73,
+    /// an expression with a bracketed constraint, like `a [not a keyword]`
+    Constraint = 74,
+    /// `..<`
+    DotsLt = 75,
 }
 
 impl SyntaxKind {
+    /// All the kinds, indexed by their number.
+    pub const ALL: [SyntaxKind; 76] = [
+        SyntaxKind::Root,
+        SyntaxKind::Comment,
+        SyntaxKind::DocComment,
+        SyntaxKind::Whitespace,
+        SyntaxKind::End,
+        SyntaxKind::Error,
+        SyntaxKind::Identifier,
+        SyntaxKind::String,
+        SyntaxKind::RawString,
+        SyntaxKind::Integer,
+        SyntaxKind::Meta,
+        SyntaxKind::CharClass,
+        SyntaxKind::Operation,
+        SyntaxKind::If,
+        SyntaxKind::AnnotationName,
+        SyntaxKind::Label,
+        SyntaxKind::Modifier,
+        SyntaxKind::Variable,
+        SyntaxKind::Operator,
+        SyntaxKind::Function,
+        SyntaxKind::Empty,
+        SyntaxKind::PragmaName,
+        SyntaxKind::Fragment,
+        SyntaxKind::Colon,
+        SyntaxKind::PlusEq,
+        SyntaxKind::SemiColon,
+        SyntaxKind::Arrow,
+        SyntaxKind::LeftBracket,
+        SyntaxKind::RightBracket,
+        SyntaxKind::LeftParen,
+        SyntaxKind::RightParen,
+        SyntaxKind::LeftBrace,
+        SyntaxKind::RightBrace,
+        SyntaxKind::Comma,
+        SyntaxKind::Bar,
+        SyntaxKind::Tilde,
+        SyntaxKind::Dot,
+        SyntaxKind::Question,
+        SyntaxKind::Star,
+        SyntaxKind::Plus,
+        SyntaxKind::Dots,
+        SyntaxKind::DotsEq,
+        SyntaxKind::Percent,
+        SyntaxKind::Minus,
+        SyntaxKind::Caret,
+        SyntaxKind::Dollar,
+        SyntaxKind::LookAheadPos,
+        SyntaxKind::LookAheadNeg,
+        SyntaxKind::LookBehindPos,
+        SyntaxKind::LookBehindNeg,
+        SyntaxKind::Rule,
+        SyntaxKind::Param,
+        SyntaxKind::Annotation,
+        SyntaxKind::Definition,
+        SyntaxKind::Group,
+        SyntaxKind::Converse,
+        SyntaxKind::Range,
+        SyntaxKind::Repeating,
+        SyntaxKind::Separated,
+        SyntaxKind::Difference,
+        SyntaxKind::Optional,
+        SyntaxKind::Insensitive,
+        SyntaxKind::Condition,
+        SyntaxKind::Comparison,
+        SyntaxKind::Logical,
+        SyntaxKind::Negation,
+        SyntaxKind::Call,
+        SyntaxKind::BraceIndicator,
+        SyntaxKind::Looking,
+        SyntaxKind::NamedGroup,
+        SyntaxKind::Action,
+        SyntaxKind::Pragma,
+        SyntaxKind::Reference,
+        SyntaxKind::Argument,
+        SyntaxKind::Constraint,
+        SyntaxKind::DotsLt,
+    ];
+
     pub fn is_error(self) -> bool {
         matches!(self, SyntaxKind::Error)
     }
@@ -103,7 +258,12 @@ impl SyntaxKind {
     }
 
     pub fn is_trivia(self) -> bool {
-        matches!(self, SyntaxKind::Whitespace | SyntaxKind::Comment)
+        matches!(
+            self,
+            SyntaxKind::Whitespace
+                | SyntaxKind::Comment
+                | SyntaxKind::DocComment
+        )
     }
 
     pub fn is_looking(self) -> bool {
@@ -130,6 +290,7 @@ impl SyntaxKind {
         matches!(
             self,
             SyntaxKind::Colon
+                | SyntaxKind::PlusEq
                 | SyntaxKind::SemiColon
                 | SyntaxKind::Arrow
                 | SyntaxKind::LeftBracket
@@ -146,6 +307,12 @@ impl SyntaxKind {
                 | SyntaxKind::Star
                 | SyntaxKind::Plus
                 | SyntaxKind::Dots
+                | SyntaxKind::DotsEq
+                | SyntaxKind::DotsLt
+                | SyntaxKind::Percent
+                | SyntaxKind::Minus
+                | SyntaxKind::Caret
+                | SyntaxKind::Dollar
                 | SyntaxKind::LookAheadPos
                 | SyntaxKind::LookAheadNeg
                 | SyntaxKind::LookBehindPos
@@ -157,16 +324,29 @@ impl SyntaxKind {
         match self {
             | SyntaxKind::Root => "root",
             | SyntaxKind::Comment => "comment",
+            | SyntaxKind::DocComment => "doc comment",
             | SyntaxKind::Whitespace => "whitespace",
             | SyntaxKind::End => "end",
             | SyntaxKind::Error => "error",
             | SyntaxKind::Identifier => "identifier",
             | SyntaxKind::String => "string",
+            | SyntaxKind::RawString => "raw string",
             | SyntaxKind::Integer => "integer",
             | SyntaxKind::Meta => "meta",
+            | SyntaxKind::CharClass => "character class",
             | SyntaxKind::Operation => "operation",
             | SyntaxKind::If => "if",
+            | SyntaxKind::AnnotationName => "annotation name",
+            | SyntaxKind::Label => "label",
+            | SyntaxKind::Modifier => "modifier",
+            | SyntaxKind::Variable => "variable",
+            | SyntaxKind::Operator => "operator",
+            | SyntaxKind::Function => "function",
+            | SyntaxKind::Empty => "empty",
+            | SyntaxKind::PragmaName => "pragma name",
+            | SyntaxKind::Fragment => "fragment",
             | SyntaxKind::Colon => "`:`",
+            | SyntaxKind::PlusEq => "`+=`",
             | SyntaxKind::SemiColon => "`;`",
             | SyntaxKind::Arrow => "`->`",
             | SyntaxKind::LeftBracket => "`[`",
@@ -183,21 +363,88 @@ impl SyntaxKind {
             | SyntaxKind::Star => "`*`",
             | SyntaxKind::Plus => "`+`",
             | SyntaxKind::Dots => "`..`",
+            | SyntaxKind::DotsEq => "`..=`",
+            | SyntaxKind::DotsLt => "`..<`",
+            | SyntaxKind::Percent => "`%`",
+            | SyntaxKind::Minus => "`-`",
+            | SyntaxKind::Caret => "`^`",
+            | SyntaxKind::Dollar => "`$`",
             | SyntaxKind::LookAheadPos => "`?=`",
             | SyntaxKind::LookAheadNeg => "`?!`",
             | SyntaxKind::LookBehindPos => "`?<=`",
             | SyntaxKind::LookBehindNeg => "`?<!`",
             | SyntaxKind::Rule => "rule",
             | SyntaxKind::Param => "param",
+            | SyntaxKind::Annotation => "annotation",
             | SyntaxKind::Definition => "definition",
             | SyntaxKind::Group => "group",
+            | SyntaxKind::NamedGroup => "named group",
             | SyntaxKind::Converse => "converse",
             | SyntaxKind::Range => "range",
             | SyntaxKind::Repeating => "repeating",
+            | SyntaxKind::Separated => "separated",
+            | SyntaxKind::Difference => "difference",
+            | SyntaxKind::Optional => "optional",
+            | SyntaxKind::Insensitive => "case-insensitive string",
+            | SyntaxKind::Condition => "condition",
+            | SyntaxKind::Comparison => "comparison",
+            | SyntaxKind::Logical => "logical expression",
+            | SyntaxKind::Negation => "negation",
+            | SyntaxKind::Call => "call",
             | SyntaxKind::BraceIndicator => "brace_indicator",
             | SyntaxKind::Looking => "looking",
             | SyntaxKind::Action => "action",
+            | SyntaxKind::Pragma => "pragma",
             | SyntaxKind::Reference => "reference",
+            | SyntaxKind::Argument => "argument",
+            | SyntaxKind::Constraint => "constraint",
+        }
+    }
+}
+
+impl TryFrom<u8> for SyntaxKind {
+    type Error = u8;
+
+    /// The kind with the given number, or the number if there is none.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        Self::ALL.get(usize::from(value)).copied().ok_or(value)
+    }
+}
+
+impl FromStr for SyntaxKind {
+    type Err = EcoString;
+
+    /// The kind with the given [name](SyntaxKind::name).
+    fn from_str(name: &str) -> Result<Self, EcoString> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| eco_format!("unknown syntax kind `{name}`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers() {
+        for (number, kind) in SyntaxKind::ALL.into_iter().enumerate() {
+            assert_eq!(kind as usize, number);
+            assert_eq!(SyntaxKind::try_from(kind as u8), Ok(kind));
+        }
+        assert_eq!(SyntaxKind::Root as u8, 0);
+        assert_eq!(SyntaxKind::Identifier as u8, 6);
+        assert_eq!(SyntaxKind::Constraint as u8, 74);
+        assert_eq!(SyntaxKind::try_from(200), Err(200));
+    }
+
+    #[test]
+    fn test_names() {
+        for kind in SyntaxKind::ALL {
+            assert_eq!(kind.name().parse(), Ok(kind));
         }
+        assert_eq!("`?<=`".parse(), Ok(SyntaxKind::LookBehindPos));
+        assert!("nothing".parse::<SyntaxKind>().is_err());
     }
 }
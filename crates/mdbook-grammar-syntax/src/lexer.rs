@@ -1,27 +1,70 @@
-use crate::{SyntaxError, SyntaxKind, SyntaxNode};
+use crate::{
+    MAX_DEPTH, SyntaxError, SyntaxKind, SyntaxNode,
+    action::{call, condition},
+    property::{is_unicode_property, suggest_property},
+};
 use ecow::{EcoString, eco_format};
+use std::ops::Range;
 use unscanny::Scanner;
 
+/// Splits a grammar into tokens, one [`SyntaxNode`] at a time.
+///
+/// Tokens are leaves or error nodes covering the whole text, trivia included.
+/// [`Lexer::next_token`] ends with an empty [`SyntaxKind::End`] token, which
+/// iterating over the lexer leaves out.
 pub struct Lexer<'s> {
     s: Scanner<'s>,
     error: Option<SyntaxError>,
+    /// How deep the parts of actions may be nested, like the items of the
+    /// parser.
+    max_depth: usize,
 }
 
 impl<'s> Lexer<'s> {
+    /// Start lexing the text.
     pub fn new(text: &'s str) -> Self {
         Self {
             s: Scanner::new(text),
             error: None,
+            max_depth: MAX_DEPTH,
         }
     }
 
+    /// Limit how deep the parts of actions may be nested, keeping deeper
+    /// actions as opaque operations.
+    pub(crate) fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Continue lexing at the given offset.
     pub fn jump(&mut self, target: usize) {
         self.s.jump(target);
     }
 
+    /// Whether the end of the text is reached.
     pub fn done(&self) -> bool {
         self.s.done()
     }
+
+    /// The current position in the text.
+    pub fn cursor(&self) -> usize {
+        self.s.cursor()
+    }
+
+    /// The text in the given range.
+    pub fn get(&self, range: Range<usize>) -> &'s str {
+        self.s.get(range)
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = SyntaxNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.next_token();
+        (token.kind() != SyntaxKind::End).then_some(token)
+    }
 }
 
 impl Lexer<'_> {
@@ -38,7 +81,8 @@ impl Lexer<'_> {
 }
 
 impl Lexer<'_> {
-    pub fn next(&mut self) -> SyntaxNode {
+    /// Lex the next token.
+    pub fn next_token(&mut self) -> SyntaxNode {
         debug_assert!(self.error.is_none());
         let start = self.s.cursor();
 
@@ -69,10 +113,37 @@ impl Lexer<'_> {
                 }
             },
             | Some(c) if is_id_start(c) => return self.identifier(start),
+            | Some('@') => return self.annotation(start),
+            | Some('#') => {
+                if self.s.eat_if(is_id_start) {
+                    self.s.eat_while(is_id_continue);
+                    SyntaxKind::Label
+                } else {
+                    self.error("expected label name");
+                    self.hint("labels are names, like `#add`");
+                    SyntaxKind::Error
+                }
+            },
             | Some('-') if self.s.eat_if('>') => {
                 return self.action(start, SyntaxKind::Arrow);
             },
+            | Some('-') => SyntaxKind::Minus,
 
+            // The meaning of brackets depends on what they hold, and on being
+            // attached to a name, but not on the item before them.
+            | Some('[')
+                if !self.s.get(0..start).ends_with(is_id_continue)
+                    && is_char_class(self.s.after()) =>
+            {
+                return self.char_class(start);
+            },
+            | Some('[')
+                if !self.s.get(0..start).ends_with(is_id_continue)
+                    && is_optional(self.s.after()) =>
+            {
+                SyntaxKind::LeftBracket
+            },
+            | Some(']') => SyntaxKind::RightBracket,
             | Some('[') => {
                 if let Some(node) = self.param(start) {
                     return node;
@@ -89,15 +160,23 @@ impl Lexer<'_> {
             | Some(',') => SyntaxKind::Comma,
             | Some('|') => SyntaxKind::Bar,
             | Some('~') => SyntaxKind::Tilde,
+            | Some('.') if self.s.eat_if(".=") => SyntaxKind::DotsEq,
+            | Some('.') if self.s.eat_if(".<") => SyntaxKind::DotsLt,
             | Some('.') if self.s.eat_if('.') => SyntaxKind::Dots,
             | Some('.') => SyntaxKind::Dot,
             | Some('*') => SyntaxKind::Star,
+            | Some('+') if self.s.eat_if('=') => SyntaxKind::PlusEq,
             | Some('+') => SyntaxKind::Plus,
+            | Some('%') => SyntaxKind::Percent,
+            | Some('^') => SyntaxKind::Caret,
+            | Some('$') => SyntaxKind::Dollar,
             | Some('?') if self.s.eat_if('=') => SyntaxKind::LookAheadPos,
             | Some('?') if self.s.eat_if('!') => SyntaxKind::LookAheadNeg,
             | Some('?') if self.s.eat_if("<=") => SyntaxKind::LookBehindPos,
             | Some('?') if self.s.eat_if("<!") => SyntaxKind::LookBehindNeg,
             | Some('?') => SyntaxKind::Question,
+            | Some('&') => SyntaxKind::LookAheadPos,
+            | Some('!') => SyntaxKind::LookAheadNeg,
 
             | Some(c) => self.error(eco_format!("unexpected character `{c}`")),
         };
@@ -115,8 +194,14 @@ impl Lexer<'_> {
     }
 
     fn line_comment(&mut self) -> SyntaxKind {
+        // Like in Rust, four slashes or more are a plain comment.
+        let doc = self.s.at('/') && !self.s.after().starts_with("//");
         self.s.eat_until(is_newline);
-        SyntaxKind::Comment
+        if doc {
+            SyntaxKind::DocComment
+        } else {
+            SyntaxKind::Comment
+        }
     }
 
     fn block_comment(&mut self) -> SyntaxKind {
@@ -137,7 +222,9 @@ impl Lexer<'_> {
             } else if c == '\\' {
                 if let Some(next) = self.s.eat() {
                     match next {
-                        | 'n' | 'r' | 't' | 'b' | 'f' | '\\' | '"' => {},
+                        | 'n' | 'r' | 't' | 'b' | 'f' | '0' | '\\' | '"' => {},
+                        | 'p' | 'P' => self.property(),
+                        | 'x' => self.hex_escape(),
                         | 'u' => {
                             let unicode = if self.s.eat_if('{') {
                                 let unicode =
@@ -179,12 +266,157 @@ impl Lexer<'_> {
         SyntaxKind::Error
     }
 
+    /// Lex a character class starting at the given position, which must be
+    /// at its `[`.
+    fn char_class(&mut self, start: usize) -> SyntaxNode {
+        self.s.jump(start);
+        self.s.expect('[');
+
+        let mut property = false;
+        let kind = loop {
+            match self.s.eat() {
+                | None => break self.unclosed_class(),
+                | Some(c) if is_newline(c) => break self.unclosed_class(),
+                | Some(']') => break SyntaxKind::CharClass,
+                | Some('\\') => match self.s.eat() {
+                    | Some('u') if self.s.eat_if('{') => {
+                        let unicode = self.s.eat_while(char::is_alphanumeric);
+                        if !self.s.eat_if('}')
+                            || u32::from_str_radix(unicode, 16).is_err()
+                        {
+                            self.error("invalid unicode escape");
+                            self.hint("consider writing it like `\\u{41}`");
+                        }
+                    },
+                    | Some('p' | 'P') => {
+                        property = true;
+                        self.property();
+                    },
+                    | Some('x') => self.hex_escape(),
+                    | Some(c) if class_escape(c).is_some() => {},
+                    | _ => {
+                        self.error("invalid escape sequence");
+                    },
+                },
+                | Some(_) => {},
+            }
+        };
+
+        // The characters of properties are not known, so neither is the
+        // order of the ranges.
+        if kind == SyntaxKind::CharClass && self.error.is_none() && !property {
+            match unescape_class(self.s.from(start)).as_deref() {
+                | Some([]) => {
+                    self.error("empty character class");
+                },
+                | Some(_) => {},
+                | None => {
+                    self.error("range endpoints are out of order");
+                    self.hint(
+                        "a range goes from the lower character, like `a-z`",
+                    );
+                },
+            }
+        }
+
+        if let Some(error) = self.error.take() {
+            SyntaxNode::error(error, self.s.from(start), start..self.s.cursor())
+        } else {
+            SyntaxNode::leaf(kind, self.s.from(start), start..self.s.cursor())
+        }
+    }
+
+    /// Lex the two digits of a hex escape after its `\x`, like `41`.
+    fn hex_escape(&mut self) {
+        let start = self.s.cursor();
+        if hex_escape(&mut self.s).is_some() {
+            return;
+        }
+
+        if self.s.from(start).len() == 2 {
+            self.error("hex escape out of range");
+            self.hint(
+                "hex escapes go up to `\\x7F`, consider writing others like \
+                 `\\u{80}`",
+            );
+        } else {
+            self.error("invalid hex escape");
+            self.hint("consider writing it like `\\x41`");
+        }
+    }
+
+    /// Lex the name of a Unicode property escape after its `\p` or `\P`, like
+    /// `{L}`.
+    fn property(&mut self) {
+        if !self.s.eat_if('{') {
+            self.error("invalid unicode property escape");
+            self.hint("consider writing it like `\\p{L}`");
+            return;
+        }
+
+        let name = self
+            .s
+            .eat_until(|c| matches!(c, '}' | ']' | '"') || is_newline(c));
+        if !self.s.eat_if('}') {
+            self.error("unclosed unicode property escape");
+            self.hint("consider closing the unicode property escape with `}`");
+        } else if !is_unicode_property(name) {
+            self.error(eco_format!("unknown unicode property `{name}`"));
+            match suggest_property(name) {
+                | Some(known) => {
+                    self.hint(eco_format!("did you mean `{known}`?"))
+                },
+                | None => self.hint(
+                    "properties are general categories like `Lu`, scripts \
+                     like `Greek` or binary properties like `White_Space`",
+                ),
+            }
+        }
+    }
+
+    fn unclosed_class(&mut self) -> SyntaxKind {
+        self.error("unclosed character class");
+        self.hint("consider closing the character class with `]`");
+        SyntaxKind::Error
+    }
+
     fn identifier(&mut self, start: usize) -> SyntaxNode {
         self.s.eat_while(is_id_continue);
+        // Qualified names, like `lexer::Identifier`, are a single identifier.
+        // Dots are left alone, since `.` matches any character.
+        while self.s.at("::") && self.s.scout(2).is_some_and(is_id_start) {
+            self.s.eat();
+            self.s.eat();
+            self.s.eat_while(is_id_continue);
+        }
         let text = self.s.from(start);
 
         if text == "if" {
             self.action(start, SyntaxKind::If)
+        } else if text == "ε"
+            || (text == "empty"
+                && !self.s.after().trim_start().starts_with(':'))
+        {
+            SyntaxNode::leaf(SyntaxKind::Empty, text, start..self.s.cursor())
+        } else if text == "i" && self.s.eat_if('"') {
+            self.insensitive(start)
+        } else if text == "r"
+            && self.s.after().trim_start_matches('#').starts_with('"')
+        {
+            let kind = self.raw_string();
+            if let Some(error) = self.error.take() {
+                SyntaxNode::error(
+                    error,
+                    self.s.from(start),
+                    start..self.s.cursor(),
+                )
+            } else {
+                SyntaxNode::leaf(
+                    kind,
+                    self.s.from(start),
+                    start..self.s.cursor(),
+                )
+            }
         } else {
             SyntaxNode::leaf(
                 SyntaxKind::Identifier,
@@ -194,6 +426,45 @@ impl Lexer<'_> {
         }
     }
 
+    /// Lex a raw string literal after its `r`, up to the quote followed by as
+    /// many `#` as before the opening quote.
+    fn raw_string(&mut self) -> SyntaxKind {
+        let hashes = self.s.eat_while('#').len();
+        self.s.expect('"');
+
+        let close = format!("\"{}", "#".repeat(hashes));
+        while !self.s.done() {
+            if self.s.eat_if(close.as_str()) {
+                return SyntaxKind::RawString;
+            }
+            self.s.eat();
+        }
+
+        self.error("unclosed raw string literal");
+        self.hint(eco_format!(
+            "consider closing the raw string literal with `{close}`"
+        ));
+        SyntaxKind::Error
+    }
+
+    /// Lex a case-insensitive string literal after its `i"`.
+    fn insensitive(&mut self, start: usize) -> SyntaxNode {
+        let quote = self.s.cursor() - 1;
+        let kind = self.string();
+        if let Some(error) = self.error.take() {
+            return SyntaxNode::error(
+                error,
+                self.s.from(start),
+                start..self.s.cursor(),
+            );
+        }
+
+        SyntaxNode::inner(SyntaxKind::Insensitive, vec![
+            SyntaxNode::leaf(SyntaxKind::Modifier, "i", start..quote),
+            SyntaxNode::leaf(kind, self.s.from(quote), quote..self.s.cursor()),
+        ])
+    }
+
     fn action(&mut self, start: usize, kind: SyntaxKind) -> SyntaxNode {
         let text = self.s.from(start);
         let cursor = self.s.cursor();
@@ -211,17 +482,72 @@ impl Lexer<'_> {
         }
 
         let action = self.s.from(cursor);
-
-        SyntaxNode::inner(SyntaxKind::Action, vec![
-            SyntaxNode::leaf(kind, text, start..cursor),
+        let parsed = match kind {
+            | SyntaxKind::If => condition(action, cursor, self.max_depth),
+            | _ => call(action, cursor, self.max_depth),
+        };
+        let operation = parsed.unwrap_or_else(|| {
             SyntaxNode::leaf(
                 SyntaxKind::Operation,
                 action,
                 cursor..self.s.cursor(),
-            ),
+            )
+        });
+
+        SyntaxNode::inner(SyntaxKind::Action, vec![
+            SyntaxNode::leaf(kind, text, start..cursor),
+            operation,
         ])
     }
 
+    fn annotation(&mut self, start: usize) -> SyntaxNode {
+        if !self.s.eat_if(is_id_start) {
+            self.error("expected annotation name");
+            self.hint("annotations are names, like `@inline`");
+            let error = self.error.take().unwrap();
+            return SyntaxNode::error(error, "@", start..self.s.cursor());
+        }
+        self.s.eat_while(is_id_continue);
+
+        let cursor = self.s.cursor();
+        let mut nodes = vec![SyntaxNode::leaf(
+            SyntaxKind::AnnotationName,
+            self.s.from(start),
+            start..cursor,
+        )];
+        if !self.s.eat_if('(') {
+            return SyntaxNode::inner(SyntaxKind::Annotation, nodes);
+        }
+
+        nodes.push(SyntaxNode::leaf(
+            SyntaxKind::LeftParen,
+            "(",
+            cursor..self.s.cursor(),
+        ));
+        let args = self.s.cursor();
+        self.s.eat_until(|c| c == ')' || is_newline(c));
+        nodes.push(SyntaxNode::leaf(
+            SyntaxKind::Operation,
+            self.s.from(args),
+            args..self.s.cursor(),
+        ));
+
+        let cursor = self.s.cursor();
+        if self.s.eat_if(')') {
+            nodes.push(SyntaxNode::leaf(
+                SyntaxKind::RightParen,
+                ")",
+                cursor..self.s.cursor(),
+            ));
+        } else {
+            let mut error = SyntaxError::new("unclosed annotation arguments");
+            error.hint("consider closing the arguments with `)`");
+            nodes.push(SyntaxNode::error(error, "", cursor..cursor));
+        }
+
+        SyntaxNode::inner(SyntaxKind::Annotation, nodes)
+    }
+
     fn param(&mut self, start: usize) -> Option<SyntaxNode> {
         let mut nodes = Vec::with_capacity(3);
 
@@ -256,6 +582,188 @@ impl Lexer<'_> {
     }
 }
 
+/// Decode the value of a string literal, including its quotes, or of a raw
+/// string literal, including its `r` and `#`.
+///
+/// Return `None` if the literal is malformed, or matches a Unicode property
+/// like `\p{L}`.
+/// Split a grammar into tokens, without the final [`SyntaxKind::End`].
+///
+/// This is enough for highlighters that don't need the syntax tree.
+pub fn tokenize(text: &str) -> Vec<SyntaxNode> {
+    Lexer::new(text).collect()
+}
+
+pub fn unescape(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let quotes = &raw[hashes..raw.len().checked_sub(hashes)?];
+        let value = quotes.strip_prefix('"')?.strip_suffix('"')?;
+        return Some(value.into());
+    }
+
+    let mut s = Scanner::new(literal.strip_prefix('"')?.strip_suffix('"')?);
+    let mut value = String::new();
+
+    while let Some(c) = s.eat() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+
+        value.push(match s.eat()? {
+            | 'n' => '\n',
+            | 'r' => '\r',
+            | 't' => '\t',
+            | 'b' => '\u{8}',
+            | 'f' => '\u{c}',
+            | '0' => '\0',
+            | 'x' => hex_escape(&mut s)?,
+            | '\\' => '\\',
+            | '"' => '"',
+            | 'u' => {
+                let hex = if s.eat_if('{') {
+                    let hex = s.eat_until('}');
+                    s.eat_if('}').then_some(hex)?
+                } else {
+                    let start = s.cursor();
+                    for _ in 0..4 {
+                        s.eat()?;
+                    }
+                    s.from(start)
+                };
+                char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+            },
+            | _ => return None,
+        });
+    }
+
+    Some(value)
+}
+
+/// Decode the ranges of a character class, including its brackets, with
+/// single characters as ranges of one.
+///
+/// A `-` at either end of the class is the character itself. Return `None` if
+/// the class is malformed, or contains a Unicode property like `\p{L}`.
+pub fn unescape_class(literal: &str) -> Option<Vec<(char, char)>> {
+    let mut s = Scanner::new(literal.strip_prefix('[')?.strip_suffix(']')?);
+
+    let mut ranges = Vec::new();
+    while let Some(start) = class_char(&mut s)? {
+        if s.at('-') && s.after().len() > 1 {
+            s.eat();
+            let end = class_char(&mut s)??;
+            (start <= end).then_some(())?;
+            ranges.push((start, end));
+        } else {
+            ranges.push((start, start));
+        }
+    }
+
+    Some(ranges)
+}
+
+/// Decode the next character of a character class, or `Some(None)` at its
+/// end.
+fn class_char(s: &mut Scanner) -> Option<Option<char>> {
+    let Some(c) = s.eat() else { return Some(None) };
+    if c != '\\' {
+        return Some(Some(c));
+    }
+
+    let c = match s.eat()? {
+        | 'u' => {
+            s.eat_if('{').then_some(())?;
+            let hex = s.eat_until('}');
+            s.eat_if('}').then_some(())?;
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?)?
+        },
+        | 'x' => hex_escape(s)?,
+        | c => class_escape(c)?,
+    };
+    Some(Some(c))
+}
+
+/// The character of an escape sequence in a character class, like `\]`, but
+/// not the unicode escapes.
+fn class_escape(c: char) -> Option<char> {
+    Some(match c {
+        | 'n' => '\n',
+        | 'r' => '\r',
+        | 't' => '\t',
+        | 'b' => '\u{8}',
+        | 'f' => '\u{c}',
+        | '0' => '\0',
+        | '\\' | ']' | '[' | '-' | '^' | '"' => c,
+        | _ => return None,
+    })
+}
+
+/// Decode the two digits of a hex escape after its `\x`, like `41`, up to
+/// `7F` like in Rust.
+///
+/// Eat the digits even if they are out of range.
+fn hex_escape(s: &mut Scanner) -> Option<char> {
+    let start = s.cursor();
+    for _ in 0..2 {
+        s.eat_if(|c: char| c.is_ascii_hexdigit());
+    }
+    let hex = s.from(start);
+    let value = u8::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 2)?;
+    value.is_ascii().then_some(char::from(value))
+}
+
+/// The words starting a constraint, like `[not a keyword]`.
+const CONSTRAINT_KEYWORDS: &[&str] = &["not", "if", "unless", "where"];
+
+/// Check if the text after a `[` is a character class: it is closed on the
+/// same line, without unescaped whitespace or quotes, and contains a range, an
+/// escape, or no letters or digits, like `[a-z]`, `[\-]` or `[_]`.
+fn is_char_class(text: &str) -> bool {
+    let mut chars = text.chars();
+    let mut len = 0;
+    let mut explicit = false;
+    let mut word = false;
+    while let Some(c) = chars.next() {
+        match c {
+            | ']' => return len == 0 || explicit || !word,
+            | '\\' => {
+                chars.next();
+                explicit = true;
+            },
+            | '-' if len > 0 && !chars.as_str().starts_with(']') => {
+                explicit = true;
+            },
+            | '"' => return false,
+            | c if c.is_whitespace() => return false,
+            | c if c.is_alphanumeric() => word = true,
+            | _ => {},
+        }
+        len += 1;
+    }
+    false
+}
+
+/// Check if the text after a `[` starts a constraint, like `[not a keyword]`.
+fn is_constraint(text: &str) -> bool {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| !is_id_continue(c))
+        .unwrap_or(text.len());
+    CONSTRAINT_KEYWORDS.contains(&&text[..end])
+        && text[end..].starts_with(|c: char| c.is_whitespace() || c == ']')
+}
+
+/// Check if the text after a `[` holds an optional expression, like ISO
+/// EBNF's `[ "," item ]` or `[digit]`: any brackets which are neither a
+/// character class nor a constraint.
+fn is_optional(text: &str) -> bool {
+    !is_constraint(text) && !is_char_class(text)
+}
+
 /// Check if the character is a newline.
 #[inline]
 fn is_newline(c: char) -> bool {
@@ -268,16 +776,18 @@ fn is_newline(c: char) -> bool {
     )
 }
 
-/// Check if the character is a valid identifier start character.
+/// Check if the character is a valid identifier start character, from the
+/// Unicode `XID_Start` property or `_`.
 #[inline]
 fn is_id_start(c: char) -> bool {
-    c.is_ascii_alphabetic() || c == '_'
+    unicode_ident::is_xid_start(c) || c == '_'
 }
 
-/// Check if the character is a valid identifier continuation character.
+/// Check if the character is a valid identifier continuation character, from
+/// the Unicode `XID_Continue` property.
 #[inline]
 fn is_id_continue(c: char) -> bool {
-    c.is_ascii_alphanumeric() || c == '_'
+    unicode_ident::is_xid_continue(c)
 }
 
 #[cfg(test)]
@@ -287,7 +797,8 @@ mod tests {
     macro_rules! test_lexer {
         ($kind:ident, $next:expr, $more:expr) => {
             let next = $next.clone();
-            let node = Lexer::new(format!("{next}{}", $more).as_str()).next();
+            let node =
+                Lexer::new(format!("{next}{}", $more).as_str()).next_token();
             assert_eq!(node.kind(), SyntaxKind::$kind);
             assert_eq!(*node.span(), (0..next.len()));
         };
@@ -297,6 +808,27 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("a: [b-c] \"d\" $;");
+        let kinds = tokens.iter().map(SyntaxNode::kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [
+            SyntaxKind::Identifier,
+            SyntaxKind::Colon,
+            SyntaxKind::Whitespace,
+            SyntaxKind::CharClass,
+            SyntaxKind::Whitespace,
+            SyntaxKind::String,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Dollar,
+            SyntaxKind::SemiColon,
+        ]);
+        assert_eq!(*tokens[3].span(), 3..8);
+        assert!(tokenize("").is_empty());
+        assert!(tokenize("a ~~ *)").iter().all(|t| !t.erroneous()));
+        assert!(tokenize("a \"b").last().unwrap().erroneous());
+    }
+
     #[test]
     fn test_whitespace() {
         test_lexer!(Whitespace, "  \n  ", "123");
@@ -307,6 +839,13 @@ mod tests {
         test_lexer!(Comment, "// comment", "\n123");
     }
 
+    #[test]
+    fn test_doc_comment() {
+        test_lexer!(DocComment, "/// doc", "\n123");
+        test_lexer!(Comment, "//// comment", "\n123");
+        test_lexer!(Comment, "//! Section", "\n123");
+    }
+
     #[test]
     fn test_block_comment() {
         test_lexer!(Comment, "/* comment \n comment */", "123");
@@ -360,6 +899,34 @@ mod tests {
     #[test]
     fn test_identifier() {
         test_lexer!(Identifier, "abc_123_haha", "-123");
+        test_lexer!(Identifier, "règle_été", " ");
+        test_lexer!(Identifier, "выражение", ":");
+        test_lexer!(Identifier, "表达式", ";");
+        test_lexer!(Identifier, "εx", ";");
+    }
+
+    #[test]
+    fn test_empty() {
+        test_lexer!(Empty, "empty", " | a");
+        test_lexer!(Empty, "ε", ";");
+        test_lexer!(Identifier, "empty", " :");
+        test_lexer!(Identifier, "empty_list", ";");
+    }
+
+    #[test]
+    fn test_qualified_identifier() {
+        test_lexer!(Identifier, "lexer::Identifier", ":");
+        test_lexer!(Identifier, "a::b_1::c", " :: d");
+        test_lexer!(Identifier, "a", ":: b");
+        test_lexer!(Identifier, "a", ":::b");
+        test_lexer!(Identifier, "a", ".b");
+        test_lexer!(Identifier, "a", ". b");
+    }
+
+    #[test]
+    fn test_plus_eq() {
+        test_lexer!(PlusEq, "+=", " b");
+        test_lexer!(Plus, "+", " = b");
     }
 
     #[test]
@@ -382,26 +949,170 @@ mod tests {
         test_lexer!(Action, "-> hahahaha");
     }
 
+    #[test]
+    fn test_annotation() {
+        test_lexer!(Annotation, "@inline", "\nrule");
+        test_lexer!(Annotation, "@token(keyword)", " rule");
+        test_lexer!(Error, "@", "1");
+
+        let node = Lexer::new("@a(b\nc").next_token();
+        assert_eq!(node.kind(), SyntaxKind::Annotation);
+        assert!(node.erroneous());
+    }
+
+    #[test]
+    fn test_label() {
+        test_lexer!(Label, "#add", " expr");
+        test_lexer!(Label, "#add_2", "|");
+        test_lexer!(Error, "#", "1");
+    }
+
+    #[test]
+    fn test_insensitive() {
+        test_lexer!(Insensitive, "i\"select\"", "i");
+        test_lexer!(Identifier, "i", " \"select\"");
+        test_lexer!(Identifier, "if_", "\"a\"");
+
+        let node = Lexer::new("i\"select\"").next_token();
+        let kinds = node.children().map(SyntaxNode::kind).collect::<Vec<_>>();
+        assert_eq!(kinds, [SyntaxKind::Modifier, SyntaxKind::String]);
+        assert!(Lexer::new("i\"select").next_token().erroneous());
+    }
+
+    #[test]
+    fn test_raw_string() {
+        test_lexer!(RawString, r#"r"\d+""#, "a");
+        test_lexer!(RawString, r###"r#"a "b" c"#"###, "\"#");
+        test_lexer!(Identifier, "r", " \"a\"");
+        test_lexer!(Identifier, "r", "#a");
+        assert!(Lexer::new(r###"r#"a""###).next_token().erroneous());
+
+        assert_eq!(unescape(r#"r"\d+""#).as_deref(), Some(r"\d+"));
+        assert_eq!(unescape(r###"r#"a "b""#"###).as_deref(), Some(r#"a "b""#));
+        assert_eq!(unescape(r##"r#""##), None);
+    }
+
+    #[test]
+    fn test_hex_escape() {
+        test_lexer!(String, r#""\x41\0""#, "a");
+        test_lexer!(CharClass, r"[\x00-\x1F\0]", "a");
+        assert_eq!(unescape(r#""\x41\x7f\0""#).as_deref(), Some("A\x7f\0"));
+        assert_eq!(unescape_class(r"[\x30-\x39]"), Some(vec![('0', '9')]));
+
+        for (code, message) in [
+            (r#""\x4""#, "invalid hex escape"),
+            (r#""\xg1""#, "invalid hex escape"),
+            (r#""\x80""#, "hex escape out of range"),
+            (r"[\xFF]", "hex escape out of range"),
+        ] {
+            let node = Lexer::new(code).next_token();
+            let error = node.as_error().unwrap();
+            assert_eq!(error.message, message, "{code}");
+        }
+        assert_eq!(unescape(r#""\x80""#), None);
+    }
+
     #[test]
     fn test_param() {
-        test_lexer!(Param, "[param]", "123");
+        test_lexer!(Param, "[not param]", "123");
     }
 
     #[test]
     fn test_symbol() {
         for symbol in [
             ":", ";", "(", ")", "{", "}", ",", "|", "~", ".", "?", "*", "+",
-            "..", "?=", "?!", "?<=", "?<!", "?",
+            "%", "-", "^", "$", "]", "..", "..=", "..<", "?=", "?!", "?<=",
+            "?<!", "?", "&", "!",
         ] {
-            let node = Lexer::new(format!("{symbol}abc123").as_str()).next();
+            let node =
+                Lexer::new(format!("{symbol}abc123").as_str()).next_token();
             assert!(node.kind().is_operator());
             assert_eq!(*node.span(), 0..symbol.len());
             assert_eq!(node.text(), symbol);
         }
     }
 
+    #[test]
+    fn test_optional() {
+        test_lexer!(LeftBracket, "[", " \"]\" a ]");
+        test_lexer!(Param, "[ not a keyword ]");
+        test_lexer!(LeftBracket, "[", "\"x\" a]");
+        test_lexer!(LeftBracket, "[", "digit]");
+
+        // Brackets attached to an identifier are its argument.
+        let mut lexer = Lexer::new("a[ \"x\" ]");
+        lexer.next_token();
+        assert_eq!(lexer.next_token().kind(), SyntaxKind::Param);
+    }
+
+    #[test]
+    fn test_char_class() {
+        test_lexer!(CharClass, "[a-z0-9_]", "*");
+        test_lexer!(CharClass, r"[\]\u{41}]", "]");
+        test_lexer!(CharClass, "[_]", "[a-z]");
+        test_lexer!(LeftBracket, "[", "note]");
+        test_lexer!(Param, "[not a-keyword]");
+    }
+
+    #[test]
+    fn test_unicode_property() {
+        test_lexer!(String, r#""\p{L}\P{Script=Greek}""#, "a");
+        test_lexer!(CharClass, r"[\p{Lu}_0-9]", " ");
+
+        for (code, message) in [
+            (r#""\p{Foo}""#, "unknown unicode property `Foo`"),
+            (r#""\pL""#, "invalid unicode property escape"),
+            (r#""\p{L""#, "unclosed unicode property escape"),
+            (r"[\p{whitespace}]", "unknown unicode property `whitespace`"),
+        ] {
+            let node = Lexer::new(code).next_token();
+            let error = node.as_error().unwrap();
+            assert_eq!(error.message, message, "{code}");
+        }
+
+        let node = Lexer::new(r#""\p{white space}""#).next_token();
+        let error = node.as_error().unwrap();
+        assert_eq!(error.hints[0], "did you mean `White_Space`?");
+        assert_eq!(unescape(r#""\p{L}""#), None);
+        assert_eq!(unescape_class(r"[\p{L}]"), None);
+    }
+
+    #[test]
+    fn test_char_class_invalid() {
+        for class in ["[a-z", "[a-z\n]", "[]", "[z-a]", r"[\a]", r"[\u{x}]"] {
+            let node = Lexer::new(class).char_class(0);
+            assert!(node.erroneous(), "{class}");
+        }
+    }
+
+    #[test]
+    fn test_unescape_class() {
+        assert_eq!(
+            unescape_class(r"[-a-c\n\u{41}-]"),
+            Some(vec![
+                ('-', '-'),
+                ('a', 'c'),
+                ('\n', '\n'),
+                ('A', 'A'),
+                ('-', '-')
+            ])
+        );
+        assert_eq!(unescape_class("[z-a]"), None);
+        assert_eq!(unescape_class(r"[\u0041]"), None);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(
+            unescape(r#""a\n\"\u{41}\u0042""#).as_deref(),
+            Some("a\n\"AB")
+        );
+        assert_eq!(unescape(r#""\u{110000}""#), None);
+        assert_eq!(unescape(r#""\a""#), None);
+    }
+
     #[test]
     fn test_unexpected() {
-        test_lexer!(Error, "%");
+        test_lexer!(Error, "`");
     }
 }
@@ -1,10 +1,38 @@
+mod action;
+#[cfg(feature = "arena")]
+mod arena;
+pub mod ast;
+mod builder;
+mod class;
+mod cursor;
+mod format;
 mod kind;
 mod lexer;
 mod node;
 mod parser;
+mod property;
+mod repeat;
+mod signature;
+pub mod span;
+mod trivia;
+mod visit;
 
 pub use self::{
+    builder::TreeBuilder,
+    class::{NegatedClass, range_bounds},
+    cursor::SyntaxCursor,
+    format::format,
     kind::SyntaxKind,
-    node::{SyntaxError, SyntaxNode},
-    parser::parse,
+    lexer::{Lexer, tokenize, unescape, unescape_class},
+    node::{Diagnostic, Severity, SyntaxError, SyntaxNode},
+    parser::{
+        MAX_DEPTH, ParseOptions, parameterized_rules, parse,
+        parse_with_diagnostics, parse_with_max_depth, parse_with_options,
+    },
+    property::is_unicode_property,
+    repeat::Repeat,
+    signature::signature,
+    span::{SourceMap, parse_book_blocks},
+    trivia::attach_trivia,
+    visit::{Visitor, walk},
 };
@@ -1,13 +1,20 @@
 use crate::SyntaxKind;
+#[cfg(feature = "arena")]
+use crate::arena::ArenaNode;
 use ecow::{EcoString, EcoVec};
 use std::{
-    fmt::{Debug, Formatter},
+    fmt::{Debug, Display, Formatter},
+    hash::{Hash, Hasher},
     ops::Range,
 };
 
 /// A node in the untyped syntax tree.
-#[derive(Clone, Eq, PartialEq, Hash)]
-pub struct SyntaxNode(Repr);
+///
+/// With the `arena` feature, the parser allocates the inner nodes of a tree in
+/// an arena shared by the tree.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SyntaxNode(pub(crate) Repr);
 
 impl SyntaxNode {
     /// Create a new leaf node.
@@ -42,6 +49,8 @@ impl SyntaxNode {
         match &self.0 {
             | Repr::Leaf(node) => node.kind,
             | Repr::Inner(node) => node.kind,
+            #[cfg(feature = "arena")]
+            | Repr::Arena(node) => node.kind(),
             | Repr::Error(_) => SyntaxKind::Error,
         }
     }
@@ -52,6 +61,8 @@ impl SyntaxNode {
         match &self.0 {
             | Repr::Leaf(node) => &node.text,
             | Repr::Inner(_) => &EMPTY,
+            #[cfg(feature = "arena")]
+            | Repr::Arena(_) => &EMPTY,
             | Repr::Error(node) => &node.text,
         }
     }
@@ -61,6 +72,8 @@ impl SyntaxNode {
         match &self.0 {
             | Repr::Leaf(node) => &node.span,
             | Repr::Inner(node) => &node.span,
+            #[cfg(feature = "arena")]
+            | Repr::Arena(node) => node.span(),
             | Repr::Error(node) => &node.span,
         }
     }
@@ -70,15 +83,59 @@ impl SyntaxNode {
         match &self.0 {
             | Repr::Leaf(_) => [].iter(),
             | Repr::Inner(node) => node.children.iter(),
+            #[cfg(feature = "arena")]
+            | Repr::Arena(node) => node.children().iter(),
             | Repr::Error(_) => [].iter(),
         }
     }
 
+    /// This node and its descendants, in document order.
+    pub fn descendants(&self) -> impl Iterator<Item = &SyntaxNode> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            stack.extend(node.children().rev());
+            Some(node)
+        })
+    }
+
+    /// The first child of the given kind.
+    pub fn find_by_kind(&self, kind: SyntaxKind) -> Option<&SyntaxNode> {
+        self.children().find(|n| n.kind() == kind)
+    }
+
+    /// The deepest node covering the byte offset, usually a token.
+    pub fn node_at_offset(&self, offset: usize) -> Option<&SyntaxNode> {
+        if !self.span().contains(&offset) {
+            return None;
+        }
+        Some(
+            self.children()
+                .find_map(|child| child.node_at_offset(offset))
+                .unwrap_or(self),
+        )
+    }
+
+    /// Transform the children of this node in place, then update whether it
+    /// contains errors and its span.
+    ///
+    /// Call it again in `f` to transform the descendants.
+    pub fn map_children(&mut self, f: impl FnMut(&mut SyntaxNode)) {
+        self.make_heap();
+        if let Repr::Inner(node) = &mut self.0 {
+            node.children.iter_mut().for_each(f);
+            let children = std::mem::take(&mut node.children);
+            *node = InnerNode::new(node.kind, children);
+        }
+    }
+
     /// Whether this node or its children contains an error.
     pub fn erroneous(&self) -> bool {
         match &self.0 {
             | Repr::Leaf(_) => false,
             | Repr::Inner(node) => node.erroneous,
+            #[cfg(feature = "arena")]
+            | Repr::Arena(node) => node.erroneous(),
             | Repr::Error(_) => true,
         }
     }
@@ -98,13 +155,38 @@ impl SyntaxNode {
             None
         }
     }
+
+    /// The errors in this node and its children, in document order.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        self.collect_diagnostics(&mut diagnostics);
+        diagnostics
+    }
+
+    fn collect_diagnostics(&self, diagnostics: &mut Vec<Diagnostic>) {
+        if let Some(error) = self.as_error() {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                message: error.message.clone(),
+                hints: error.hints.clone(),
+                span: self.span().clone(),
+            });
+        } else if self.erroneous() {
+            for child in self.children() {
+                child.collect_diagnostics(diagnostics);
+            }
+        }
+    }
 }
 
 impl SyntaxNode {
     pub fn convert_kind(&mut self, kind: SyntaxKind) {
+        self.make_heap();
         match &mut self.0 {
             | Repr::Leaf(node) => node.kind = kind,
             | Repr::Inner(node) => node.kind = kind,
+            #[cfg(feature = "arena")]
+            | Repr::Arena(_) => unreachable!(),
             | Repr::Error(_) => {},
         }
     }
@@ -119,17 +201,85 @@ impl SyntaxNode {
             error: SyntaxError::new(message),
         });
     }
+
+    /// Copy an inner node out of its arena before changing it, since the
+    /// arena is shared by the whole tree.
+    fn make_heap(&mut self) {
+        #[cfg(feature = "arena")]
+        if let Repr::Arena(node) = &self.0 {
+            self.0 = Repr::Inner(node.to_heap());
+        }
+    }
+}
+
+/// Compare nodes by content, so that an inner node in an arena equals the
+/// same node on the heap.
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            | (Repr::Leaf(a), Repr::Leaf(b)) => a == b,
+            | (Repr::Inner(a), Repr::Inner(b)) => a == b,
+            | (Repr::Error(a), Repr::Error(b)) => a == b,
+            | (Repr::Leaf(_) | Repr::Error(_), _)
+            | (_, Repr::Leaf(_) | Repr::Error(_)) => false,
+            #[cfg(feature = "arena")]
+            | _ => {
+                self.kind() == other.kind()
+                    && self.span() == other.span()
+                    && self.erroneous() == other.erroneous()
+                    && self.children().eq(other.children())
+            },
+        }
+    }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
-enum Repr {
+impl Eq for SyntaxNode {}
+
+impl Hash for SyntaxNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            | Repr::Leaf(node) => {
+                state.write_u8(0);
+                node.hash(state);
+            },
+            | Repr::Error(node) => {
+                state.write_u8(2);
+                node.hash(state);
+            },
+            | _ => {
+                state.write_u8(1);
+                self.kind().hash(state);
+                self.span().hash(state);
+                self.erroneous().hash(state);
+                self.children().as_slice().hash(state);
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) enum Repr {
     Leaf(LeafNode),
     Inner(InnerNode),
+    /// An inner node allocated by the parser in the arena of its tree,
+    /// serialized like one on the heap.
+    #[cfg(feature = "arena")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            rename = "Inner",
+            serialize_with = "ArenaNode::serialize",
+            skip_deserializing
+        )
+    )]
+    Arena(ArenaNode),
     Error(ErrorNode),
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
-struct LeafNode {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct LeafNode {
     kind: SyntaxKind,
     text: EcoString,
     span: Range<usize>,
@@ -151,12 +301,14 @@ impl LeafNode {
     }
 }
 
+/// An inner node on the heap, owning its children.
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
-struct InnerNode {
-    kind: SyntaxKind,
-    span: Range<usize>,
-    erroneous: bool,
-    children: Vec<SyntaxNode>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct InnerNode {
+    pub(crate) kind: SyntaxKind,
+    pub(crate) span: Range<usize>,
+    pub(crate) erroneous: bool,
+    pub(crate) children: Vec<SyntaxNode>,
 }
 
 impl InnerNode {
@@ -177,7 +329,8 @@ impl InnerNode {
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
-struct ErrorNode {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct ErrorNode {
     text: EcoString,
     span: Range<usize>,
     error: SyntaxError,
@@ -185,6 +338,7 @@ struct ErrorNode {
 
 /// A syntactical error.
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SyntaxError {
     pub message: EcoString,
     pub hints: EcoVec<EcoString>,
@@ -205,6 +359,24 @@ impl SyntaxError {
     }
 }
 
+/// How severe a diagnostic is.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem in the source, located by its span.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: EcoString,
+    pub hints: EcoVec<EcoString>,
+    pub span: Range<usize>,
+}
+
 impl Debug for SyntaxNode {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match &self.0 {
@@ -218,6 +390,12 @@ impl Debug for SyntaxNode {
                 .field("kind", &inner.kind)
                 .field("children", &inner.children)
                 .finish(),
+            #[cfg(feature = "arena")]
+            | Repr::Arena(inner) => f
+                .debug_struct("Inner")
+                .field("kind", &inner.kind())
+                .field("children", &inner.children())
+                .finish(),
             | Repr::Error(error) => f
                 .debug_struct("Error")
                 .field("error", &error.error)
@@ -225,3 +403,144 @@ impl Debug for SyntaxNode {
         }
     }
 }
+
+/// Print the tree with one node per line, indented by depth, like
+/// `Identifier "a" @0..1`.
+impl Display for SyntaxNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.dump(f, 0)
+    }
+}
+
+impl SyntaxNode {
+    fn dump(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "{:indent$}{:?}", "", self.kind(), indent = depth * 2)?;
+        if let Repr::Leaf(_) | Repr::Error(_) = &self.0 {
+            write!(f, " {:?}", self.text().as_str())?;
+        }
+        write!(f, " @{:?}", self.span())?;
+        if let Some(error) = self.as_error() {
+            write!(f, " {}", error.message)?;
+        }
+        writeln!(f)?;
+        for child in self.children() {
+            child.dump(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_display() {
+        let root = parse("a: \"b\" c\n;\nd");
+        assert_eq!(
+            root.to_string(),
+            concat!(
+                "Root @0..12\n",
+                "  Rule @0..10\n",
+                "    Identifier \"a\" @0..1\n",
+                "    Colon \":\" @1..2\n",
+                "    Definition @2..9\n",
+                "      Whitespace \" \" @2..3\n",
+                "      String \"\\\"b\\\"\" @3..6\n",
+                "      Whitespace \" \" @6..7\n",
+                "      Identifier \"c\" @7..8\n",
+                "      Whitespace \"\\n\" @8..9\n",
+                "    SemiColon \";\" @9..10\n",
+                "  Whitespace \"\\n\" @10..11\n",
+                "  Rule @11..12\n",
+                "    Identifier \"d\" @11..12\n",
+                "    Error \"\" @12..12 expected `:`, found end\n",
+                "    Definition @0..0\n",
+                "    Error \"\" @12..12 expected `;`, found end\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_queries() {
+        let root = parse("a: b (c);");
+        let kinds = root.descendants().map(|n| n.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, [
+            SyntaxKind::Root,
+            SyntaxKind::Rule,
+            SyntaxKind::Identifier,
+            SyntaxKind::Colon,
+            SyntaxKind::Definition,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Identifier,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Group,
+            SyntaxKind::LeftParen,
+            SyntaxKind::Identifier,
+            SyntaxKind::RightParen,
+            SyntaxKind::SemiColon,
+        ]);
+
+        let rule = root.find_by_kind(SyntaxKind::Rule).unwrap();
+        assert_eq!(
+            rule.find_by_kind(SyntaxKind::Identifier).unwrap().text(),
+            "a"
+        );
+        assert!(rule.find_by_kind(SyntaxKind::Group).is_none());
+
+        let node = root.node_at_offset(6).unwrap();
+        assert_eq!(
+            (node.kind(), node.text().as_str()),
+            (SyntaxKind::Identifier, "c")
+        );
+        assert_eq!(
+            root.node_at_offset(5).unwrap().kind(),
+            SyntaxKind::LeftParen
+        );
+        assert!(root.node_at_offset(9).is_none());
+    }
+
+    #[test]
+    fn test_map_children() {
+        fn hide_b(node: &mut SyntaxNode) {
+            if node.text() == "b" {
+                node.convert_to_error("b is hidden");
+            }
+            node.map_children(hide_b);
+        }
+
+        let mut root = parse("a: b;\nc: d;");
+        root.map_children(hide_b);
+        assert!(root.erroneous());
+        assert_eq!(root.diagnostics()[0].message, "b is hidden");
+        assert!(!root.children().nth(2).unwrap().erroneous());
+
+        // The span of a rule follows its children.
+        root.map_children(|rule| {
+            rule.map_children(|child| {
+                if *child.span() == (4..5) {
+                    *child =
+                        SyntaxNode::leaf(SyntaxKind::SemiColon, ";;", 4..6);
+                }
+            });
+        });
+        assert_eq!(*root.children().next().unwrap().span(), 0..6);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let root = parse("a: b | ;\nc: d");
+        let json = serde_json::to_string(&root).unwrap();
+        let back: SyntaxNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, root);
+        assert_eq!(back.diagnostics(), root.diagnostics());
+
+        let leaf = SyntaxNode::leaf(SyntaxKind::Identifier, "a", 0..1);
+        assert_eq!(
+            serde_json::to_string(&leaf).unwrap(),
+            r#"{"Leaf":{"kind":"Identifier","text":"a","span":{"start":0,"end":1}}}"#
+        );
+    }
+}
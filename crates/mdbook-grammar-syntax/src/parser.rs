@@ -1,10 +1,62 @@
-use crate::{SyntaxKind, SyntaxNode, lexer::Lexer};
+#[cfg(feature = "arena")]
+use crate::arena::Arena;
+use crate::{
+    Diagnostic, SyntaxError, SyntaxKind, SyntaxNode,
+    lexer::{Lexer, unescape},
+};
 use ecow::{EcoString, eco_format};
-use std::ops::{Index, IndexMut};
+#[cfg(feature = "arena")]
+use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    ops::{Index, IndexMut},
+};
+
+/// How deeply items can be nested by default, like groups in groups, before
+/// the rest is reported as an error.
+pub const MAX_DEPTH: usize = 128;
 
 /// Parse a grammar rule from the input string.
 pub fn parse(input: &str) -> SyntaxNode {
-    let mut p = Parser::new(input);
+    parse_with_options(input, &ParseOptions::default())
+}
+
+/// Parse a grammar, reporting the items nested deeper than `max_depth` as
+/// errors instead of recursing into them.
+pub fn parse_with_max_depth(input: &str, max_depth: usize) -> SyntaxNode {
+    parse_with_options(input, &ParseOptions {
+        max_depth,
+        ..ParseOptions::default()
+    })
+}
+
+/// How to parse a grammar.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// How deeply items can be nested, like groups in groups, before the rest
+    /// is reported as an error.
+    pub max_depth: usize,
+    /// The rules defined with parameters outside of the grammar, like in
+    /// other code blocks of a book.
+    ///
+    /// A reference directly followed by `(` takes arguments only if its rule
+    /// is one of these or is defined with parameters in the grammar itself,
+    /// so `a(b)*` repeats the group `(b)` after `a` otherwise.
+    pub parameterized: HashSet<EcoString>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            parameterized: HashSet::new(),
+        }
+    }
+}
+
+/// Parse a grammar with the given options.
+pub fn parse_with_options(input: &str, options: &ParseOptions) -> SyntaxNode {
+    let mut p = Parser::new(input, options);
     loop {
         p.eat_while(SyntaxKind::is_trivia);
 
@@ -12,33 +64,300 @@ pub fn parse(input: &str) -> SyntaxNode {
             break;
         }
 
-        rule(&mut p);
+        let start = p.doc_comments();
+        if p.eat_if(SyntaxKind::Percent) {
+            pragma(&mut p, start);
+        } else {
+            rule(&mut p, start);
+        }
     }
 
     p.finish(SyntaxKind::Root)
 }
 
-/// Parse the next rule.
-fn rule(p: &mut Parser<'_>) {
-    let start = p.marker();
+/// Find the names of the rules defined with parameters in a grammar, like
+/// `list` in `list(item): item ("," item)*;`, without parsing it.
+pub fn parameterized_rules(input: &str) -> HashSet<EcoString> {
+    let mut names = HashSet::new();
+    // The identifier before each open parenthesis, and the one before the
+    // parenthesis just closed, waiting for a `:` to make it a rule header.
+    let mut parens: Vec<Option<EcoString>> = Vec::new();
+    let mut closed = None;
+    let mut previous = None;
+
+    for token in Lexer::new(input) {
+        let kind = token.kind();
+        if kind.is_trivia() {
+            continue;
+        }
+        match kind {
+            | SyntaxKind::LeftParen => parens.push(previous.take()),
+            | SyntaxKind::RightParen => closed = parens.pop().flatten(),
+            | SyntaxKind::Colon | SyntaxKind::PlusEq => {
+                names.extend(closed.take());
+            },
+            | _ => {},
+        }
+        if kind != SyntaxKind::RightParen {
+            closed = None;
+        }
+        previous =
+            (kind == SyntaxKind::Identifier).then(|| token.text().clone());
+    }
 
-    p.expect(SyntaxKind::Identifier);
-    p.eat_if(SyntaxKind::Param);
-    p.expect(SyntaxKind::Colon);
+    names
+}
+
+/// Parse a grammar, also returning the syntax errors found in it.
+pub fn parse_with_diagnostics(input: &str) -> (SyntaxNode, Vec<Diagnostic>) {
+    let root = parse(input);
+    let diagnostics = root.diagnostics();
+    (root, diagnostics)
+}
+
+/// Parse the next rule, wrapping the nodes from `start`.
+fn rule(p: &mut Parser<'_>, start: Marker) {
+    while p.eat_if(SyntaxKind::Annotation) {}
+    if !p.expect(SyntaxKind::Identifier) {
+        recover(p);
+        p.wrap(start, SyntaxKind::Rule);
+        return;
+    }
+    // `fragment a: b;` defines a rule only composing tokens, while a rule may
+    // still be named `fragment`.
+    let name = p.marker().prev();
+    if p[name].kind() == SyntaxKind::Identifier
+        && p[name].text() == "fragment"
+        && p.eat_if(SyntaxKind::Identifier)
+    {
+        p[name].convert_kind(SyntaxKind::Fragment);
+    }
+    // References to `empty` are the keyword, so such a rule is unreachable.
+    let name = p.marker().prev();
+    if p[name].text() == "empty" {
+        p[name].convert_to_error("`empty` is a keyword, not a rule name");
+        p[name].hints("consider renaming the rule, like `empty_rule`");
+    }
+    let marker = p.marker();
+    if p.eat_if(SyntaxKind::LeftParen) {
+        params(p, marker);
+    } else {
+        p.eat_if(SyntaxKind::Param);
+    }
+    // `a += b;` adds alternatives to a rule defined elsewhere.
+    if !p.eat_if(SyntaxKind::PlusEq) {
+        p.expect(SyntaxKind::Colon);
+    }
 
     let marker = p.marker();
     expression(p);
     p.wrap(marker, SyntaxKind::Definition);
 
-    p.expect(SyntaxKind::SemiColon);
-    p.hint("consider ending the rule with `;`");
+    if !p.expect(SyntaxKind::SemiColon) {
+        p.hint("consider ending the rule with `;`");
+        recover(p);
+    }
 
     p.wrap(start, SyntaxKind::Rule);
 }
 
+/// Skip the tokens after an error up to the end of the rule, at its `;` or
+/// before the next rule, merging them into the error so that they are not
+/// parsed as rules of their own.
+fn recover(p: &mut Parser<'_>) {
+    let error = p.marker().prev();
+    if p[error].text().is_empty() {
+        return;
+    }
+
+    let semicolon = loop {
+        if p.at_rule_header() {
+            break false;
+        }
+        match p.eat() {
+            | SyntaxKind::SemiColon => {
+                p.uneat();
+                break true;
+            },
+            | SyntaxKind::End => {
+                p.uneat();
+                break false;
+            },
+            | _ => {},
+        }
+    };
+    // Trivia before the end of the rule is left outside of the error.
+    p.rewind_trivia();
+
+    if p.marker().prev() != error {
+        let message = p[error].as_error().cloned().unwrap();
+        p.merge_error(error, message);
+    }
+    if semicolon {
+        p.eat();
+    }
+}
+
+/// Parse a pragma after its `%`, like `%start program;`, wrapping the nodes
+/// from `start`.
+fn pragma(p: &mut Parser<'_>, start: Marker) {
+    if p.eat_attached(SyntaxKind::Identifier) {
+        let name = p.marker().prev();
+        p[name].convert_kind(SyntaxKind::PragmaName);
+        match p[name].text().as_str() {
+            | "start" => {
+                p.expect(SyntaxKind::Identifier);
+                p.hint("the start pragma names a rule, like `%start program;`");
+            },
+            | "skip" => {
+                p.expect(SyntaxKind::Identifier);
+                p.hint(
+                    "the skip pragma names rules, like `%skip whitespace \
+                     comment;`",
+                );
+                while p.eat_if(SyntaxKind::Identifier) {}
+            },
+            | "import" => {
+                p.expect(SyntaxKind::String);
+                p.hint(
+                    "the import pragma names a file, like `%import \
+                     \"lexer.syntax\";`",
+                );
+            },
+            | text => {
+                let message = eco_format!("unknown pragma `%{text}`");
+                p[name].convert_to_error(message);
+                p[name].hints(
+                    "the known pragmas are `%start`, `%skip` and `%import`",
+                );
+                while p.eat_if(SyntaxKind::Identifier) {}
+            },
+        }
+    } else {
+        p.error("expected a pragma name after `%`");
+        p.hint("pragmas are like `%start program;`");
+    }
+
+    p.expect(SyntaxKind::SemiColon);
+    p.hint("consider ending the pragma with `;`");
+
+    p.wrap(start, SyntaxKind::Pragma);
+}
+
+/// Eat the name of a group and its `:`, like `sub:` in `(sub: "a" | "b")`,
+/// or nothing if the group is not named.
+fn group_name(p: &mut Parser<'_>) -> bool {
+    let marker = p.marker();
+    if p.eat_if(SyntaxKind::Identifier) && p.eat_if(SyntaxKind::Colon) {
+        return true;
+    }
+    if let Some(node) = p.nodes.get(marker.0) {
+        p.lexer.jump(node.span().start);
+        p.nodes.truncate(marker.0);
+    }
+    false
+}
+
+/// Parse the parameters of a rule, like `(item, separator)`, after its `(`.
+fn params(p: &mut Parser<'_>, start: Marker) {
+    loop {
+        p.expect(SyntaxKind::Identifier);
+        p.hint("parameters are names, like `list(item)`");
+        if !p.eat_if(SyntaxKind::Comma) {
+            break;
+        }
+    }
+
+    p.expect(SyntaxKind::RightParen);
+    p.hint("consider closing the parameters with `)`");
+    p.wrap(start, SyntaxKind::Param);
+}
+
+/// Parse the arguments of a rule reference after its `(`, each being an
+/// expression.
+fn arguments(p: &mut Parser<'_>) {
+    loop {
+        let start = p.marker();
+        expression(p);
+        p.wrap(start, SyntaxKind::Argument);
+        if !p.eat_if(SyntaxKind::Comma) {
+            break;
+        }
+    }
+
+    p.expect(SyntaxKind::RightParen);
+    p.hint("consider closing the arguments with `)`");
+}
+
 /// Parse an expression greedily.
 fn expression(p: &mut Parser<'_>) {
-    while item(p, None) {}
+    loop {
+        // A missing `;` must not swallow the next rule.
+        if p.at_rule_header() {
+            p.rewind_trivia();
+            break;
+        }
+        let start = p.marker();
+        if !item(p, None) {
+            break;
+        }
+        // Each operator of a chain nests the items before it one level
+        // deeper, so it counts against the depth of its right side.
+        let depth = p.depth;
+        loop {
+            if p.eat_if(SyntaxKind::Percent) {
+                p.depth += 1;
+                binary(p, start, SyntaxKind::Separated);
+            } else if p.eat_if(SyntaxKind::Minus) {
+                p.depth += 1;
+                binary(p, start, SyntaxKind::Difference);
+            } else {
+                break;
+            }
+        }
+        p.depth = depth;
+    }
+}
+
+/// Parse the right side of a binary operator just eaten, like `","` in
+/// `arg % ","` or `"\""` in `char - "\""`, wrapping the expression from
+/// `start` into `kind`.
+fn binary(p: &mut Parser<'_>, start: Marker, kind: SyntaxKind) {
+    let operator = p.marker().prev();
+    let name = p[operator].kind().name();
+    let operand = |kind| !matches!(kind, SyntaxKind::Bar | SyntaxKind::Action);
+
+    let before = p.nodes[start.0..operator.0]
+        .iter()
+        .rfind(|n| !n.kind().is_trivia())
+        .map(SyntaxNode::kind);
+    if !before.is_some_and(operand) {
+        p[operator]
+            .convert_to_error(eco_format!("expected an item before {name}"));
+        return;
+    }
+
+    if !item(p, None) || !operand(p[p.last_significant()].kind()) {
+        let (message, hint) = match kind {
+            | SyntaxKind::Separated => (
+                "expected a separator after `%`",
+                "a list separates items, like `arg % \",\"`",
+            ),
+            | _ => (
+                "expected an item to exclude after `-`",
+                "a difference excludes the matches of an item, like `char - \
+                 \"\\\"\"`",
+            ),
+        };
+        p[operator].convert_to_error(message);
+        p[operator].hints(hint);
+        return;
+    }
+
+    // Trivia after the right side is left outside of the expression.
+    let trivia = p.nodes.split_off(p.last_significant().0 + 1);
+    p.wrap(start, kind);
+    p.nodes.extend(trivia);
 }
 
 /// Parse the next item in an expression.
@@ -48,28 +367,125 @@ fn expression(p: &mut Parser<'_>) {
 /// If `wrapper` is assigned, wrap the item from the given marker into the given
 /// kind before detecting repeating indicator.
 fn item(p: &mut Parser, wrapper: Option<(Marker, SyntaxKind)>) -> bool {
+    if p.depth > p.max_depth {
+        return too_deep(p);
+    }
+    p.depth += 1;
+    let eaten = nested_item(p, wrapper);
+    p.depth -= 1;
+    eaten
+}
+
+/// Skip the rest of a group nested too deeply into a single error, instead of
+/// recursing into its items.
+fn too_deep(p: &mut Parser<'_>) -> bool {
+    p.eat_while(SyntaxKind::is_trivia);
+    let start = p.marker();
+    let mut open = 0_usize;
+    loop {
+        match p.eat() {
+            | SyntaxKind::LeftParen | SyntaxKind::LeftBracket => open += 1,
+            | SyntaxKind::RightParen | SyntaxKind::RightBracket if open > 0 => {
+                open -= 1;
+            },
+            | SyntaxKind::RightParen
+            | SyntaxKind::RightBracket
+            | SyntaxKind::SemiColon
+            | SyntaxKind::End => {
+                p.uneat();
+                break;
+            },
+            | _ => {},
+        }
+    }
+    p.rewind_trivia();
+    if p.marker() == start {
+        return false;
+    }
+
+    let mut error = SyntaxError::new("item is nested too deeply");
+    error.hint(eco_format!(
+        "items can be nested at most {} deep",
+        p.max_depth
+    ));
+    p.merge_error(start, error);
+    true
+}
+
+/// Parse the next item, once the depth is checked.
+fn nested_item(p: &mut Parser, wrapper: Option<(Marker, SyntaxKind)>) -> bool {
     let start = p.marker();
 
     match p.eat() {
         | SyntaxKind::Meta
+        | SyntaxKind::Empty
+        | SyntaxKind::RawString
+        | SyntaxKind::Insensitive
+        | SyntaxKind::CharClass
         | SyntaxKind::Dot
+        | SyntaxKind::Caret
+        | SyntaxKind::Dollar
         | SyntaxKind::Bar
         | SyntaxKind::Action => {},
 
         | SyntaxKind::Identifier => {
-            if p.eat_if(SyntaxKind::Param) {
-                p.wrap(start, SyntaxKind::Reference);
+            // A bracket after whitespace is a constraint, and a parenthesis
+            // a group, not arguments.
+            let name = p.last_significant();
+            let paren = p.marker();
+            if p.eat_attached([SyntaxKind::Param, SyntaxKind::LeftParen]) {
+                if p.kind() == SyntaxKind::Param {
+                    p.wrap(start, SyntaxKind::Reference);
+                } else if p.eat_if(SyntaxKind::is_looking) {
+                    // `a(?=b)` looks around after `a`.
+                    expression(p);
+                    p.expect(SyntaxKind::RightParen);
+                    p.wrap(paren, SyntaxKind::Looking);
+                } else if p.parameterized.contains(p[name].text()) {
+                    arguments(p);
+                    p.wrap(start, SyntaxKind::Reference);
+                } else {
+                    // `a(b)*` repeats the group `(b)` after a rule without
+                    // parameters.
+                    p.uneat();
+                }
             }
         },
 
         | SyntaxKind::String => {
-            if p.eat_if(SyntaxKind::Dots) {
-                p.expect(SyntaxKind::String);
-                p.hint("`..` can only connect two string literals");
+            if p.eat_if([
+                SyntaxKind::Dots,
+                SyntaxKind::DotsEq,
+                SyntaxKind::DotsLt,
+            ]) {
+                let inclusive = p.kind() != SyntaxKind::DotsLt;
+                if p.expect(SyntaxKind::String) {
+                    p.expect_char();
+                } else {
+                    p.hint("`..` can only connect two string literals");
+                }
+
+                let first = (start.0..p.nodes.len())
+                    .find(|&i| p.nodes[i].kind() == SyntaxKind::String);
+                if let Some(first) = first {
+                    p.expect_char_at(Marker(first));
+                }
+                p.expect_range(start, inclusive);
+
                 p.wrap(start, SyntaxKind::Range);
             }
         },
 
+        | SyntaxKind::Param => {
+            let mut error =
+                SyntaxError::new("expected an item before the constraint");
+            error.hint(
+                "a constraint follows the item it constrains, like \
+                 `identifier [not a keyword]`",
+            );
+            p.merge_error(start, error);
+        },
+
         | SyntaxKind::Tilde => {
             if !item(p, Some((start, SyntaxKind::Converse))) {
                 p.unexpected();
@@ -80,17 +496,54 @@ fn item(p: &mut Parser, wrapper: Option<(Marker, SyntaxKind)>) -> bool {
             }
         },
 
+        | SyntaxKind::LookAheadPos | SyntaxKind::LookAheadNeg => {
+            // `&a` and `!a` are shorthands for `(?=a)` and `(?!a)`, applying
+            // to the item with its repetition, like in PEGs.
+            if !item(p, None) {
+                p.unexpected();
+                p.hint("a predicate applies to an item, like `!\"}\" char`");
+            }
+            p.wrap(start, SyntaxKind::Looking);
+            return true;
+        },
+
         | SyntaxKind::LeftParen => {
             let kind = if p.eat_if(SyntaxKind::is_looking) {
                 SyntaxKind::Looking
+            } else if group_name(p) {
+                SyntaxKind::NamedGroup
             } else {
                 SyntaxKind::Group
             };
+            // A named group is a rule, with a definition like top-level ones.
+            let marker = p.marker();
             expression(p);
+            if kind == SyntaxKind::NamedGroup {
+                p.wrap(marker, SyntaxKind::Definition);
+            }
             p.expect(SyntaxKind::RightParen);
             p.wrap(start, kind);
         },
 
+        | SyntaxKind::Label => {
+            let label = p.marker().prev();
+            if !starts_alternative(p, label) {
+                p[label].convert_to_error("unexpected label");
+                p[label].hints(
+                    "labels start an alternative of a rule, like `#add expr \
+                     \"+\" expr`",
+                );
+            }
+            return true;
+        },
+
+        | SyntaxKind::LeftBracket => {
+            expression(p);
+            p.expect(SyntaxKind::RightBracket);
+            p.hint("consider closing the optional expression with `]`");
+            p.wrap(start, SyntaxKind::Optional);
+        },
+
         | SyntaxKind::LeftBrace => {
             p.unexpected();
             p.hint("range should be attached to an expression");
@@ -100,7 +553,11 @@ fn item(p: &mut Parser, wrapper: Option<(Marker, SyntaxKind)>) -> bool {
             p.hint("consider starting a range with `{`");
         },
 
-        | SyntaxKind::RightParen | SyntaxKind::End | SyntaxKind::SemiColon => {
+        | SyntaxKind::RightParen
+        | SyntaxKind::RightBracket
+        | SyntaxKind::Comma
+        | SyntaxKind::End
+        | SyntaxKind::SemiColon => {
             p.uneat();
             return false;
         },
@@ -130,26 +587,80 @@ fn item(p: &mut Parser, wrapper: Option<(Marker, SyntaxKind)>) -> bool {
         p.wrap(start.prev(), SyntaxKind::Repeating);
     }
 
+    // Attach constraints to the item, unless it is a separator.
+    loop {
+        let item = p.last_significant();
+        if matches!(p[item].kind(), SyntaxKind::Bar | SyntaxKind::Action)
+            || !p.eat_if(SyntaxKind::Param)
+        {
+            break;
+        }
+        p.wrap(item, SyntaxKind::Constraint);
+    }
+
     true
 }
 
+/// Check if the node at `at` starts an alternative of the definition of a
+/// rule, not of a group.
+fn starts_alternative(p: &Parser<'_>, at: Marker) -> bool {
+    let mut nodes = p.nodes[..at.0]
+        .iter()
+        .rev()
+        .map(SyntaxNode::kind)
+        .filter(|kind| !kind.is_trivia());
+    if !matches!(nodes.next(), Some(SyntaxKind::Colon | SyntaxKind::Bar)) {
+        return false;
+    }
+
+    // Open groups are not wrapped yet, so their bracket is still a node.
+    nodes
+        .find(|kind| {
+            matches!(
+                kind,
+                SyntaxKind::Colon
+                    | SyntaxKind::LeftParen
+                    | SyntaxKind::LeftBracket
+            )
+        })
+        .is_none_or(|kind| kind == SyntaxKind::Colon)
+}
+
 /// Manages parsing a stream of tokens into a tree of [`SyntaxNode`]s.
 struct Parser<'s> {
     lexer: Lexer<'s>,
     nodes: Vec<SyntaxNode>,
+    /// How many items enclose the one being parsed.
+    depth: usize,
+    max_depth: usize,
+    /// The rules whose references take arguments.
+    parameterized: HashSet<EcoString>,
+    /// The arena of the inner nodes, with the `arena` feature.
+    #[cfg(feature = "arena")]
+    arena: Arc<Arena>,
 }
 
 impl<'s> Parser<'s> {
     /// Create a new parser for the given text.
-    fn new(text: &'s str) -> Self {
+    fn new(text: &'s str, options: &ParseOptions) -> Self {
+        let mut parameterized = parameterized_rules(text);
+        parameterized.extend(options.parameterized.iter().cloned());
         Self {
-            lexer: Lexer::new(text),
+            lexer: Lexer::new(text).with_max_depth(options.max_depth),
             nodes: Vec::new(),
+            depth: 0,
+            max_depth: options.max_depth,
+            parameterized,
+            #[cfg(feature = "arena")]
+            arena: Arc::default(),
         }
     }
 
     /// Finish parsing and return the resulting nodes as a single node.
     fn finish(self, kind: SyntaxKind) -> SyntaxNode {
+        #[cfg(feature = "arena")]
+        return self.arena.root(kind, self.nodes);
+        #[cfg(not(feature = "arena"))]
         SyntaxNode::inner(kind, self.nodes)
     }
 }
@@ -159,7 +670,7 @@ impl Parser<'_> {
     /// Return the kind of the first non-trivia token.
     fn eat(&mut self) -> SyntaxKind {
         loop {
-            let node = self.lexer.next();
+            let node = self.lexer.next_token();
             let kind = node.kind();
             self.nodes.push(node);
             if !kind.is_trivia() {
@@ -185,6 +696,20 @@ impl Parser<'_> {
         }
     }
 
+    /// Eat the next token if it matches the given pattern and directly
+    /// follows the previous node, without trivia in between.
+    fn eat_attached(&mut self, pattern: impl Pattern) -> bool {
+        let marker = self.marker();
+        if !self.eat_if(pattern) {
+            return false;
+        }
+        if self.marker().0 == marker.0 + 1 {
+            return true;
+        }
+        self.uneat();
+        false
+    }
+
     /// Eat tokens while the pattern matches.
     fn eat_while(&mut self, pattern: impl Pattern) {
         while pattern.matches(self.eat()) {}
@@ -200,11 +725,87 @@ impl Parser<'_> {
     fn wrap(&mut self, from: Marker, kind: SyntaxKind) {
         let to = self.marker().0;
         let from = from.0.min(to);
-        let children = self.nodes.split_off(from);
-        let node = SyntaxNode::inner(kind, children);
+        let node = self.inner(from, kind);
         self.nodes.push(node);
     }
 
+    /// Take the nodes from the index into a new inner node.
+    fn inner(&mut self, from: usize, kind: SyntaxKind) -> SyntaxNode {
+        #[cfg(feature = "arena")]
+        return self.arena.inner(kind, &mut self.nodes, from);
+        #[cfg(not(feature = "arena"))]
+        SyntaxNode::inner(kind, self.nodes.split_off(from))
+    }
+
+    /// Return the marker pointing to the last node which is not trivia.
+    fn last_significant(&self) -> Marker {
+        let mut marker = self.marker().prev();
+        while self[marker].kind().is_trivia() {
+            marker = marker.prev();
+        }
+        marker
+    }
+
+    /// Return the marker pointing to the doc comments just eaten, separated
+    /// only by whitespace, or to the next node if there are none.
+    fn doc_comments(&self) -> Marker {
+        let mut start = self.nodes.len();
+        while start > 0
+            && matches!(
+                self.nodes[start - 1].kind(),
+                SyntaxKind::Whitespace | SyntaxKind::DocComment
+            )
+        {
+            start -= 1;
+        }
+        while self
+            .nodes
+            .get(start)
+            .is_some_and(|n| n.kind() == SyntaxKind::Whitespace)
+        {
+            start += 1;
+        }
+        Marker(start)
+    }
+
+    /// Check if the next tokens start a rule, like `a:`, `list(item):` or
+    /// `@inline`, without eating them.
+    fn at_rule_header(&mut self) -> bool {
+        let marker = self.marker();
+        let header = match self.eat() {
+            | SyntaxKind::Annotation => true,
+            | SyntaxKind::Identifier => {
+                if self.nodes.last().unwrap().text() == "fragment" {
+                    self.eat_if(SyntaxKind::Identifier);
+                }
+                if self.eat_attached(SyntaxKind::LeftParen) {
+                    self.eat_while([SyntaxKind::Identifier, SyntaxKind::Comma]);
+                    self.eat_if(SyntaxKind::RightParen);
+                } else {
+                    self.eat_attached(SyntaxKind::Param);
+                }
+                self.eat_if([SyntaxKind::Colon, SyntaxKind::PlusEq])
+            },
+            | _ => false,
+        };
+        self.lexer.jump(self.nodes[marker.0].span().start);
+        self.nodes.truncate(marker.0);
+        header
+    }
+
+    /// Give back the trivia eaten after the last token, like the doc comments
+    /// of the next rule.
+    fn rewind_trivia(&mut self) {
+        let mut end = self.nodes.len();
+        while end > 0 && self.nodes[end - 1].kind().is_trivia() {
+            end -= 1;
+        }
+        if let Some(node) = self.nodes.get(end) {
+            self.lexer.jump(node.span().start);
+            self.nodes.truncate(end);
+        }
+    }
+
     /// Return the marker pointing to the next node.
     fn marker(&self) -> Marker {
         Marker(self.nodes.len())
@@ -221,11 +822,25 @@ impl Parser<'_> {
     /// If it does not match, report an error.
     fn expect(&mut self, pattern: impl Pattern) -> bool {
         if pattern.matches(self.eat()) {
-            true
+            return true;
+        }
+
+        self.uneat();
+        if self.at_rule_header() {
+            // Leave the next rule alone, reporting the missing token right
+            // after the last one.
+            self.rewind_trivia();
+            let offset = self.lexer.cursor();
+            let message =
+                eco_format!("expected {}, found the next rule", pattern.name());
+            let error = SyntaxError::new(message);
+            self.nodes
+                .push(SyntaxNode::error(error, "", offset..offset));
         } else {
+            self.eat();
             self.expected(pattern);
-            false
         }
+        false
     }
 
     /// Report an error saying that the token is not what is expected.
@@ -237,6 +852,61 @@ impl Parser<'_> {
         ));
     }
 
+    /// Report an error if the last token is not a string literal of a single
+    /// character.
+    fn expect_char(&mut self) {
+        self.expect_char_at(self.marker().prev());
+    }
+
+    /// Report an error if the node at the marker is not a string literal of a
+    /// single character.
+    fn expect_char_at(&mut self, marker: Marker) {
+        let node = &mut self[marker];
+        let single = unescape(node.text()).is_some_and(|value| {
+            let mut chars = value.chars();
+            chars.next().is_some() && chars.next().is_none()
+        });
+
+        if !single {
+            node.convert_to_error("range endpoint must be a single character");
+            node.hints("a range connects two characters, like `\"a\"..\"z\"`");
+        }
+    }
+
+    /// Report an error on the end of the range from the marker if the range
+    /// matches no character, like `"b".."a"` or `"a".."a"`.
+    fn expect_range(&mut self, start: Marker, inclusive: bool) {
+        let bounds = (start.0..self.nodes.len())
+            .filter(|&i| self.nodes[i].kind() == SyntaxKind::String)
+            .filter_map(|i| {
+                Some((i, unescape(self.nodes[i].text())?.chars().next()?))
+            })
+            .collect::<Vec<_>>();
+        let [(_, first), (last, end)] = &bounds[..] else {
+            return;
+        };
+        if first < end || (first == end && inclusive) {
+            return;
+        }
+
+        let node = &mut self.nodes[*last];
+        node.convert_to_error("range is empty");
+        if first == end {
+            node.hints("`..<` excludes its end, consider `..` to include it");
+        } else {
+            node.hints("the end of a range cannot come before its start");
+        }
+    }
+
+    /// Merge the nodes from the marker into a single error node.
+    fn merge_error(&mut self, from: Marker, error: SyntaxError) {
+        let end = self.nodes.last().unwrap().span().end;
+        let span = self[from].span().start..end;
+        let text = self.lexer.get(span.clone());
+        self.nodes.truncate(from.0);
+        self.nodes.push(SyntaxNode::error(error, text, span));
+    }
+
     /// Report an error saying that the token is unexpected.
     fn unexpected(&mut self) {
         self.error(eco_format!("unexpected {}", self.kind().name(),));
@@ -400,7 +1070,10 @@ mod tests {
             | SyntaxKind::Meta => "<meta>",
             | SyntaxKind::Operation => " operation ",
             | SyntaxKind::If => "if",
+            | SyntaxKind::Empty => "empty",
+            | SyntaxKind::Fragment => "fragment",
             | SyntaxKind::Colon => ":",
+            | SyntaxKind::PlusEq => "+=",
             | SyntaxKind::SemiColon => ";",
             | SyntaxKind::Arrow => "->",
             | SyntaxKind::LeftBracket => "[",
@@ -411,12 +1084,18 @@ mod tests {
             | SyntaxKind::RightBrace => "}",
             | SyntaxKind::Comma => ",",
             | SyntaxKind::Bar => "|",
+            | SyntaxKind::Caret => "^",
+            | SyntaxKind::Dollar => "$",
             | SyntaxKind::Tilde => "~",
             | SyntaxKind::Dot => ".",
             | SyntaxKind::Question => "?",
             | SyntaxKind::Star => "*",
             | SyntaxKind::Plus => "+",
             | SyntaxKind::Dots => "..",
+            | SyntaxKind::DotsEq => "..=",
+            | SyntaxKind::DotsLt => "..<",
+            | SyntaxKind::Percent => "%",
+            | SyntaxKind::Minus => "-",
             | SyntaxKind::LookAheadPos => "?=",
             | SyntaxKind::LookAheadNeg => "?!",
             | SyntaxKind::LookBehindPos => "?<=",
@@ -482,6 +1161,100 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pragma() {
+        test_node! {
+            Root => {
+                Pragma => {
+                    Percent,
+                    PragmaName => "start",
+                    Whitespace => " ",
+                    Identifier,
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pragma_skip() {
+        let root = parse("%skip whitespace comment;");
+        let pragma = root.children().next().unwrap();
+        assert_eq!(pragma.kind(), SyntaxKind::Pragma);
+        assert!(!pragma.erroneous());
+        assert_eq!(
+            pragma
+                .children()
+                .filter(|n| n.kind() == SyntaxKind::Identifier)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_pragma_errors() {
+        for (code, message) in [
+            ("%start;", "expected identifier, found `;`"),
+            ("% start a;", "expected a pragma name after `%`"),
+            ("%begin a;", "unknown pragma `%begin`"),
+            ("%skip;", "expected identifier, found `;`"),
+            ("%import lexer;", "expected string, found identifier"),
+            ("%start a", "expected `;`, found end"),
+        ] {
+            let root = parse(code);
+            let pragma = root.children().next().unwrap();
+            assert_eq!(pragma.kind(), SyntaxKind::Pragma);
+            let error = pragma.children().find_map(SyntaxNode::as_error);
+            assert_eq!(error.unwrap().message, message, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_rule_extension() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    PlusEq,
+                    Definition => {
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_fragment() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Fragment,
+                    Whitespace => " ",
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier => "fragment",
+                    Colon,
+                    Definition => {
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_rule_param() {
         test_node! {
@@ -502,14 +1275,354 @@ mod tests {
     }
 
     #[test]
-    fn test_rule_whitespace() {
+    fn test_rule_whitespace() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Whitespace,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_doc_comment() {
+        test_node! {
+            Root => {
+                Comment => "// comment",
+                Whitespace => "\n",
+                Rule => {
+                    DocComment => "/// doc",
+                    Whitespace => "\n",
+                    DocComment => "/// more",
+                    Whitespace => "\n",
+                    Identifier,
+                    Colon,
+                    Definition => {},
+                    SemiColon,
+                },
+                Whitespace => "\n",
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {},
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_annotations() {
+        test_node! {
+            Root => {
+                Rule => {
+                    DocComment => "/// doc",
+                    Whitespace => "\n",
+                    Annotation => {
+                        AnnotationName => "@token",
+                        LeftParen,
+                        Operation => "keyword",
+                        RightParen,
+                    },
+                    Whitespace => " ",
+                    Annotation => {
+                        AnnotationName => "@inline",
+                    },
+                    Whitespace => "\n",
+                    Identifier,
+                    Colon,
+                    Definition => {},
+                    SemiColon,
+                }
+            }
+        }
+        assert!(parse("a: @inline b;").erroneous());
+    }
+
+    #[test]
+    fn test_rule_line_comment() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Comment => "// comment",
+                        Whitespace => "\n",
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_block_comment() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Comment => "/* comment */",
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_identifier() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_reference() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Reference => {
+                            Identifier,
+                            Param => {
+                                LeftBracket,
+                                Operation,
+                                RightBracket,
+                            },
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_separated() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Separated => {
+                            Whitespace => " ",
+                            Identifier,
+                            Whitespace => " ",
+                            Percent,
+                            Whitespace => " ",
+                            String,
+                        },
+                        Whitespace => " ",
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+        assert!(parse("a: b %;").erroneous());
+        assert!(parse("a: % b;").erroneous());
+        assert!(parse("a: b | % c;").erroneous());
+        assert!(parse("a: b % | c;").erroneous());
+    }
+
+    #[test]
+    fn test_rule_difference() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Difference => {
+                            Whitespace => " ",
+                            Identifier,
+                            Whitespace => " ",
+                            Minus,
+                            Whitespace => " ",
+                            String,
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+
+        // Differences are left-associative.
+        let root = parse("a: b - c - d;");
+        assert!(!root.erroneous());
+        let child = |node: &SyntaxNode, kind| {
+            node.children().find(|n| n.kind() == kind).cloned().unwrap()
+        };
+        let rule = child(&root, SyntaxKind::Rule);
+        let outer = child(
+            &child(&rule, SyntaxKind::Definition),
+            SyntaxKind::Difference,
+        );
+        child(&outer, SyntaxKind::Difference);
+
+        assert!(!parse("a: b -> c;").erroneous());
+        assert!(parse("a: b -;").erroneous());
+        assert!(parse("a: - b;").erroneous());
+        assert!(parse("a: b | - c;").erroneous());
+    }
+
+    #[test]
+    fn test_rule_optional() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Optional => {
+                            Whitespace => " ",
+                            LeftBracket,
+                            Whitespace => " ",
+                            String,
+                            Whitespace => " ",
+                            Bar,
+                            Whitespace => " ",
+                            Identifier,
+                            Whitespace => " ",
+                            RightBracket,
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+
+        // Other brackets are a class or a constraint.
+        assert!(!parse("a: [a-z] b [ not a keyword ];").erroneous());
+        assert!(parse("a: [ \"b\";").erroneous());
+        assert!(parse("a: b ];").erroneous());
+    }
+
+    #[test]
+    fn test_rule_brackets() {
+        // The meaning of brackets does not depend on the item before them.
+        for (code, kind) in [
+            ("a: [a-z];", SyntaxKind::CharClass),
+            ("a: \"x\" [a-z];", SyntaxKind::CharClass),
+            ("a: [_];", SyntaxKind::CharClass),
+            ("a: \"x\" [_];", SyntaxKind::CharClass),
+            ("a: [xyz];", SyntaxKind::Optional),
+            ("a: \"x\" [xyz];", SyntaxKind::Optional),
+            ("a: [ \"x\" ];", SyntaxKind::Optional),
+            ("a: \"x\" [ \"x\" ];", SyntaxKind::Optional),
+            ("a: \"x\" [not a keyword];", SyntaxKind::Constraint),
+            ("a: [not a keyword];", SyntaxKind::Error),
+            ("a: b[c];", SyntaxKind::Reference),
+            ("a: b[a-z];", SyntaxKind::Reference),
+        ] {
+            let root = parse(code);
+            let definition = root
+                .descendants()
+                .find(|n| n.kind() == SyntaxKind::Definition)
+                .unwrap();
+            let last = definition
+                .children()
+                .rfind(|n| !n.kind().is_trivia())
+                .unwrap();
+            assert_eq!(last.kind(), kind, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_rule_optional_iso() {
+        // Like in ISO EBNF, with or without spaces inside the brackets.
+        for (code, inner) in [
+            ("a: [digit];", "digit"),
+            ("a: b [digit];", "digit"),
+            ("a: b [\",\" c];", "\",\""),
+            ("a: b [ c ];", "c"),
+            ("a: b [c+];", "c"),
+        ] {
+            let root = parse(code);
+            assert!(!root.erroneous(), "{code}");
+            let optional = root
+                .descendants()
+                .find(|n| n.kind() == SyntaxKind::Optional)
+                .unwrap_or_else(|| panic!("no optional in {code}"));
+            let first = optional
+                .descendants()
+                .find(|n| {
+                    matches!(
+                        n.kind(),
+                        SyntaxKind::Identifier | SyntaxKind::String
+                    )
+                })
+                .unwrap();
+            assert_eq!(first.text(), inner, "{code}");
+        }
+    }
+
+    #[test]
+    fn test_rule_label() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Whitespace => " ",
+                        Label => "#add",
+                        Whitespace => " ",
+                        Identifier,
+                        Whitespace => " ",
+                        Bar,
+                        Whitespace => " ",
+                        Label => "#mul",
+                        Whitespace => " ",
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+        assert!(!parse("a: #x b | (c) d | #y e;").erroneous());
+        assert!(parse("a: b #x c;").erroneous());
+        assert!(parse("a: (#x b);").erroneous());
+        assert!(parse("a: (b | #x c);").erroneous());
+        assert!(parse("a: b % #x c;").erroneous());
+    }
+
+    #[test]
+    fn test_rule_insensitive() {
         test_node! {
             Root => {
                 Rule => {
                     Identifier,
                     Colon,
                     Definition => {
-                        Whitespace,
+                        Whitespace => " ",
+                        Insensitive => {
+                            Modifier => "i",
+                            String,
+                        },
+                        Whitespace => " ",
+                        Identifier,
                     },
                     SemiColon,
                 }
@@ -518,15 +1631,22 @@ mod tests {
     }
 
     #[test]
-    fn test_rule_line_comment() {
+    fn test_rule_params() {
         test_node! {
             Root => {
                 Rule => {
-                    Identifier,
+                    Identifier => "list",
+                    Param => {
+                        LeftParen,
+                        Identifier => "item",
+                        Comma,
+                        Whitespace => " ",
+                        Identifier => "sep",
+                        RightParen,
+                    },
                     Colon,
                     Definition => {
-                        Comment => "// comment",
-                        Whitespace => "\n",
+                        Identifier => "item",
                     },
                     SemiColon,
                 }
@@ -535,50 +1655,148 @@ mod tests {
     }
 
     #[test]
-    fn test_rule_block_comment() {
+    fn test_rule_reference_arguments() {
         test_node! {
             Root => {
                 Rule => {
                     Identifier,
                     Colon,
                     Definition => {
-                        Comment => "/* comment */",
+                        Reference => {
+                            Identifier => "list",
+                            LeftParen,
+                            Argument => {
+                                Identifier,
+                                Whitespace => " ",
+                                Bar,
+                                Whitespace => " ",
+                                String,
+                            },
+                            Comma,
+                            Argument => {
+                                Group => {
+                                    Whitespace => " ",
+                                    LeftParen,
+                                    Identifier,
+                                    RightParen,
+                                },
+                            },
+                            RightParen,
+                        },
+                        Whitespace => " ",
+                        Identifier,
+                        Looking => {
+                            LeftParen,
+                            LookAheadNeg,
+                            Identifier,
+                            RightParen,
+                        },
                     },
                     SemiColon,
+                },
+                Whitespace,
+                Rule => {
+                    Identifier => "list",
+                    Param => {
+                        LeftParen,
+                        Identifier,
+                        RightParen,
+                    },
+                    Colon,
+                    Definition => {},
+                    SemiColon,
                 }
             }
         }
     }
 
     #[test]
-    fn test_rule_identifier() {
+    fn test_rule_reference_group() {
+        // Without parameters, the parenthesis after a reference opens a
+        // group.
         test_node! {
             Root => {
                 Rule => {
                     Identifier,
                     Colon,
                     Definition => {
-                        Identifier,
+                        Identifier => "a",
+                        Repeating => {
+                            Group => {
+                                LeftParen,
+                                Identifier => "b",
+                                RightParen,
+                            },
+                            Star,
+                        },
                     },
                     SemiColon,
+                },
+                Whitespace,
+                Rule => {
+                    Identifier => "a",
+                    Colon,
+                    Definition => {},
+                    SemiColon,
                 }
             }
         }
+
+        let options = ParseOptions {
+            parameterized: HashSet::from(["a".into()]),
+            ..ParseOptions::default()
+        };
+        let root = parse_with_options("b: a(b)*;", &options);
+        assert!(
+            root.descendants()
+                .any(|node| node.kind() == SyntaxKind::Argument)
+        );
     }
 
     #[test]
-    fn test_rule_reference() {
+    fn test_parameterized_rules() {
+        assert_eq!(
+            parameterized_rules(
+                "list(item): a(b)*;\nsep (x, y) += x;\nc: (d): e;\nf(g)\n: h;"
+            ),
+            HashSet::from(["list".into(), "sep".into(), "f".into()])
+        );
+    }
+
+    #[test]
+    fn test_rule_stray_comma() {
+        assert!(parse("a: b, c;").erroneous());
+        assert!(parse("a(b c): b;").erroneous());
+        assert!(parse("list(a): a;\nb: list(b;").erroneous());
+    }
+
+    #[test]
+    fn test_rule_constraint() {
         test_node! {
             Root => {
                 Rule => {
                     Identifier,
                     Colon,
                     Definition => {
-                        Reference => {
+                        Constraint => {
+                            Repeating => {
+                                String,
+                                Star,
+                                Whitespace,
+                            },
+                            Param => {
+                                LeftBracket,
+                                Operation => " not operation ",
+                                RightBracket,
+                            },
+                        },
+                        Bar,
+                        Constraint => {
                             Identifier,
+                            Whitespace,
                             Param => {
                                 LeftBracket,
-                                Operation,
+                                Operation => " not operation ",
                                 RightBracket,
                             },
                         },
@@ -621,6 +1839,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rule_empty_keyword() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Empty,
+                        Bar,
+                        String,
+                        Identifier,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_named_empty() {
+        let (names, messages) = recovered("empty: \"x\";\na: empty;");
+        assert_eq!(names, ["a"]);
+        assert_eq!(messages, ["`empty` is a keyword, not a rule name"]);
+
+        let root = parse("empty: \"x\";\na: empty;");
+        let reference = root.children().nth(2).unwrap();
+        assert!(
+            reference
+                .descendants()
+                .any(|n| n.kind() == SyntaxKind::Empty)
+        );
+
+        let (names, messages) = recovered("fragment empty: \"x\";");
+        assert!(names.is_empty());
+        assert_eq!(messages.len(), 1);
+        assert!(recovered("empty_rule: \"x\";").1.is_empty());
+    }
+
     #[test]
     fn test_if_action() {
         test_node!(
@@ -631,7 +1888,26 @@ mod tests {
                     Definition => {
                         Action => {
                             If,
-                            Operation,
+                            Condition => {
+                                Whitespace => " ",
+                                Variable => "operation",
+                                Whitespace => " ",
+                            },
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        );
+        test_node!(
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Action => {
+                            If,
+                            Operation => " a.b() ",
                         },
                     },
                     SemiColon,
@@ -700,6 +1976,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rule_predicate() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Looking => {
+                            LookAheadNeg => "!",
+                            Repeating => {
+                                String,
+                                Star,
+                                Whitespace => " ",
+                            },
+                        },
+                        Looking => {
+                            LookAheadPos => "&",
+                            Identifier,
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+        assert!(parse("a: !;").erroneous());
+    }
+
     #[test]
     fn test_rule_lookahead_neg() {
         test_node! {
@@ -968,6 +2272,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rule_anchors() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Caret,
+                        Identifier,
+                        Dollar,
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_rule_dot() {
         test_node! {
@@ -993,9 +2315,135 @@ mod tests {
                     Colon,
                     Definition => {
                         Range => {
-                            String,
+                            String => "a",
                             Dots,
-                            String,
+                            String => "z",
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_range_inclusive() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Range => {
+                            String => "a",
+                            DotsEq,
+                            String => "z",
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_range_empty() {
+        for (range, hint) in [
+            ("\"a\"..<\"a\"", "consider `..`"),
+            ("\"b\"..\"a\"", "cannot come before its start"),
+            ("\"b\"..=\"a\"", "cannot come before its start"),
+        ] {
+            let root = parse(&format!("r: {range};"));
+            let rule = root.children().next().unwrap();
+            let definition = rule.find_by_kind(SyntaxKind::Definition).unwrap();
+            let range = definition.children().next().unwrap();
+            let error =
+                range.children().find_map(SyntaxNode::as_error).unwrap();
+            assert_eq!(error.message, "range is empty");
+            assert!(error.hints[0].contains(hint));
+        }
+        assert!(!parse("r: \"a\"..\"a\";").erroneous());
+        assert!(!parse("r: \"a\"..=\"a\";").erroneous());
+    }
+
+    #[test]
+    fn test_rule_named_group() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        NamedGroup => {
+                            LeftParen,
+                            Identifier => "sub",
+                            Colon,
+                            Definition => {
+                                Whitespace => " ",
+                                String,
+                                Bar,
+                                Identifier,
+                            },
+                            RightParen,
+                        },
+                        Whitespace => " ",
+                        Group => {
+                            LeftParen,
+                            Identifier,
+                            Whitespace => " ",
+                            Identifier,
+                            RightParen,
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_char_class() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        CharClass => "[_]",
+                        Whitespace => " ",
+                        Repeating => {
+                            CharClass => "[a-z0-9_]",
+                            Star,
+                            Whitespace => " ",
+                        },
+                        Constraint => {
+                            CharClass => "[\\-]",
+                            Whitespace => " ",
+                            Param => {
+                                LeftBracket,
+                                Operation => "if note",
+                                RightBracket,
+                            },
+                        },
+                    },
+                    SemiColon,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rule_range_not_char() {
+        test_node! {
+            Root => {
+                Rule => {
+                    Identifier,
+                    Colon,
+                    Definition => {
+                        Range => {
+                            Error => "\"ab\"",
+                            Dots,
+                            String => "\u{5A}",
                         },
                     },
                     SemiColon,
@@ -1061,12 +2509,16 @@ mod tests {
                         },
                         Bar,
                         Range => {
-                            String,
+                            String => "a",
                             Whitespace,
                             Dots,
-                            String,
+                            String => "z",
                         },
-                        Action => { If, Operation },
+                        Action => { If, Condition => {
+                            Whitespace => " ",
+                            Variable => "operation",
+                            Whitespace => " ",
+                        } },
                         Whitespace,
                         Action => { Arrow, Operation },
                     },
@@ -1107,11 +2559,19 @@ mod tests {
                                 },
                                 RightParen,
                             },
-                            Action => { If, Operation },
+                            Action => { If, Condition => {
+                            Whitespace => " ",
+                            Variable => "operation",
+                            Whitespace => " ",
+                        } },
                             Whitespace,
                             RightParen
                         },
-                        Action => { If, Operation },
+                        Action => { If, Condition => {
+                            Whitespace => " ",
+                            Variable => "operation",
+                            Whitespace => " ",
+                        } },
                     },
                     SemiColon,
                 },
@@ -1119,4 +2579,164 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_parse_with_diagnostics() {
+        let (root, diagnostics) = parse_with_diagnostics("a: b;\nc: d");
+        assert_eq!(root.kind(), SyntaxKind::Root);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, crate::Severity::Error);
+        assert_eq!(diagnostic.message, "expected `;`, found end");
+        assert_eq!(diagnostic.hints.as_slice(), [
+            "consider ending the rule with `;`"
+        ]);
+        assert_eq!(diagnostic.span, 10..10);
+
+        assert!(parse_with_diagnostics("a: b;").1.is_empty());
+    }
+
+    /// The names of the rules parsed from the input, and the messages of its
+    /// errors.
+    fn recovered(input: &str) -> (Vec<String>, Vec<String>) {
+        let (root, diagnostics) = parse_with_diagnostics(input);
+        let names = root
+            .cast::<crate::ast::Root>()
+            .unwrap()
+            .rules()
+            .filter_map(|rule| rule.name())
+            .map(ToString::to_string)
+            .collect();
+        let messages = diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.message.to_string())
+            .collect();
+        (names, messages)
+    }
+
+    #[test]
+    fn test_recover_missing_semicolon() {
+        let (names, messages) =
+            recovered("a: b\n/// doc\nc: d;\n@inline e: f;");
+        assert_eq!(names, ["a", "c", "e"]);
+        assert_eq!(messages, ["expected `;`, found the next rule"]);
+
+        let root = parse("a: b\n/// doc\nc: d;");
+        let c = root.cast::<crate::ast::Root>().unwrap().rules().nth(1);
+        assert_eq!(c.unwrap().docs().collect::<Vec<_>>(), ["doc"]);
+
+        let (names, _) = recovered("a: (b | c\nlist(item): item;");
+        assert_eq!(names, ["a", "list"]);
+    }
+
+    #[test]
+    fn test_recover_skip_to_semicolon() {
+        let (names, messages) = recovered("a: b ) c (d; e: f;");
+        assert_eq!(names, ["a", "e"]);
+        assert_eq!(messages, ["expected `;`, found `)`"]);
+
+        let root = parse("a: b ) c;");
+        let error = root.children().next().unwrap().children().nth(3).unwrap();
+        assert_eq!(error.text(), ") c");
+        assert_eq!(*error.span(), 5..8);
+
+        let (names, messages) = recovered(") a: b;");
+        assert_eq!(names, ["a"]);
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let root = parse_with_max_depth("a: (b (c (d))) e;", 2);
+        let diagnostics = root.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "item is nested too deeply");
+        assert_eq!(diagnostics[0].span, 10..11);
+        assert!(
+            parse_with_max_depth("a: (b (c)) e;", 2)
+                .diagnostics()
+                .is_empty()
+        );
+
+        // Actions nested too deeply are kept as opaque operations.
+        let code = "a: b -> f(g(h(x)));";
+        let root = parse_with_max_depth(code, 2);
+        assert!(root.find_by_kind(SyntaxKind::Rule).is_some());
+        let kinds = |root: &SyntaxNode| {
+            root.descendants().map(SyntaxNode::kind).collect::<Vec<_>>()
+        };
+        assert!(kinds(&root).contains(&SyntaxKind::Operation));
+        assert!(!kinds(&root).contains(&SyntaxKind::Call));
+        assert!(kinds(&parse(code)).contains(&SyntaxKind::Call));
+
+        let depth = 100_000;
+        let code =
+            format!("a: {}b{}; c: d;", "(".repeat(depth), ")".repeat(depth));
+        let (root, diagnostics) = parse_with_diagnostics(&code);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            root.children()
+                .filter(|n| n.kind() == SyntaxKind::Rule)
+                .count(),
+            2
+        );
+
+        let code = format!("a: b ~{};", "~".repeat(depth));
+        assert_eq!(parse_with_diagnostics(&code).1.len(), 1);
+
+        let code = format!("a: b if {}c;", "!".repeat(depth));
+        assert!(!parse(&code).erroneous());
+
+        // Chains of binary operators nest one level per operator.
+        let code = "a: b - c - d - e;";
+        assert!(parse_with_max_depth(code, 3).diagnostics().is_empty());
+        let diagnostics = parse_with_max_depth(code, 2).diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "item is nested too deeply");
+
+        let code = format!("a: b{}; c: d;", " - c".repeat(depth));
+        let (root, diagnostics) = parse_with_diagnostics(&code);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            root.children()
+                .filter(|n| n.kind() == SyntaxKind::Rule)
+                .count(),
+            2
+        );
+
+        let code = format!("a: b{};", " % \",\"".repeat(depth));
+        assert_eq!(parse_with_diagnostics(&code).1.len(), 1);
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let items = |code: &str| {
+            let root = parse(code);
+            root.descendants()
+                .find(|node| node.kind() == SyntaxKind::Definition)
+                .unwrap()
+                .children()
+                .filter(|node| !node.kind().is_trivia())
+                .map(|node| (node.kind(), node.text().to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        // A dot matches any character, even between two names.
+        let dotted = [
+            (SyntaxKind::Identifier, "b".to_string()),
+            (SyntaxKind::Dot, ".".to_string()),
+            (SyntaxKind::Identifier, "c".to_string()),
+        ];
+        assert_eq!(items("a: b.c;"), dotted);
+        assert_eq!(items("a: b . c;"), dotted);
+
+        assert_eq!(items("a: lexer::b;"), [(
+            SyntaxKind::Identifier,
+            "lexer::b".to_string()
+        )]);
+        let root = parse("lexer::b: c;");
+        assert!(!root.erroneous());
+        let rule = root.find_by_kind(SyntaxKind::Rule).unwrap();
+        assert_eq!(rule.children().next().unwrap().text(), "lexer::b");
+    }
 }
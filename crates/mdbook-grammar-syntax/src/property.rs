@@ -0,0 +1,199 @@
+/// The general categories of Unicode, by their short and long names.
+const CATEGORIES: &[&str] = &[
+    "L",
+    "Letter",
+    "LC",
+    "Cased_Letter",
+    "Lu",
+    "Uppercase_Letter",
+    "Ll",
+    "Lowercase_Letter",
+    "Lt",
+    "Titlecase_Letter",
+    "Lm",
+    "Modifier_Letter",
+    "Lo",
+    "Other_Letter",
+    "M",
+    "Mark",
+    "Mn",
+    "Nonspacing_Mark",
+    "Mc",
+    "Spacing_Mark",
+    "Me",
+    "Enclosing_Mark",
+    "N",
+    "Number",
+    "Nd",
+    "Decimal_Number",
+    "Nl",
+    "Letter_Number",
+    "No",
+    "Other_Number",
+    "P",
+    "Punctuation",
+    "Pc",
+    "Connector_Punctuation",
+    "Pd",
+    "Dash_Punctuation",
+    "Ps",
+    "Open_Punctuation",
+    "Pe",
+    "Close_Punctuation",
+    "Pi",
+    "Initial_Punctuation",
+    "Pf",
+    "Final_Punctuation",
+    "Po",
+    "Other_Punctuation",
+    "S",
+    "Symbol",
+    "Sm",
+    "Math_Symbol",
+    "Sc",
+    "Currency_Symbol",
+    "Sk",
+    "Modifier_Symbol",
+    "So",
+    "Other_Symbol",
+    "Z",
+    "Separator",
+    "Zs",
+    "Space_Separator",
+    "Zl",
+    "Line_Separator",
+    "Zp",
+    "Paragraph_Separator",
+    "C",
+    "Other",
+    "Cc",
+    "Control",
+    "Cf",
+    "Format",
+    "Cs",
+    "Surrogate",
+    "Co",
+    "Private_Use",
+    "Cn",
+    "Unassigned",
+];
+
+/// The binary properties of Unicode most used by grammars.
+const BINARY: &[&str] = &[
+    "Alphabetic",
+    "Any",
+    "ASCII",
+    "ASCII_Hex_Digit",
+    "Assigned",
+    "Dash",
+    "Default_Ignorable_Code_Point",
+    "Emoji",
+    "Emoji_Presentation",
+    "Extended_Pictographic",
+    "Hex_Digit",
+    "ID_Continue",
+    "ID_Start",
+    "Lowercase",
+    "Math",
+    "Noncharacter_Code_Point",
+    "Pattern_Syntax",
+    "Pattern_White_Space",
+    "Quotation_Mark",
+    "Uppercase",
+    "White_Space",
+    "XID_Continue",
+    "XID_Start",
+];
+
+/// The scripts of Unicode most used by grammars.
+const SCRIPTS: &[&str] = &[
+    "Arabic",
+    "Armenian",
+    "Bengali",
+    "Common",
+    "Cyrillic",
+    "Devanagari",
+    "Ethiopic",
+    "Georgian",
+    "Greek",
+    "Gujarati",
+    "Gurmukhi",
+    "Han",
+    "Hangul",
+    "Hebrew",
+    "Hiragana",
+    "Inherited",
+    "Kannada",
+    "Katakana",
+    "Khmer",
+    "Lao",
+    "Latin",
+    "Malayalam",
+    "Myanmar",
+    "Oriya",
+    "Sinhala",
+    "Tamil",
+    "Telugu",
+    "Thaana",
+    "Thai",
+    "Tibetan",
+];
+
+/// Check if a name is a known Unicode property, like `L`, `White_Space`, or
+/// `Script=Greek`.
+pub fn is_unicode_property(name: &str) -> bool {
+    match name.split_once('=') {
+        | Some(("gc" | "General_Category", value)) => {
+            CATEGORIES.contains(&value)
+        },
+        | Some(("sc" | "Script" | "scx" | "Script_Extensions", value)) => {
+            SCRIPTS.contains(&value)
+        },
+        | Some(_) => false,
+        | None => [CATEGORIES, BINARY, SCRIPTS]
+            .iter()
+            .any(|names| names.contains(&name)),
+    }
+}
+
+/// The known property spelled like a name up to case, `-` and spaces, to
+/// suggest instead of an unknown property.
+pub(crate) fn suggest_property(name: &str) -> Option<&'static str> {
+    let loose = |name: &str| {
+        name.chars()
+            .filter(|c| !matches!(c, '_' | '-' | ' '))
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    let name = loose(name);
+    [CATEGORIES, BINARY, SCRIPTS]
+        .into_iter()
+        .flatten()
+        .copied()
+        .find(|&known| known.len() > 2 && loose(known) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_property() {
+        for name in ["L", "Lu", "Letter", "White_Space", "Greek", "sc=Greek"] {
+            assert!(is_unicode_property(name), "{name}");
+        }
+        for name in ["", "l", "Letters", "sc=Lu", "x=L"] {
+            assert!(!is_unicode_property(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_suggest_property() {
+        assert_eq!(suggest_property("whitespace"), Some("White_Space"));
+        assert_eq!(
+            suggest_property("upper-case letter"),
+            Some("Uppercase_Letter")
+        );
+        assert_eq!(suggest_property("lu"), None);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{SyntaxKind, SyntaxNode};
+
+/// The bounds of a repetition, decoded from its indicator.
+///
+/// `?` is `{0,1}`, `*` is `{0,}` and `+` is `{1,}`. A trailing `?` makes the
+/// repetition match as few occurrences as possible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Repeat {
+    /// The minimum number of occurrences.
+    pub min: u64,
+    /// The maximum number of occurrences, if bounded.
+    pub max: Option<u64>,
+    /// Whether to match as many occurrences as possible.
+    pub greedy: bool,
+}
+
+impl Repeat {
+    /// Decode the repetition of a [`SyntaxKind::Repeating`] node.
+    ///
+    /// Return `None` if the indicator is malformed, like `{a}`.
+    pub fn of(repeating: &SyntaxNode) -> Option<Self> {
+        debug_assert_eq!(repeating.kind(), SyntaxKind::Repeating);
+
+        let mut tokens = repeating
+            .children()
+            .skip(1)
+            .filter(|n| !n.kind().is_trivia());
+
+        let token = tokens.next()?;
+        let (min, max) = match token.kind() {
+            | SyntaxKind::Question => (0, Some(1)),
+            | SyntaxKind::Star => (0, None),
+            | SyntaxKind::Plus => (1, None),
+            | SyntaxKind::BraceIndicator if !token.erroneous() => {
+                let mut bounds = token.children().filter(|n| {
+                    matches!(n.kind(), SyntaxKind::Integer | SyntaxKind::Comma)
+                });
+                let min = bounds.next()?.text().parse().ok()?;
+                match bounds.next() {
+                    | None => (min, Some(min)),
+                    | Some(_) => match bounds.next() {
+                        | Some(max) => (min, Some(max.text().parse().ok()?)),
+                        | None => (min, None),
+                    },
+                }
+            },
+            | _ => return None,
+        };
+
+        let greedy = tokens.next().is_none();
+        Some(Self { min, max, greedy })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    /// Decode the repetition of the item `b` with the given indicator.
+    fn repeat(indicator: &str) -> Option<Repeat> {
+        let root = parse(&format!("a: b{indicator};"));
+        let rule = root.children().next().unwrap();
+        let definition = rule.find_by_kind(SyntaxKind::Definition)?;
+        let repeating =
+            definition.children().find(|n| !n.kind().is_trivia())?;
+        assert_eq!(repeating.kind(), SyntaxKind::Repeating);
+        Repeat::of(repeating)
+    }
+
+    #[track_caller]
+    fn test(indicator: &str, min: u64, max: Option<u64>, greedy: bool) {
+        assert_eq!(repeat(indicator), Some(Repeat { min, max, greedy }));
+    }
+
+    #[test]
+    fn test_repeat_question() {
+        test("?", 0, Some(1), true);
+        test("??", 0, Some(1), false);
+    }
+
+    #[test]
+    fn test_repeat_star() {
+        test("*", 0, None, true);
+        test("*?", 0, None, false);
+    }
+
+    #[test]
+    fn test_repeat_plus() {
+        test("+", 1, None, true);
+        test("+?", 1, None, false);
+    }
+
+    #[test]
+    fn test_repeat_brace() {
+        test("{2}", 2, Some(2), true);
+        test("{2,}", 2, None, true);
+        test("{2,5}", 2, Some(5), true);
+        test("{2}?", 2, Some(2), false);
+        test("{2,}?", 2, None, false);
+        test("{2,5}?", 2, Some(5), false);
+    }
+
+    #[test]
+    fn test_repeat_malformed() {
+        assert_eq!(repeat("{x}"), None);
+    }
+}
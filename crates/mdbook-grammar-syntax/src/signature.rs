@@ -0,0 +1,115 @@
+use crate::{SyntaxKind, SyntaxNode};
+
+/// A content hash of a rule, stable across releases and platforms.
+///
+/// The hash covers the tokens of the rule, ignoring whitespace and comments,
+/// so that reformatting or documenting a rule leaves its signature unchanged.
+pub fn signature(rule: &SyntaxNode) -> u64 {
+    let mut hasher = Fnv::default();
+    hash_tokens(rule, &mut hasher);
+    hasher.0
+}
+
+/// Hash the non-trivia tokens of a node, each followed by a separator.
+fn hash_tokens(node: &SyntaxNode, hasher: &mut Fnv) {
+    if node.kind().is_trivia() {
+        return;
+    }
+
+    // A constraint and an argument only differ by whitespace.
+    if node.kind() == SyntaxKind::Constraint {
+        hasher.write(b" ");
+    }
+
+    // Conditions and calls were opaque operations before being parsed, so
+    // they are hashed like them to keep their signatures.
+    if matches!(node.kind(), SyntaxKind::Condition | SyntaxKind::Call) {
+        let mut text = String::new();
+        flat_text(node, &mut text);
+        for word in text.split_whitespace() {
+            hasher.write(word.as_bytes());
+            hasher.write(b" ");
+        }
+        hasher.write(&[0]);
+        return;
+    }
+
+    if node.children().len() == 0 {
+        if node.kind() == SyntaxKind::Operation {
+            for word in node.text().split_whitespace() {
+                hasher.write(word.as_bytes());
+                hasher.write(b" ");
+            }
+        } else if node.kind() == SyntaxKind::Empty {
+            // Both spellings of the empty production are the same.
+            hasher.write("ε".as_bytes());
+        } else {
+            hasher.write(node.text().as_bytes());
+        }
+        hasher.write(&[0]);
+    }
+
+    for child in node.children() {
+        hash_tokens(child, hasher);
+    }
+}
+
+/// Append the text of the leaves of a node.
+fn flat_text(node: &SyntaxNode, out: &mut String) {
+    out.push_str(node.text());
+    for child in node.children() {
+        flat_text(child, out);
+    }
+}
+
+/// The 64-bit FNV-1a hash, whose output does not depend on the platform or
+/// the version of the standard library.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn rule(code: &str) -> u64 {
+        signature(parse(code).children().next().unwrap())
+    }
+
+    #[test]
+    fn test_signature_stable() {
+        assert_eq!(rule("a: b;"), 0xfc3d_0628_8fa0_f38f);
+        assert_eq!(rule("a: b if x && !y -> z;"), 0x40a6_5f6f_c60f_95ef);
+        assert_eq!(rule("a: b -> push(b, 1);"), 0xa91f_393d_3866_fabe);
+    }
+
+    #[test]
+    fn test_signature_trivia() {
+        assert_eq!(rule("a: b | c;"), rule("a :\n    b // first\n  | c ;"));
+        assert_eq!(rule("a: b if x -> y\n;"), rule("a: b if  x  ->  y\n;"));
+        assert_eq!(rule("a: empty | b;"), rule("a: ε | b;"));
+    }
+
+    #[test]
+    fn test_signature_changes() {
+        assert_ne!(rule("a: b c;"), rule("a: bc;"));
+        assert_ne!(rule("a: \"b c\";"), rule("a: \"b  c\";"));
+        assert_ne!(rule("a: b;"), rule("a: b*;"));
+        assert_ne!(rule("a: b;"), rule("c: b;"));
+        assert_ne!(rule("a: b[x];"), rule("a: b [x];"));
+    }
+}
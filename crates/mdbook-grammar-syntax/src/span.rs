@@ -0,0 +1,176 @@
+//! Helpers for the spans of syntax nodes, which are byte ranges in the code of
+//! a grammar block, and the [`SourceMap`] resolving them in a chapter.
+
+use crate::{SyntaxError, SyntaxNode, parse};
+use std::ops::Range;
+
+/// The smallest span covering both spans.
+pub fn join(a: &Range<usize>, b: &Range<usize>) -> Range<usize> {
+    a.start.min(b.start)..a.end.max(b.end)
+}
+
+/// Whether the `outer` span covers the `inner` one, including an empty span
+/// at its end.
+pub fn contains(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Move a span in the code of a block to the text containing the block,
+/// given the offset of the code in the text.
+pub fn translate(span: &Range<usize>, offset: usize) -> Range<usize> {
+    offset + span.start..offset + span.end
+}
+
+/// The offset of the code of a block in a chapter, given the offset of its
+/// opening fence, like ```` ```grammar ````. The code starts on the next line.
+pub fn code_offset(text: &str, fence: usize) -> usize {
+    text[fence..]
+        .find('\n')
+        .map_or(text.len(), |newline| fence + newline + 1)
+}
+
+/// Parse the grammar blocks of a chapter, given as their ranges in the text
+/// of the chapter, in order.
+///
+/// The spans of the trees are relative to their block; the [`SourceMap`] of
+/// the chapter, returned with them, resolves them to positions in the chapter.
+pub fn parse_book_blocks(
+    text: &str,
+    blocks: &[Range<usize>],
+) -> (Vec<SyntaxNode>, SourceMap) {
+    let roots = blocks
+        .iter()
+        .map(|range| parse(&text[range.clone()]))
+        .collect();
+    (roots, SourceMap::new(text, blocks))
+}
+
+/// The positions of the lines and grammar blocks of a chapter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceMap {
+    /// The range of each block in the chapter, in order.
+    blocks: Vec<Range<usize>>,
+    /// The offset where each line of the chapter starts.
+    lines: Vec<usize>,
+}
+
+impl SourceMap {
+    /// Map the text of a chapter and its blocks, given as their ranges in the
+    /// text, in order.
+    pub fn new(text: &str, blocks: &[Range<usize>]) -> Self {
+        debug_assert!(blocks.is_sorted_by_key(|range| range.start));
+        let newlines = text.match_indices('\n').map(|(i, _)| i + 1);
+        Self {
+            blocks: blocks.to_vec(),
+            lines: std::iter::once(0).chain(newlines).collect(),
+        }
+    }
+
+    /// Resolve a span in a block to the chapter.
+    ///
+    /// Panics if there is no such block.
+    pub fn resolve(&self, block: usize, span: &Range<usize>) -> Range<usize> {
+        translate(span, self.blocks[block].start)
+    }
+
+    /// Find the block containing an offset of the chapter, with the offset
+    /// relative to it. The end of a block is part of it.
+    pub fn find(&self, offset: usize) -> Option<(usize, usize)> {
+        let block = self.blocks.partition_point(|range| range.end < offset);
+        let range = self.blocks.get(block)?;
+        (range.start <= offset).then(|| (block, offset - range.start))
+    }
+
+    /// The line of an offset of the chapter, from 1.
+    pub fn line(&self, offset: usize) -> usize {
+        self.lines.partition_point(|&start| start <= offset)
+    }
+
+    /// The errors of the parsed blocks, with their spans in the chapter.
+    pub fn errors<'a>(
+        &self,
+        roots: &'a [SyntaxNode],
+    ) -> Vec<(Range<usize>, &'a SyntaxError)> {
+        let mut errors = Vec::new();
+        for (block, root) in roots.iter().enumerate() {
+            let mut stack = vec![root];
+            while let Some(node) = stack.pop() {
+                if let Some(error) = node.as_error() {
+                    errors.push((self.resolve(block, node.span()), error));
+                }
+                stack.extend(node.children().rev());
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_contains() {
+        assert_eq!(join(&(2..4), &(6..9)), 2..9);
+        assert_eq!(join(&(6..9), &(2..4)), 2..9);
+        assert_eq!(join(&(2..9), &(4..6)), 2..9);
+
+        assert!(contains(&(2..9), &(4..6)));
+        assert!(contains(&(2..9), &(9..9)));
+        assert!(!contains(&(2..9), &(8..10)));
+        assert!(!contains(&(4..6), &(2..9)));
+    }
+
+    #[test]
+    fn test_translate() {
+        let text = "# Title\n\n```grammar\na: b %;\n```\n";
+        let offset = code_offset(text, 9);
+        assert_eq!(offset, 20);
+
+        let root = parse(&text[offset..text.len() - 4]);
+        let error = root.diagnostics().remove(0);
+        let span = translate(&error.span, offset);
+        assert_eq!(&text[span], "%");
+        assert_eq!(code_offset("```grammar", 0), 10);
+    }
+
+    const TEXT: &str =
+        "# Title\n\n```grammar\na: b;\n```\n\n```grammar\nc: %;\n```\n";
+    const BLOCKS: &[Range<usize>] = &[20..26, 42..48];
+
+    #[test]
+    fn test_source_map_find() {
+        let map = SourceMap::new(TEXT, BLOCKS);
+        assert_eq!(map.find(5), None);
+        assert_eq!(map.find(20), Some((0, 0)));
+        assert_eq!(map.find(26), Some((0, 6)));
+        assert_eq!(map.find(30), None);
+        assert_eq!(map.find(45), Some((1, 3)));
+        assert_eq!(map.find(50), None);
+        assert_eq!(SourceMap::new("", &[]).find(0), None);
+    }
+
+    #[test]
+    fn test_source_map_line() {
+        let map = SourceMap::new(TEXT, BLOCKS);
+        assert_eq!(map.line(0), 1);
+        assert_eq!(map.line(7), 1);
+        assert_eq!(map.line(8), 2);
+        assert_eq!(map.line(20), 4);
+        assert_eq!(map.line(TEXT.len()), 10);
+        assert_eq!(SourceMap::new("", &[]).line(0), 1);
+    }
+
+    #[test]
+    fn test_source_map_errors() {
+        let (roots, map) = parse_book_blocks(TEXT, BLOCKS);
+        assert_eq!(roots.len(), 2);
+        assert!(!roots[0].erroneous());
+
+        let errors = map.errors(&roots);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(&TEXT[errors[0].0.clone()], "%");
+        assert_eq!(map.find(errors[0].0.start), Some((1, 3)));
+        assert_eq!(map.line(errors[0].0.start), 8);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{SyntaxKind, SyntaxNode};
+
+/// Move the comments around the rules and pragmas of a grammar into them,
+/// keeping the tree lossless.
+///
+/// The parser leaves comments as siblings of the rules. Here, the comments
+/// right above a rule, without a blank line in between, become its leading
+/// trivia, and a comment after its `;` on the same line its trailing trivia.
+/// Other comments stay in the root.
+pub fn attach_trivia(root: &SyntaxNode) -> SyntaxNode {
+    let nodes = root.children().collect::<Vec<_>>();
+    let mut children = Vec::new();
+    let mut i = 0;
+    while i < nodes.len() {
+        let node = nodes[i];
+        i += 1;
+        if !matches!(node.kind(), SyntaxKind::Rule | SyntaxKind::Pragma) {
+            children.push(node.clone());
+            continue;
+        }
+
+        let mut inner = children.split_off(leading_start(&children));
+        inner.extend(node.children().cloned());
+        let end = trailing_end(&nodes, i);
+        inner.extend(nodes[i..end].iter().map(|&n| n.clone()));
+        i = end;
+        children.push(SyntaxNode::inner(node.kind(), inner));
+    }
+    SyntaxNode::inner(root.kind(), children)
+}
+
+/// The index of the first comment of the run ending the nodes, or their
+/// length if they don't end with comments.
+fn leading_start(nodes: &[SyntaxNode]) -> usize {
+    let mut start = nodes.len();
+    for (i, node) in nodes.iter().enumerate().rev() {
+        match node.kind() {
+            | SyntaxKind::Comment | SyntaxKind::DocComment => start = i,
+            | SyntaxKind::Whitespace if !is_blank_line(node.text()) => {},
+            | _ => break,
+        }
+    }
+    start
+}
+
+/// The index after a comment on the same line as the end of a rule, or the
+/// index of the rule end if there is none.
+fn trailing_end(nodes: &[&SyntaxNode], start: usize) -> usize {
+    let mut end = start;
+    if nodes.get(end).is_some_and(|n| {
+        n.kind() == SyntaxKind::Whitespace && !n.text().contains('\n')
+    }) {
+        end += 1;
+    }
+    if nodes
+        .get(end)
+        .is_some_and(|n| n.kind() == SyntaxKind::Comment)
+    {
+        end + 1
+    } else {
+        start
+    }
+}
+
+fn is_blank_line(whitespace: &str) -> bool {
+    whitespace.matches('\n').count() > 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn text(node: &SyntaxNode) -> String {
+        if node.children().len() == 0 {
+            return node.text().to_string();
+        }
+        node.children().map(text).collect()
+    }
+
+    fn kinds(node: &SyntaxNode) -> Vec<SyntaxKind> {
+        node.children().map(SyntaxNode::kind).collect()
+    }
+
+    #[test]
+    fn test_attach_trivia() {
+        let input =
+            "// a\n\n// b\n/// doc\na: b; // c\n%start a; /* d */\n// e";
+        let root = attach_trivia(&parse(input));
+        assert_eq!(text(&root), input);
+        assert_eq!(kinds(&root), [
+            SyntaxKind::Comment,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Rule,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Pragma,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Comment,
+        ]);
+
+        let rule = root.children().nth(2).unwrap();
+        assert_eq!(*rule.span(), 6..29);
+        assert_eq!(&kinds(rule)[..4], [
+            SyntaxKind::Comment,
+            SyntaxKind::Whitespace,
+            SyntaxKind::DocComment,
+            SyntaxKind::Whitespace,
+        ]);
+        assert_eq!(rule.children().last().unwrap().text(), "// c");
+
+        let pragma = root.children().nth(4).unwrap();
+        assert_eq!(pragma.children().last().unwrap().text(), "/* d */");
+    }
+
+    #[test]
+    fn test_attach_trivia_between_rules() {
+        // A comment after a rule on the same line stays with it.
+        let root = attach_trivia(&parse("a: b; // c\nd: e;"));
+        assert_eq!(kinds(&root), [
+            SyntaxKind::Rule,
+            SyntaxKind::Whitespace,
+            SyntaxKind::Rule
+        ]);
+        assert_eq!(root.children().next().unwrap().span().end, 10);
+        assert_eq!(root.children().last().unwrap().span().start, 11);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::SyntaxNode;
+
+/// Hooks called while walking a syntax tree with [`walk`].
+///
+/// Implementations match on the kind of the node, or cast it to a typed view
+/// of the [`ast`](crate::ast) module, to handle the kinds they care about.
+pub trait Visitor<'a> {
+    /// Called before the children of a node. Return `false` to skip them.
+    fn enter(&mut self, node: &'a SyntaxNode) -> bool {
+        let _ = node;
+        true
+    }
+
+    /// Called after the children of a node, unless they were skipped.
+    fn leave(&mut self, node: &'a SyntaxNode) {
+        let _ = node;
+    }
+}
+
+/// Walk a node and its descendants in document order.
+pub fn walk<'a>(node: &'a SyntaxNode, visitor: &mut impl Visitor<'a>) {
+    if visitor.enter(node) {
+        for child in node.children() {
+            walk(child, visitor);
+        }
+        visitor.leave(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SyntaxKind, parse};
+
+    /// Record the kinds entered and left, skipping the children of groups.
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Visitor<'_> for Recorder {
+        fn enter(&mut self, node: &SyntaxNode) -> bool {
+            self.0.push(format!("+{:?}", node.kind()));
+            node.kind() != SyntaxKind::Group
+        }
+
+        fn leave(&mut self, node: &SyntaxNode) {
+            self.0.push(format!("-{:?}", node.kind()));
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let root = parse("a: (b);");
+        let mut recorder = Recorder::default();
+        walk(&root, &mut recorder);
+        assert_eq!(recorder.0, [
+            "+Root",
+            "+Rule",
+            "+Identifier",
+            "-Identifier",
+            "+Colon",
+            "-Colon",
+            "+Definition",
+            "+Group",
+            "-Definition",
+            "+SemiColon",
+            "-SemiColon",
+            "-Rule",
+            "-Root",
+        ]);
+    }
+
+    #[test]
+    fn test_walk_collect() {
+        struct Names<'a>(Vec<&'a str>);
+
+        impl<'a> Visitor<'a> for Names<'a> {
+            fn enter(&mut self, node: &'a SyntaxNode) -> bool {
+                if node.kind() == SyntaxKind::Identifier {
+                    self.0.push(node.text());
+                }
+                true
+            }
+        }
+
+        let root = parse("a: b (c | d);");
+        let mut names = Names(Vec::new());
+        walk(&root, &mut names);
+        assert_eq!(names.0, ["a", "b", "c", "d"]);
+    }
+}
@@ -6,7 +6,7 @@ use mdbook::{
     BookItem,
     book::{Book, Chapter},
 };
-use mdbook_grammar_runner::run;
+use mdbook_grammar_runner::{Config, run};
 use std::path::PathBuf;
 
 #[derive(Debug)]
@@ -36,5 +36,5 @@ impl MyBook {
 }
 
 fuzz_target!(|book: MyBook| {
-    run(&mut book.into_book(), "/");
+    run(&mut book.into_book(), &Config::default());
 });
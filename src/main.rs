@@ -1,29 +1,195 @@
-use mdbook::preprocess::{CmdPreprocessor, PreprocessorContext};
-use mdbook_grammar_runner::run;
+use mdbook::{MDBook, preprocess::CmdPreprocessor};
+use mdbook_grammar_runner::{
+    Config, GrammarError, GrammarPreprocessor, Server, Snapshot, registry,
+};
+use std::{fs, io};
 
 fn main() {
-    let mut args = std::env::args().skip(1);
+    if let Err(err) = try_main() {
+        for diagnostic in err.diagnostics() {
+            eprintln!("{diagnostic}");
+        }
+        eprintln!("error: {err}");
+        std::process::exit(err.exit_code());
+    }
+}
+
+fn try_main() -> Result<(), GrammarError> {
+    let mut preprocessor = GrammarPreprocessor::new();
+    let mut args = std::env::args().skip(1).peekable();
+
+    if args.next_if(|arg| arg == "--timings").is_some() {
+        preprocessor = preprocessor.timings(true);
+    }
 
     match args.next().as_deref() {
-        | Some("supports") => return,
+        | Some("supports") => return Ok(()),
+        | Some("rules") => return rules(args),
+        | Some("snapshot") => return snapshot(args),
+        | Some("verify") => return verify(args),
+        | Some("serve-daemon") => {
+            let mut server = Server::new();
+            return Ok(server.serve(io::stdin().lock(), io::stdout())?);
+        },
         | Some(arg) => {
-            eprintln!("unknown argument: {arg}");
-            std::process::exit(1);
+            return Err(GrammarError::Usage(
+                format!("unknown argument: {arg}").into(),
+            ));
         },
         | None => {},
     }
 
-    let (context, mut book) =
-        CmdPreprocessor::parse_input(std::io::stdin()).unwrap();
-    run(&mut book, get_site_url(&context).unwrap_or("/"));
-    serde_json::to_writer(std::io::stdout(), &book).unwrap();
+    let (context, book) = CmdPreprocessor::parse_input(std::io::stdin())
+        .map_err(|err| GrammarError::MdbookProtocol(err.to_string().into()))?;
+    let (book, diagnostics) = preprocessor.process(&context, book)?;
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+
+    serde_json::to_writer(std::io::stdout(), &book).map_err(io::Error::from)?;
+    Ok(())
+}
+
+/// Print the rules of the book, for `rules [--json] [--filter <glob>] [dir]`.
+fn rules(mut args: impl Iterator<Item = String>) -> Result<(), GrammarError> {
+    let mut json = false;
+    let mut filter = None;
+    let mut dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            | "--json" => json = true,
+            | "--filter" => filter = args.next(),
+            | _ if dir.is_none() => dir = Some(arg),
+            | _ => {
+                return Err(GrammarError::Usage(
+                    format!("unknown argument: {arg}").into(),
+                ));
+            },
+        }
+    }
+
+    let book = MDBook::load(dir.as_deref().unwrap_or("."))
+        .map_err(|err| GrammarError::MdbookProtocol(err.to_string().into()))?;
+    let config = Config::from_book(&book.config, "html");
+    let entries = registry(&book.book, &config)
+        .into_iter()
+        .filter(|entry| filter.as_deref().is_none_or(|f| glob(f, &entry.name)));
+
+    if json {
+        let entries = entries.map(|entry| entry.to_json()).collect();
+        let value = serde_json::Value::Array(entries);
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+    } else {
+        for entry in entries {
+            let anchor =
+                entry.anchor.map(|a| format!("#{a}")).unwrap_or_default();
+            println!(
+                "{}\t{}{anchor}\t{}",
+                entry.name, entry.chapter, entry.references
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a snapshot of the grammar, for
+/// `snapshot [-o <file>] [--version <version>] [dir]`.
+fn snapshot(
+    mut args: impl Iterator<Item = String>,
+) -> Result<(), GrammarError> {
+    let mut output = None;
+    let mut version = None;
+    let mut dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            | "-o" | "--output" => output = args.next(),
+            | "--version" => version = args.next(),
+            | _ if dir.is_none() => dir = Some(arg),
+            | _ => {
+                return Err(GrammarError::Usage(
+                    format!("unknown argument: {arg}").into(),
+                ));
+            },
+        }
+    }
+
+    let mut snapshot = load_snapshot(dir.as_deref())?;
+    snapshot.version = version.map(Into::into);
+    let json = serde_json::to_string_pretty(&snapshot.to_json()).unwrap();
+    match output {
+        | Some(path) => fs::write(path, json + "\n")?,
+        | None => println!("{json}"),
+    }
+
+    Ok(())
+}
+
+/// Check the grammar against a snapshot, for `verify <snapshot> [dir]`.
+fn verify(mut args: impl Iterator<Item = String>) -> Result<(), GrammarError> {
+    let Some(path) = args.next() else {
+        return Err(GrammarError::Usage("missing snapshot to verify".into()));
+    };
+    let dir = args.next();
+    if let Some(arg) = args.next() {
+        return Err(GrammarError::Usage(
+            format!("unknown argument: {arg}").into(),
+        ));
+    }
+
+    let value = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(io::Error::from)?;
+    let Some(expected) = Snapshot::from_json(&value) else {
+        return Err(GrammarError::Usage(
+            format!("not a grammar snapshot: {path}").into(),
+        ));
+    };
+
+    let diagnostics = expected.verify(&load_snapshot(dir.as_deref())?);
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(GrammarError::Strict { diagnostics })
+    }
+}
+
+/// Take a snapshot of the book in the directory, or the current one.
+fn load_snapshot(dir: Option<&str>) -> Result<Snapshot, GrammarError> {
+    let book = MDBook::load(dir.unwrap_or("."))
+        .map_err(|err| GrammarError::MdbookProtocol(err.to_string().into()))?;
+    let config = Config::from_book(&book.config, "html");
+    Ok(Snapshot::of(&book.book, &config))
 }
 
-fn get_site_url(context: &PreprocessorContext) -> Option<&str> {
-    context
-        .config
-        .get("output")?
-        .get("html")?
-        .get("site-url")?
-        .as_str()
+/// Whether the name matches the pattern, where `*` matches any text and `?`
+/// any character.
+fn glob(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    // Backtrack to the last star on mismatch.
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            | Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            },
+            | Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            | _ => match star {
+                | Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                },
+                | None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
 }